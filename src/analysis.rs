@@ -1,4 +1,4 @@
-use crate::chess::{ChessMove, Color, Position};
+use crate::chess::{ChessError, ChessMove, Color, Position};
 use crate::pgn::move_to_san;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -42,7 +42,7 @@ pub struct GameAnalysis {
 }
 
 impl GameAnalysis {
-    pub fn from_history(start_fen: &str, moves: &[ChessMove]) -> Result<Self, String> {
+    pub fn from_history(start_fen: &str, moves: &[ChessMove]) -> Result<Self, ChessError> {
         let mut position = Position::from_fen(start_fen.trim())?;
         let mut items = Vec::with_capacity(moves.len());
 
@@ -186,7 +186,7 @@ impl GameAnalysis {
 }
 
 pub fn move_accuracy_from_loss_cp(loss_cp: i32) -> f32 {
-    (100.0 - (loss_cp.max(0).min(1000) as f32 / 10.0)).clamp(0.0, 100.0)
+    (100.0 - (loss_cp.clamp(0, 1000) as f32 / 10.0)).clamp(0.0, 100.0)
 }
 
 pub fn format_cp(score: Option<i32>) -> String {
@@ -250,7 +250,7 @@ mod tests {
 
     #[test]
     fn builds_analysis_jobs_for_history() {
-        let mut position = Position::startpos();
+        let position = Position::startpos();
         let chess_move = position.parse_uci_move("e2e4").unwrap();
         let analysis = GameAnalysis::from_history(STARTPOS_FEN, &[chess_move]).unwrap();
         assert_eq!(analysis.items.len(), 1);
@@ -260,7 +260,7 @@ mod tests {
 
     #[test]
     fn computes_accuracy_from_centipawn_loss() {
-        let mut position = Position::startpos();
+        let position = Position::startpos();
         let chess_move = position.parse_uci_move("e2e4").unwrap();
         let mut analysis = GameAnalysis::from_history(STARTPOS_FEN, &[chess_move]).unwrap();
         let jobs = analysis.jobs();
@@ -6,15 +6,16 @@ use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStdin, Command, Stdio};
 use std::sync::mpsc::{self, Receiver};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use eframe::egui;
-use rchess::analysis::{format_accuracy, format_cp, format_cp_value, AnalysisJob, GameAnalysis};
-use rchess::chess::{square_name, ChessMove, Color, DrawReason, PieceKind, Position, STARTPOS_FEN};
+use rchess::analysis::{format_accuracy, format_cp, format_cp_value, AnalysisJob, AnalysisStage, GameAnalysis};
+use rchess::chess::{square_name, ChessMove, Color, DrawReason, Game, PieceKind, Position, STARTPOS_FEN};
 use rchess::experience::{append_game_to_experience_book, ExperienceConfig};
 use rchess::matchplay::{uci_position_command_from_history, EngineMatchController, SearchLimit, UciEngineSlot};
 use rchess::pgn::{export_pgn, move_to_san, parse_pgn, position_after_moves};
 use rchess::search::evaluate_tactical_for_side_to_move;
+use rchess::search_method::{search_method, search_methods, MethodOptionKind, MethodOptions, DEFAULT_SEARCH_METHOD};
 
 fn main() -> eframe::Result<()> {
     if env::args().any(|arg| arg == "--engine-mode") {
@@ -73,10 +74,15 @@ impl PiecePreset {
 
 
 const BUILTIN_ENGINE_PRESET_INDEX: usize = 0;
+/// Deepest `go depth` the GUI sliders offer.
+const MAX_GUI_SEARCH_DEPTH: u8 = 20;
 
 #[derive(Clone)]
 struct EnginePresetSettings {
     search_depth: u8,
+    search_method: String,
+    /// Values of the options the search methods declare.
+    method_options: MethodOptions,
     deterministic_multithread: bool,
     max_threads: u16,
     granularity: u16,
@@ -96,6 +102,8 @@ impl EnginePresetSettings {
         let experience = ExperienceConfig::default();
         Self {
             search_depth: 4,
+            search_method: DEFAULT_SEARCH_METHOD.to_string(),
+            method_options: MethodOptions::default(),
             deterministic_multithread: default_threads > 1,
             max_threads: default_threads,
             granularity: 1,
@@ -113,7 +121,8 @@ impl EnginePresetSettings {
     }
 
     fn normalized(mut self) -> Self {
-        self.search_depth = self.search_depth.clamp(1, 8);
+        self.search_depth = self.search_depth.clamp(1, MAX_GUI_SEARCH_DEPTH);
+        self.search_method = normalized_search_method(&self.search_method);
         self.max_threads = self.max_threads.clamp(1, 32);
         self.granularity = self.granularity.clamp(1, 16);
         self.hash_mb = self.hash_mb.clamp(1, 4096);
@@ -127,6 +136,8 @@ impl EnginePresetSettings {
     fn from_gui(app: &RChessGui) -> Self {
         Self {
             search_depth: app.search_depth,
+            search_method: app.search_method.clone(),
+            method_options: app.method_options.clone(),
             deterministic_multithread: app.deterministic_multithread,
             max_threads: app.planned_threads,
             granularity: app.search_granularity,
@@ -303,9 +314,10 @@ impl PieceGlyphSet {
 }
 
 struct RChessGui {
-    position: Position,
+    game: Game,
     fen_input: String,
-    game_start_fen: String,
+    sanitize_fen: bool,
+    chess960_index: u16,
     pgn_text: String,
     pgn_path: String,
     selected: Option<u8>,
@@ -316,7 +328,6 @@ struct RChessGui {
     animate_moves: bool,
     move_animation_ms: u32,
     promotion_request: Option<PromotionRequest>,
-    played_moves: Vec<ChessMove>,
     redo_moves: Vec<ChessMove>,
     history_view_ply: Option<usize>,
     player_color: Color,
@@ -335,6 +346,8 @@ struct RChessGui {
     engine_log: Vec<String>,
     last_engine_info: String,
     last_engine_score_cp: Option<i32>,
+    search_method: String,
+    method_options: MethodOptions,
     deterministic_multithread: bool,
     planned_threads: u16,
     search_granularity: u16,
@@ -366,6 +379,7 @@ struct RChessGui {
     match_white_options: String,
     match_black_options: String,
     match_max_plies: u32,
+    match_chess960: bool,
     match_controller: Option<EngineMatchController>,
     match_white_engine: Option<UciEngine>,
     match_black_engine: Option<UciEngine>,
@@ -377,12 +391,18 @@ struct RChessGui {
     match_log: Vec<String>,
     match_pgn_text: String,
     analysis_depth: u8,
+    /// `MultiPV` sent to the analysis engine.
+    analysis_lines: usize,
     analysis: Option<GameAnalysis>,
     analysis_engine: Option<UciEngine>,
     analysis_rx: Option<Receiver<String>>,
     analysis_jobs: VecDeque<AnalysisJob>,
     analysis_current_job: Option<AnalysisJob>,
     analysis_last_score_cp: Option<i32>,
+    /// Engine lines of the running job, by `multipv` rank.
+    analysis_current_lines: Vec<String>,
+    /// Engine lines before each analysed move, by item index.
+    analysis_best_lines: Vec<Vec<String>>,
     analysis_running: bool,
     analysis_status: String,
     analysis_log: Vec<String>,
@@ -440,7 +460,7 @@ impl MoveAnimation {
 
 impl RChessGui {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let position = Position::startpos();
+        let game = Game::new(Position::startpos());
         let detected_stockfish10 = detect_stockfish10_path().unwrap_or_default();
         let stockfish10_status = if detected_stockfish10.is_empty() {
             "Stockfish 10 binary not detected yet".to_string()
@@ -468,10 +488,11 @@ impl RChessGui {
             .unwrap_or_else(|| EnginePreset::builtin_default(default_threads));
         let mut app = Self {
             fen_input: STARTPOS_FEN.to_string(),
-            game_start_fen: STARTPOS_FEN.to_string(),
+            sanitize_fen: false,
+            chess960_index: 518,
             pgn_text: String::new(),
             pgn_path: String::new(),
-            position,
+            game,
             selected: None,
             selected_moves: Vec::new(),
             dragging_from: None,
@@ -480,7 +501,6 @@ impl RChessGui {
             animate_moves: true,
             move_animation_ms: 180,
             promotion_request: None,
-            played_moves: Vec::new(),
             redo_moves: Vec::new(),
             history_view_ply: None,
             player_color: Color::White,
@@ -499,6 +519,8 @@ impl RChessGui {
             engine_log: Vec::new(),
             last_engine_info: String::new(),
             last_engine_score_cp: None,
+            search_method: DEFAULT_SEARCH_METHOD.to_string(),
+            method_options: MethodOptions::default(),
             deterministic_multithread: default_threads > 1,
             planned_threads: default_threads,
             search_granularity: 1,
@@ -530,6 +552,7 @@ impl RChessGui {
             match_white_options: String::new(),
             match_black_options: String::new(),
             match_max_plies: 160,
+            match_chess960: false,
             match_controller: None,
             match_white_engine: None,
             match_black_engine: None,
@@ -541,12 +564,15 @@ impl RChessGui {
             match_log: Vec::new(),
             match_pgn_text: String::new(),
             analysis_depth: 3,
+            analysis_lines: 1,
             analysis: None,
             analysis_engine: None,
             analysis_rx: None,
             analysis_jobs: VecDeque::new(),
             analysis_current_job: None,
             analysis_last_score_cp: None,
+            analysis_current_lines: Vec::new(),
+            analysis_best_lines: Vec::new(),
             analysis_running: false,
             analysis_status: "Analysis is idle".to_string(),
             analysis_log: Vec::new(),
@@ -575,24 +601,33 @@ impl RChessGui {
     }
 
     fn new_game(&mut self) {
+        self.start_new_game(Position::startpos(), "New game".to_string());
+    }
+
+    fn new_chess960_game(&mut self) {
+        match Position::chess960(self.chess960_index) {
+            Ok(position) => self.start_new_game(position, format!("New Chess960 game #{}", self.chess960_index)),
+            Err(error) => self.engine_status = error.to_string(),
+        }
+    }
+
+    fn start_new_game(&mut self, position: Position, status: String) {
         self.stop_analysis("Analysis stopped by new game");
         self.stop_engine_match("Engine match stopped by new game");
-        self.position = Position::startpos();
-        self.fen_input = STARTPOS_FEN.to_string();
-        self.game_start_fen = STARTPOS_FEN.to_string();
+        self.game = Game::new(position);
+        self.fen_input = self.game.position().to_fen();
         self.selected = None;
         self.selected_moves.clear();
         self.dragging_from = None;
         self.drag_pointer = None;
         self.move_animation = None;
         self.promotion_request = None;
-        self.played_moves.clear();
         self.redo_moves.clear();
         self.history_view_ply = None;
         self.pgn_text.clear();
         self.pending_engine = false;
         self.last_engine_score_cp = None;
-        self.engine_status = "New game".to_string();
+        self.engine_status = status;
         self.send_to_engine("ucinewgame");
         self.refresh_game_status();
 
@@ -604,10 +639,14 @@ impl RChessGui {
     fn load_fen(&mut self) {
         self.stop_analysis("Analysis stopped by FEN load");
         self.stop_engine_match("Engine match stopped by FEN load");
-        match Position::from_fen(self.fen_input.trim()) {
-            Ok(position) => {
-                self.position = position;
-                self.game_start_fen = self.position.to_fen();
+        let loaded = if self.sanitize_fen {
+            Position::from_fen_sanitized(self.fen_input.trim())
+        } else {
+            Position::from_fen_strict(self.fen_input.trim()).map(|position| (position, Vec::new()))
+        };
+        match loaded {
+            Ok((position, repaired)) => {
+                self.game = Game::new(position);
                 self.pgn_text.clear();
                 self.selected = None;
                 self.selected_moves.clear();
@@ -615,12 +654,17 @@ impl RChessGui {
                 self.drag_pointer = None;
                 self.move_animation = None;
                 self.promotion_request = None;
-                self.played_moves.clear();
                 self.redo_moves.clear();
                 self.history_view_ply = None;
                 self.pending_engine = false;
                 self.last_engine_score_cp = None;
-                self.engine_status = "FEN loaded".to_string();
+                self.engine_status = if repaired.is_empty() {
+                    "FEN loaded".to_string()
+                } else {
+                    let repaired = repaired.iter().map(ToString::to_string).collect::<Vec<_>>();
+                    format!("FEN loaded, repaired: {}", repaired.join("; "))
+                };
+                self.fen_input = self.game.position().to_fen();
                 self.refresh_game_status();
             }
             Err(error) => {
@@ -631,7 +675,7 @@ impl RChessGui {
 
     fn export_pgn_to_text(&mut self) {
         let result = self.current_result();
-        match export_pgn(&self.game_start_fen, &self.played_moves, &result) {
+        match export_pgn(&self.game.start_fen(), self.game.moves(), &result) {
             Ok(text) => {
                 self.pgn_text = text;
                 self.engine_status = "PGN exported".to_string();
@@ -683,17 +727,15 @@ impl RChessGui {
     fn load_pgn_from_text(&mut self) {
         self.stop_analysis("Analysis stopped by PGN load");
         self.stop_engine_match("Engine match stopped by PGN load");
-        match parse_pgn(&self.pgn_text).and_then(|game| {
-            let position = position_after_moves(&game.start_fen, &game.moves)?;
-            Ok((game, position))
+        match parse_pgn(&self.pgn_text).map_err(|error| error.to_string()).and_then(|pgn| {
+            let game = Game::from_moves(&pgn.start_fen, &pgn.chess_moves()).map_err(|error| error.to_string())?;
+            Ok((pgn.result, game))
         }) {
-            Ok((game, position)) => {
-                self.game_start_fen = game.start_fen;
-                self.played_moves = game.moves;
+            Ok((result, game)) => {
+                self.game = game;
                 self.redo_moves.clear();
                 self.history_view_ply = None;
-                self.position = position;
-                self.fen_input = self.position.to_fen();
+                self.fen_input = self.game.position().to_fen();
                 self.selected = None;
                 self.selected_moves.clear();
                 self.dragging_from = None;
@@ -702,7 +744,7 @@ impl RChessGui {
                 self.promotion_request = None;
                 self.pending_engine = false;
                 self.last_engine_score_cp = None;
-                self.engine_status = format!("PGN loaded, result {}", game.result);
+                self.engine_status = format!("PGN loaded, result {result}");
                 self.refresh_game_status();
 
                 if self.should_auto_engine_move() {
@@ -716,31 +758,23 @@ impl RChessGui {
     }
 
     fn current_result(&self) -> String {
-        if self.position.is_checkmate() {
-            match self.position.side_to_move() {
-                Color::White => "0-1".to_string(),
-                Color::Black => "1-0".to_string(),
-            }
-        } else if self.current_draw_reason().is_some() {
-            "1/2-1/2".to_string()
-        } else {
-            "*".to_string()
-        }
+        self.game.result().to_string()
     }
 
     fn current_draw_reason(&self) -> Option<DrawReason> {
-        Position::draw_reason_from_history(&self.game_start_fen, &self.played_moves)
-            .ok()
-            .flatten()
+        self.game.draw_reason()
     }
 
     fn current_position_command(&self) -> String {
-        uci_position_command_from_history(&self.game_start_fen, &self.played_moves)
+        uci_position_command_from_history(&self.game.start_fen(), self.game.moves())
     }
 
     fn draw_reason_for_ply(&self, ply: usize) -> Option<DrawReason> {
-        let clamped = ply.min(self.played_moves.len());
-        Position::draw_reason_from_history(&self.game_start_fen, &self.played_moves[..clamped])
+        let clamped = ply.min(self.game.moves().len());
+        if clamped == self.game.moves().len() {
+            return self.game.draw_reason();
+        }
+        Position::draw_reason_from_history(&self.game.start_fen(), &self.game.moves()[..clamped])
             .ok()
             .flatten()
     }
@@ -782,16 +816,17 @@ impl RChessGui {
         {
             return false;
         }
-        let Some(piece) = self.position.piece_at(square) else {
+        let Some(piece) = self.game.position().piece_at(square) else {
             return false;
         };
-        if piece.color != self.position.side_to_move() {
+        if piece.color != self.game.position().side_to_move() {
             return false;
         }
 
         self.selected = Some(square);
         self.selected_moves = self
-            .position
+            .game
+            .position()
             .legal_moves()
             .into_iter()
             .filter(|chess_move| chess_move.from == square)
@@ -811,7 +846,7 @@ impl RChessGui {
             self.move_animation = None;
             return;
         }
-        let Some(piece) = self.position.piece_at(chess_move.to) else {
+        let Some(piece) = self.game.position().piece_at(chess_move.to) else {
             self.move_animation = None;
             return;
         };
@@ -838,7 +873,7 @@ impl RChessGui {
         self.auto_engine
             && !self.pending_engine
             && !self.match_running
-            && self.position.side_to_move() != self.player_color
+            && self.game.position().side_to_move() != self.player_color
             && self.current_result() == "*"
     }
 
@@ -857,6 +892,17 @@ impl RChessGui {
             .filter(|chess_move| chess_move.to == to)
             .collect();
         if candidates.is_empty() {
+            // In Chess960 the king may also be dropped on its castling
+            // destination instead of on the rook.
+            let castling = self
+                .game
+                .position()
+                .parse_uci_move(&format!("{}{}", square_name(from), square_name(to)))
+                .filter(|chess_move| self.game.position().is_castling(*chess_move));
+            if let Some(chess_move) = castling {
+                self.apply_user_move(chess_move);
+                return true;
+            }
             return false;
         }
 
@@ -880,9 +926,9 @@ impl RChessGui {
     }
 
     fn apply_user_move(&mut self, chess_move: ChessMove) {
-        match self.position.make_legal_move(chess_move) {
+        match self.game.push(chess_move) {
             Ok(()) => {
-                self.record_applied_move(chess_move);
+                self.record_applied_move();
                 self.start_move_animation(chess_move);
                 self.clear_selection();
                 self.promotion_request = None;
@@ -893,31 +939,16 @@ impl RChessGui {
                 }
             }
             Err(error) => {
-                self.engine_status = error;
+                self.engine_status = error.to_string();
             }
         }
     }
 
-    fn record_applied_move(&mut self, chess_move: ChessMove) {
-        self.played_moves.push(chess_move);
+    fn record_applied_move(&mut self) {
         self.redo_moves.clear();
         self.history_view_ply = None;
         self.last_engine_score_cp = None;
-        self.fen_input = self.position.to_fen();
-    }
-
-    fn rebuild_position_from_history(&mut self) -> Result<(), String> {
-        let mut position = Position::from_fen(&self.game_start_fen)?;
-        for chess_move in &self.played_moves {
-            position.make_legal_move(*chess_move)?;
-        }
-        self.position = position;
-        self.fen_input = self.position.to_fen();
-        self.history_view_ply = None;
-        self.clear_selection();
-        self.promotion_request = None;
-        self.refresh_game_status();
-        Ok(())
+        self.fen_input = self.game.position().to_fen();
     }
 
     fn undo_move(&mut self) {
@@ -925,16 +956,18 @@ impl RChessGui {
             self.engine_status = "Cannot undo while an engine is thinking".to_string();
             return;
         }
-        let Some(chess_move) = self.played_moves.pop() else {
+        let Some(chess_move) = self.game.pop() else {
             self.engine_status = "Nothing to undo".to_string();
             return;
         };
         self.redo_moves.push(chess_move);
         self.move_animation = None;
-        match self.rebuild_position_from_history() {
-            Ok(()) => self.engine_status = format!("Undid {}", chess_move.to_uci()),
-            Err(error) => self.engine_status = format!("Undo error: {error}"),
-        }
+        self.fen_input = self.game.position().to_fen();
+        self.history_view_ply = None;
+        self.clear_selection();
+        self.promotion_request = None;
+        self.refresh_game_status();
+        self.engine_status = format!("Undid {}", chess_move.to_uci());
     }
 
     fn redo_move(&mut self) {
@@ -946,13 +979,12 @@ impl RChessGui {
             self.engine_status = "Nothing to redo".to_string();
             return;
         };
-        match self.position.make_legal_move(chess_move) {
+        match self.game.push(chess_move) {
             Ok(()) => {
-                self.played_moves.push(chess_move);
                 self.history_view_ply = None;
                 self.last_engine_score_cp = None;
                 self.start_move_animation(chess_move);
-                self.fen_input = self.position.to_fen();
+                self.fen_input = self.game.position().to_fen();
                 self.clear_selection();
                 self.promotion_request = None;
                 self.refresh_game_status();
@@ -1030,6 +1062,7 @@ impl RChessGui {
         self.send_primary_engine_resource_options();
         self.pending_engine = true;
         self.engine_status = format!("Engine is thinking at depth {}", self.search_depth);
+        self.send_to_engine(&uci_chess960_option(self.game.position().is_chess960()));
         self.send_to_engine(&self.current_position_command());
         self.send_to_engine(&format!("go depth {}", self.search_depth));
     }
@@ -1067,6 +1100,19 @@ impl RChessGui {
         }
     }
 
+    /// Sends the `Clear Hash` button option to the running engine, internal
+    /// or external.
+    fn clear_engine_hash(&mut self) {
+        if self.engine.is_none() {
+            self.resource_settings_status = "No running engine child; its hash starts empty anyway".to_string();
+            return;
+        }
+        self.send_to_engine("setoption name Clear Hash");
+        if self.engine.is_some() {
+            self.resource_settings_status = "Sent Clear Hash to the running engine".to_string();
+        }
+    }
+
     fn send_primary_engine_resource_options(&mut self) {
         if self.engine_backend != EngineBackend::RChess {
             self.resource_settings_status = "Resource settings are only applied to the internal rchess backend for now".to_string();
@@ -1087,7 +1133,8 @@ impl RChessGui {
         match send_rchess_all_options_from_settings(engine, &settings) {
             Ok(()) => {
                 self.resource_settings_status = format!(
-                    "Applied to internal rchess: deterministic_multithread={}, max_threads={}, granularity={}, Hash={} MB, AvoidDraws={}, RiskLevel={}, HumanityLevel={}, experience_book={}",
+                    "Applied to internal rchess: SearchMethod={}, deterministic_multithread={}, max_threads={}, granularity={}, Hash={} MB, AvoidDraws={}, RiskLevel={}, HumanityLevel={}, experience_book={}",
+                    settings.search_method,
                     settings.deterministic_multithread,
                     settings.max_threads,
                     settings.granularity,
@@ -1148,7 +1195,11 @@ impl RChessGui {
             return;
         }
         if line.starts_with("info ") {
-            self.last_engine_info = compact_uci_info_line(&line);
+            // The summary line after the last depth has no `pv`; keep the line
+            // from the last completed depth on screen instead.
+            if uci_info_has_pv(&line) || !uci_info_has_pv(&self.last_engine_info) {
+                self.last_engine_info = compact_uci_info_line(&line, self.game.position());
+            }
             if let Some(score_cp) = parse_uci_score_cp(&line) {
                 self.last_engine_score_cp = Some(score_cp);
             }
@@ -1166,10 +1217,10 @@ impl RChessGui {
             return;
         }
 
-        match self.position.parse_uci_move(move_text) {
-            Some(chess_move) => match self.position.make_legal_move(chess_move) {
+        match self.game.position().parse_uci_move(move_text) {
+            Some(chess_move) => match self.game.push(chess_move) {
                 Ok(()) => {
-                    self.record_applied_move(chess_move);
+                    self.record_applied_move();
                     self.start_move_animation(chess_move);
                     self.engine_status = format!("Engine played {move_text}");
                     self.clear_selection();
@@ -1186,16 +1237,6 @@ impl RChessGui {
     }
 
 
-    fn experience_config(&self) -> ExperienceConfig {
-        ExperienceConfig {
-            enabled: self.experience_book_enabled,
-            path: self.experience_book_path.clone(),
-            min_games: self.experience_min_games,
-            score_tolerance_cp: self.experience_score_tolerance_cp,
-        }
-        .normalized()
-    }
-
     fn personality_risk_level(&self) -> i32 {
         normalized_personality_axis(self.personality_risk)
     }
@@ -1222,6 +1263,8 @@ impl RChessGui {
     fn apply_engine_settings_to_gui(&mut self, settings: &EnginePresetSettings) {
         let settings = settings.clone().normalized();
         self.search_depth = settings.search_depth;
+        self.search_method = settings.search_method;
+        self.method_options = settings.method_options;
         self.deterministic_multithread = settings.deterministic_multithread;
         self.planned_threads = settings.max_threads;
         self.search_granularity = settings.granularity;
@@ -1401,16 +1444,16 @@ impl RChessGui {
             self.experience_status = "No engine match is available to export".to_string();
             return;
         };
-        if controller.played_moves.is_empty() {
+        if controller.played_moves().is_empty() {
             self.experience_status = "Current match has no moves".to_string();
             return;
         }
         let analysis = self.analysis.as_ref();
         match append_game_to_experience_book(
             &self.experience_book_path,
-            &controller.start_fen,
-            &controller.played_moves,
-            &controller.result,
+            &controller.start_fen(),
+            controller.played_moves(),
+            controller.result(),
             &controller.white.name,
             &controller.black.name,
             analysis,
@@ -1473,8 +1516,12 @@ impl RChessGui {
             .with_args(black_command.args.clone())
             .with_limit(black_limit);
 
-        let start_fen = self.position.to_fen();
-        let controller = match EngineMatchController::from_fen(&start_fen, white_slot, black_slot) {
+        let controller = if self.match_chess960 {
+            EngineMatchController::chess960(self.chess960_index, white_slot, black_slot)
+        } else {
+            EngineMatchController::from_fen(&self.game.position().to_fen(), white_slot, black_slot)
+        };
+        let controller = match controller {
             Ok(controller) => controller,
             Err(error) => {
                 self.match_status = format!("Match start FEN error: {error}");
@@ -1505,6 +1552,12 @@ impl RChessGui {
             self.match_status = format!("Black match UCI startup error: {error}");
             return;
         }
+        if let Some(command) = controller.chess960_setoption_command() {
+            if let Err(error) = white_engine.send(&command).and_then(|_| black_engine.send(&command)) {
+                self.match_status = format!("Match UCI_Chess960 option error: {error}");
+                return;
+            }
+        }
 
         if let Err(error) = send_match_engine_startup_options(
             &mut white_engine,
@@ -1525,8 +1578,8 @@ impl RChessGui {
             return;
         }
 
-        self.game_start_fen = start_fen;
-        self.played_moves.clear();
+        self.game = controller.game.clone();
+        self.fen_input = self.game.position().to_fen();
         self.redo_moves.clear();
         self.history_view_ply = None;
         self.match_controller = Some(controller);
@@ -1593,20 +1646,20 @@ impl RChessGui {
             return;
         };
 
-        if controller.result != "*" || controller.position.is_checkmate() || controller.position.is_stalemate() {
+        if controller.game.is_over() {
             self.match_running = false;
-            self.match_status = format!("Match finished: {}", controller.result);
+            self.match_status = format!("Match finished: {}", controller.result());
             self.update_match_pgn_text();
             return;
         }
-        if controller.played_moves.len() as u32 >= self.match_max_plies {
+        if controller.played_moves().len() as u32 >= self.match_max_plies {
             self.match_running = false;
             self.match_status = format!("Match stopped after {} plies", self.match_max_plies);
             self.update_match_pgn_text();
             return;
         }
 
-        let color = controller.position.side_to_move();
+        let color = controller.position().side_to_move();
         let position_command = controller.position_command();
         let go_command = controller.current_go_command();
         controller.start_thinking();
@@ -1673,7 +1726,11 @@ impl RChessGui {
         }
         if line == "uciok" || line == "readyok" || line.starts_with("info ") {
             if line.starts_with("info ") {
-                self.match_status = format!("{} {}", color_name(color), compact_uci_info_line(&line));
+                let info = match &self.match_controller {
+                    Some(controller) => compact_uci_info_line(&line, controller.position()),
+                    None => line.clone(),
+                };
+                self.match_status = format!("{} {}", color_name(color), info);
             }
             return;
         }
@@ -1688,26 +1745,22 @@ impl RChessGui {
         self.match_waiting_for = None;
 
         let result = if let Some(controller) = self.match_controller.as_mut() {
-            controller.record_bestmove(&line).map(|_| {
-                (
-                    controller.position.clone(),
-                    controller.played_moves.clone(),
-                    controller.result.clone(),
-                )
-            })
+            controller
+                .record_bestmove(&line)
+                .map_err(|error| error.to_string())
+                .map(|_| (controller.game.clone(), controller.result()))
         } else {
             Err("No match controller".to_string())
         };
 
         match result {
-            Ok((position, moves, result)) => {
-                let last_match_move = moves.last().copied();
-                self.position = position;
-                self.played_moves = moves;
+            Ok((game, result)) => {
+                let last_match_move = game.last_move();
+                self.game = game;
                 self.redo_moves.clear();
                 self.history_view_ply = None;
                 self.last_engine_score_cp = None;
-                self.fen_input = self.position.to_fen();
+                self.fen_input = self.game.position().to_fen();
                 if let Some(chess_move) = last_match_move {
                     self.start_move_animation(chess_move);
                 }
@@ -1766,13 +1819,11 @@ impl RChessGui {
                 return;
             }
         };
-        match position_after_moves(&start_fen, &moves) {
-            Ok(position) => {
-                self.game_start_fen = start_fen.clone();
-                self.played_moves = moves.clone();
+        match Game::from_moves(&start_fen, &moves) {
+            Ok(game) => {
+                self.game = game;
                 self.redo_moves.clear();
-                self.position = position;
-                self.fen_input = self.position.to_fen();
+                self.fen_input = self.game.position().to_fen();
                 self.history_view_ply = None;
                 self.refresh_game_status();
             }
@@ -1805,18 +1856,14 @@ impl RChessGui {
         };
 
         if is_internal_rchess {
-            let _ = send_rchess_resource_options(
-                &mut engine,
-                self.deterministic_multithread,
-                self.planned_threads,
-                self.search_granularity,
-                self.planned_hash_mb,
-                false,
-                0,
-                0,
-            );
+            let mut settings = self.current_gui_engine_settings();
+            settings.avoid_draws = false;
+            settings.risk = 0.0;
+            settings.humanity = 0.0;
+            let _ = send_rchess_resource_options_from_settings(&mut engine, &settings);
         }
 
+        self.analysis_best_lines = vec![Vec::new(); analysis.items.len()];
         self.analysis = Some(analysis);
         self.analysis_jobs = jobs;
         self.analysis_engine = Some(engine);
@@ -1827,16 +1874,19 @@ impl RChessGui {
         self.analysis_log.clear();
         self.analysis_status = format!("Analysing with {label} at depth {}", self.analysis_depth);
         self.send_to_analysis_engine("uci");
+        self.send_to_analysis_engine(&uci_chess960_option(self.game.position().is_chess960()));
+        self.send_to_analysis_engine(&format!("setoption name MultiPV value {}", self.analysis_lines));
         self.send_to_analysis_engine("isready");
         self.request_next_analysis_job();
     }
 
     fn analysis_source_history(&self) -> Result<(String, Vec<ChessMove>), String> {
         if !self.pgn_text.trim().is_empty() {
-            let game = parse_pgn(&self.pgn_text)?;
-            Ok((game.start_fen, game.moves))
+            let game = parse_pgn(&self.pgn_text).map_err(|error| error.to_string())?;
+            let moves = game.chess_moves();
+            Ok((game.start_fen, moves))
         } else {
-            Ok((self.game_start_fen.clone(), self.played_moves.clone()))
+            Ok((self.game.start_fen(), self.game.moves().to_vec()))
         }
     }
 
@@ -1876,6 +1926,7 @@ impl RChessGui {
             return;
         };
         self.analysis_last_score_cp = None;
+        self.analysis_current_lines.clear();
         self.send_to_analysis_engine(&format!("position fen {}", job.fen));
         self.send_to_analysis_engine(&format!("go depth {}", self.analysis_depth));
         let ply = job.item_index + 1;
@@ -1903,9 +1954,26 @@ impl RChessGui {
         }
 
         if line.starts_with("info ") {
-            if let Some(score_cp) = parse_uci_score_cp(&line) {
+            let Some(score_cp) = parse_uci_score_cp(&line) else {
+                return;
+            };
+            let rank = parse_uci_multipv(&line).unwrap_or(1);
+            if rank == 1 {
                 self.analysis_last_score_cp = Some(score_cp);
             }
+            let before_move = self
+                .analysis_current_job
+                .as_ref()
+                .filter(|job| job.stage == AnalysisStage::BeforeMove)
+                .and_then(|job| Position::from_fen(&job.fen).ok());
+            if let (Some(position), Some(pv_index)) = (before_move, line.find(" pv ")) {
+                let moves: Vec<&str> = line[pv_index + 4..].split_whitespace().collect();
+                if self.analysis_current_lines.len() < rank {
+                    self.analysis_current_lines.resize(rank, String::new());
+                }
+                self.analysis_current_lines[rank - 1] =
+                    format!("{:>7} {}", format_cp_value(score_cp), pv_to_san(&position, &moves));
+            }
             return;
         }
         if line == "uciok" || line == "readyok" || line.starts_with("id ") {
@@ -1924,6 +1992,11 @@ impl RChessGui {
         if let Some(analysis) = self.analysis.as_mut() {
             analysis.set_score(&job, score_cp);
         }
+        if job.stage == AnalysisStage::BeforeMove {
+            if let Some(lines) = self.analysis_best_lines.get_mut(job.item_index) {
+                *lines = std::mem::take(&mut self.analysis_current_lines);
+            }
+        }
         self.request_next_analysis_job();
     }
 
@@ -1937,12 +2010,12 @@ impl RChessGui {
     }
 
     fn refresh_game_status(&mut self) {
-        let side = color_name(self.position.side_to_move());
-        self.game_status = if self.position.is_checkmate() {
+        let side = color_name(self.game.position().side_to_move());
+        self.game_status = if self.game.position().is_checkmate() {
             format!("Checkmate. {side} has no legal move")
         } else if let Some(reason) = self.current_draw_reason() {
             format!("Draw by {}", reason.label())
-        } else if self.position.is_in_check(self.position.side_to_move()) {
+        } else if self.game.position().is_in_check(self.game.position().side_to_move()) {
             format!("{side} to move, in check")
         } else {
             format!("{side} to move")
@@ -1950,16 +2023,16 @@ impl RChessGui {
     }
 
     fn history_view_ply(&self) -> usize {
-        self.history_view_ply.unwrap_or(self.played_moves.len()).min(self.played_moves.len())
+        self.history_view_ply.unwrap_or(self.game.moves().len()).min(self.game.moves().len())
     }
 
     fn is_history_view_live(&self) -> bool {
-        self.history_view_ply().min(self.played_moves.len()) == self.played_moves.len()
+        self.history_view_ply().min(self.game.moves().len()) == self.game.moves().len()
     }
 
     fn history_view_label(&self) -> String {
         let ply = self.history_view_ply();
-        let total = self.played_moves.len();
+        let total = self.game.moves().len();
         if ply == total {
             format!("Live position, ply {ply}/{total}")
         } else {
@@ -1968,19 +2041,16 @@ impl RChessGui {
     }
 
     fn position_at_ply(&self, ply: usize) -> Result<Position, String> {
-        let mut position = Position::from_fen(&self.game_start_fen)?;
-        for chess_move in self.played_moves.iter().take(ply.min(self.played_moves.len())) {
-            position.make_legal_move(*chess_move)?;
-        }
-        Ok(position)
+        let moves = &self.game.moves()[..ply.min(self.game.moves().len())];
+        position_after_moves(&self.game.start_fen(), moves).map_err(|error| error.to_string())
     }
 
     fn display_position(&self) -> Position {
-        self.position_at_ply(self.history_view_ply()).unwrap_or_else(|_| self.position.clone())
+        self.position_at_ply(self.history_view_ply()).unwrap_or_else(|_| self.game.position().clone())
     }
 
     fn navigate_history_to(&mut self, ply: usize) {
-        let total = self.played_moves.len();
+        let total = self.game.moves().len();
         let clamped = ply.min(total);
         self.history_view_ply = if clamped == total { None } else { Some(clamped) };
         self.clear_selection();
@@ -2004,11 +2074,11 @@ impl RChessGui {
     }
 
     fn history_to_live(&mut self) {
-        self.navigate_history_to(self.played_moves.len());
+        self.navigate_history_to(self.game.moves().len());
     }
 
     fn handle_history_keyboard(&mut self, ctx: &egui::Context) {
-        if ctx.wants_keyboard_input() {
+        if ctx.egui_wants_keyboard_input() {
             return;
         }
         let previous = ctx.input(|input| input.key_pressed(egui::Key::ArrowLeft));
@@ -2064,27 +2134,12 @@ impl RChessGui {
 
 
     fn san_move_rows(&self) -> Vec<String> {
-        let mut position = match Position::from_fen(&self.game_start_fen) {
-            Ok(position) => position,
-            Err(_) => {
-                return self
-                    .played_moves
-                    .chunks(2)
-                    .enumerate()
-                    .map(|(index, pair)| {
-                        let white_move = pair.first().map(|chess_move| chess_move.to_uci()).unwrap_or_default();
-                        let black_move = pair.get(1).map(|chess_move| chess_move.to_uci()).unwrap_or_default();
-                        format!("{}. {:<8} {}", index + 1, white_move, black_move)
-                    })
-                    .collect();
-            }
-        };
-
-        let mut move_number = start_fullmove_number(&self.game_start_fen);
+        let mut position = self.game.start_position().clone();
+        let mut move_number = position.fullmove_number();
         let mut current_row = String::new();
         let mut rows = Vec::new();
 
-        for chess_move in &self.played_moves {
+        for chess_move in self.game.moves() {
             let side = position.side_to_move();
             let san = match move_to_san(&position, *chess_move) {
                 Ok(san) => san,
@@ -2148,13 +2203,13 @@ impl RChessGui {
                 self.history_previous();
             }
             if ui
-                .add_enabled(self.history_view_ply() < self.played_moves.len(), egui::Button::new(">"))
+                .add_enabled(self.history_view_ply() < self.game.moves().len(), egui::Button::new(">"))
                 .clicked()
             {
                 self.history_next();
             }
             if ui
-                .add_enabled(self.history_view_ply() < self.played_moves.len(), egui::Button::new(">|"))
+                .add_enabled(self.history_view_ply() < self.game.moves().len(), egui::Button::new(">|"))
                 .clicked()
             {
                 self.history_to_live();
@@ -2212,6 +2267,9 @@ impl RChessGui {
                 if ui.button("New game").clicked() {
                     self.new_game();
                 }
+                if ui.button(format!("New Chess960 game #{}", self.chess960_index)).clicked() {
+                    self.new_chess960_game();
+                }
                 if ui.button("Export PGN to text").clicked() {
                     self.export_pgn_to_text();
                 }
@@ -2237,7 +2295,7 @@ impl RChessGui {
                     self.request_engine_move();
                 }
                 if ui
-                    .add_enabled(!self.pending_engine && !self.match_running && !self.played_moves.is_empty(), egui::Button::new("Undo"))
+                    .add_enabled(!self.pending_engine && !self.match_running && !self.game.moves().is_empty(), egui::Button::new("Undo"))
                     .clicked()
                 {
                     self.undo_move();
@@ -2348,6 +2406,9 @@ impl RChessGui {
             if ui.button("New").clicked() {
                 self.new_game();
             }
+            if ui.button("New 960").clicked() {
+                self.new_chess960_game();
+            }
             if ui
                 .add_enabled(!self.pending_engine && !self.match_running, egui::Button::new("Engine"))
                 .clicked()
@@ -2355,7 +2416,7 @@ impl RChessGui {
                 self.request_engine_move();
             }
             if ui
-                .add_enabled(!self.pending_engine && !self.match_running && !self.played_moves.is_empty(), egui::Button::new("Undo"))
+                .add_enabled(!self.pending_engine && !self.match_running && !self.game.moves().is_empty(), egui::Button::new("Undo"))
                 .clicked()
             {
                 self.undo_move();
@@ -2368,7 +2429,15 @@ impl RChessGui {
             }
         });
 
-        ui.add(egui::Slider::new(&mut self.search_depth, 1..=8).text("Search depth"));
+        ui.horizontal(|ui| {
+            ui.label("Chess960 #");
+            ui.add(egui::DragValue::new(&mut self.chess960_index).range(0..=959));
+            if ui.button("Random 960").clicked() {
+                self.chess960_index = random_chess960_index();
+                self.new_chess960_game();
+            }
+        });
+        ui.add(egui::Slider::new(&mut self.search_depth, 1..=MAX_GUI_SEARCH_DEPTH).text("Search depth"));
         ui.checkbox(&mut self.auto_engine, "Auto engine reply");
         ui.checkbox(&mut self.flipped, "Flip board");
         egui::ComboBox::from_id_salt("left_player_color")
@@ -2390,9 +2459,11 @@ impl RChessGui {
                 self.load_fen();
             }
             if ui.button("Copy FEN").clicked() {
-                self.fen_input = self.position.to_fen();
+                self.fen_input = self.game.position().to_fen();
                 ui.ctx().copy_text(self.fen_input.clone());
             }
+            ui.checkbox(&mut self.sanitize_fen, "Sanitize")
+                .on_hover_text("Drop impossible castling rights and en-passant squares instead of rejecting the FEN");
         });
 
         ui.separator();
@@ -2841,6 +2912,8 @@ impl RChessGui {
         ui.separator();
         ui.heading("Search resources");
         ui.label("These settings are active for the internal rchess UCI backend. They are intentionally deterministic: root moves are split in a fixed order and the shared transposition table uses atomic replace-by-depth+age entries.");
+        search_method_selector(ui, "gui_search_method", &mut self.search_method);
+        method_option_editors(ui, "gui_method_option", &self.search_method, &mut self.method_options);
         ui.checkbox(&mut self.deterministic_multithread, "deterministic_multithread");
         ui.add(egui::Slider::new(&mut self.planned_threads, 1..=32).text("max_threads"));
         ui.add(egui::Slider::new(&mut self.search_granularity, 1..=16).text("granularity"));
@@ -2863,9 +2936,14 @@ impl RChessGui {
             ui.label("Tolerance cp");
             ui.add(egui::DragValue::new(&mut self.experience_score_tolerance_cp).range(0..=1000).speed(5.0));
         });
-        if ui.button("Apply to running rchess child").clicked() {
-            self.send_primary_engine_resource_options();
-        }
+        ui.horizontal_wrapped(|ui| {
+            if ui.button("Apply to running rchess child").clicked() {
+                self.send_primary_engine_resource_options();
+            }
+            if ui.button("Clear Hash").clicked() {
+                self.clear_engine_hash();
+            }
+        });
         ui.label(&self.resource_settings_status);
         ui.label(&self.experience_status);
     }
@@ -3001,7 +3079,8 @@ impl RChessGui {
         ui.heading("Game analysis");
         ui.label(&self.analysis_status);
         ui.small("Flow: paste or open a PGN below, then press Start analysis. If the PGN buffer is empty, the current board history is analysed instead.");
-        ui.add(egui::Slider::new(&mut self.analysis_depth, 1..=8).text("Analysis depth"));
+        ui.add(egui::Slider::new(&mut self.analysis_depth, 1..=MAX_GUI_SEARCH_DEPTH).text("Analysis depth"));
+        ui.add(egui::Slider::new(&mut self.analysis_lines, 1..=5).text("Engine lines (MultiPV)"));
         ui.horizontal_wrapped(|ui| {
             if ui
                 .add_enabled(!self.analysis_running, egui::Button::new("Start analysis"))
@@ -3093,6 +3172,16 @@ impl RChessGui {
                         }
                     }
                 });
+            let best_lines = current_view_ply
+                .checked_sub(1)
+                .and_then(|index| self.analysis_best_lines.get(index))
+                .filter(|lines| !lines.is_empty());
+            if let Some(lines) = best_lines {
+                ui.label(egui::RichText::new(format!("Engine lines before ply {current_view_ply}")).strong());
+                for line in lines {
+                    ui.monospace(line);
+                }
+            }
             if let Some(ply) = requested_ply {
                 self.navigate_history_to(ply);
             }
@@ -3161,12 +3250,12 @@ impl RChessGui {
         ui.label("Per-side power. Movetime > 0 overrides depth for that side.");
         ui.horizontal(|ui| {
             ui.label("White power");
-            ui.add(egui::Slider::new(&mut self.match_white_depth, 1..=8).text("depth"));
+            ui.add(egui::Slider::new(&mut self.match_white_depth, 1..=MAX_GUI_SEARCH_DEPTH).text("depth"));
             ui.add(egui::DragValue::new(&mut self.match_white_movetime_ms).range(0..=60_000).speed(50.0).prefix("ms "));
         });
         ui.horizontal(|ui| {
             ui.label("Black power");
-            ui.add(egui::Slider::new(&mut self.match_black_depth, 1..=8).text("depth"));
+            ui.add(egui::Slider::new(&mut self.match_black_depth, 1..=MAX_GUI_SEARCH_DEPTH).text("depth"));
             ui.add(egui::DragValue::new(&mut self.match_black_movetime_ms).range(0..=60_000).speed(50.0).prefix("ms "));
         });
         ui.collapsing("Per-side UCI options", |ui| {
@@ -3182,6 +3271,13 @@ impl RChessGui {
             ui.label("Max plies");
             ui.add(egui::DragValue::new(&mut self.match_max_plies).range(2..=600).speed(2.0));
         });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.match_chess960, "Chess960 start #");
+            ui.add_enabled(
+                self.match_chess960,
+                egui::DragValue::new(&mut self.chess960_index).range(0..=959),
+            );
+        });
         ui.checkbox(&mut self.avoid_draws, "Internal rchess should avoid draw loops");
         ui.horizontal_wrapped(|ui| {
             if ui
@@ -3330,7 +3426,7 @@ impl RChessGui {
             egui::pos2(inner.left(), footer_top),
             egui::pos2(inner.right(), inner.bottom()),
         );
-        ui.allocate_ui_at_rect(footer_rect, |ui| {
+        ui.scope_builder(egui::UiBuilder::new().max_rect(footer_rect), |ui| {
             ui.set_clip_rect(footer_rect);
             ui.horizontal_wrapped(|ui| {
                 ui.monospace(format!("{} | eval {}", self.history_view_label(), format_eval_cp_value(eval_cp)));
@@ -3374,109 +3470,6 @@ impl RChessGui {
         );
     }
 
-    fn show_board(&mut self, ui: &mut egui::Ui) {
-        ui.vertical(|ui| {
-            let display_position = self.display_position();
-            let eval_cp = self.display_eval_cp_white(&display_position);
-            let available_width = ui.available_width().max(320.0);
-            let available_height = ui.available_height().max(320.0);
-            let board_size = (available_width - 46.0)
-                .min(available_height - 74.0)
-                .clamp(260.0, 720.0);
-            let row_width = board_size + 38.0;
-            let leading_space = ((available_width - row_width) * 0.5).max(0.0);
-
-            ui.add_space(6.0);
-            ui.horizontal(|ui| {
-                ui.add_space(leading_space);
-                self.paint_evaluation_bar(ui, board_size, eval_cp);
-
-                let (rect, response) = ui.allocate_exact_size(
-                    egui::vec2(board_size, board_size),
-                    egui::Sense::click_and_drag(),
-                );
-
-                if self.is_history_view_live() && response.drag_started() {
-                    if let Some(pointer_pos) = response.interact_pointer_pos() {
-                        if let Some(square) = pointer_to_square(rect, pointer_pos, self.flipped) {
-                            if self.select_piece(square) {
-                                self.dragging_from = Some(square);
-                                self.drag_pointer = Some(pointer_pos);
-                            }
-                        }
-                    }
-                }
-
-                if self.dragging_from.is_some() {
-                    if let Some(pointer_pos) = response.interact_pointer_pos() {
-                        self.drag_pointer = Some(pointer_pos);
-                    }
-                }
-
-                self.paint_board(ui, rect, &display_position);
-
-                if response.drag_stopped() {
-                    if self.dragging_from.is_some() {
-                        if let Some(pointer_pos) = self.drag_pointer.or_else(|| response.interact_pointer_pos()) {
-                            if let Some(square) = pointer_to_square(rect, pointer_pos, self.flipped) {
-                                self.try_apply_selected_to(square);
-                            } else {
-                                self.clear_selection();
-                            }
-                        }
-                    }
-                    self.dragging_from = None;
-                    self.drag_pointer = None;
-                } else if self.is_history_view_live() && response.clicked() {
-                    if let Some(pointer_pos) = response.interact_pointer_pos() {
-                        if let Some(square) = pointer_to_square(rect, pointer_pos, self.flipped) {
-                            self.select_square(square);
-                        }
-                    }
-                }
-            });
-
-            ui.add_space(8.0);
-            ui.horizontal_wrapped(|ui| {
-                ui.monospace(format!("{} | eval {}", self.history_view_label(), format_eval_cp_value(eval_cp)));
-                if !self.is_history_view_live() && ui.button("Return live").clicked() {
-                    self.history_to_live();
-                }
-            });
-            egui::ScrollArea::horizontal()
-                .id_salt("display_fen_scroll")
-                .max_height(24.0)
-                .show(ui, |ui| {
-                    ui.monospace(display_position.to_fen());
-                });
-        });
-    }
-
-    fn paint_evaluation_bar(&self, ui: &mut egui::Ui, board_size: f32, score_cp: i32) {
-        ui.vertical_centered(|ui| {
-            let (rect, _) = ui.allocate_exact_size(egui::vec2(30.0, board_size), egui::Sense::hover());
-            let painter = ui.painter_at(rect);
-            painter.rect_filled(rect, 3.0, egui::Color32::from_rgb(20, 20, 20));
-
-            let clamped = score_cp.clamp(-1000, 1000) as f32;
-            let white_ratio = (0.5 + clamped / 2000.0).clamp(0.03, 0.97);
-            let white_height = rect.height() * white_ratio;
-            let white_rect = egui::Rect::from_min_max(
-                egui::pos2(rect.left(), rect.bottom() - white_height),
-                rect.right_bottom(),
-            );
-            painter.rect_filled(white_rect, 3.0, egui::Color32::from_rgb(235, 235, 225));
-            painter.rect_stroke(
-                rect,
-                3.0,
-                egui::Stroke::new(1.0, egui::Color32::from_rgb(90, 90, 90)),
-                egui::StrokeKind::Outside,
-            );
-            ui.add_space(4.0);
-            ui.monospace(format_eval_cp_value(score_cp));
-        });
-    }
-
     fn paint_board(&self, ui: &mut egui::Ui, rect: egui::Rect, display_position: &Position) {
         let painter = ui.painter_at(rect);
         let square_size = rect.width() / 8.0;
@@ -3505,7 +3498,7 @@ impl RChessGui {
                 );
                 let square_rect = egui::Rect::from_min_size(min, egui::vec2(square_size, square_size));
 
-                let is_light = (row + col) % 2 == 0;
+                let is_light = (row + col).is_multiple_of(2);
                 let mut fill = if is_light {
                     self.light_square_color
                 } else {
@@ -3620,7 +3613,7 @@ impl RChessGui {
     ) {
         let file = (b'a' + square % 8) as char;
         let rank = (b'1' + square / 8) as char;
-        let is_light = (row + col) % 2 == 0;
+        let is_light = (row + col).is_multiple_of(2);
         if !self.show_coordinates {
             return;
         }
@@ -3652,19 +3645,10 @@ impl RChessGui {
     }
 
     fn checked_king_square(&self, position: &Position) -> Option<u8> {
-        let color = position.side_to_move();
-        if !position.is_in_check(color) {
+        if position.checkers() == 0 {
             return None;
         }
-
-        for square in 0..64 {
-            if let Some(piece) = position.piece_at(square) {
-                if piece.color == color && piece.kind == PieceKind::King {
-                    return Some(square);
-                }
-            }
-        }
-        None
+        position.king_square(position.side_to_move())
     }
 
 }
@@ -3727,7 +3711,7 @@ impl eframe::App for RChessGui {
             None
         };
 
-        ui.allocate_ui_at_rect(left_rect, |ui| {
+        ui.scope_builder(egui::UiBuilder::new().max_rect(left_rect), |ui| {
             ui.set_clip_rect(left_rect);
             egui::Frame::group(ui.style()).show(ui, |ui| {
                 ui.set_width((left_width - 12.0).max(120.0));
@@ -3738,13 +3722,13 @@ impl eframe::App for RChessGui {
             });
         });
 
-        ui.allocate_ui_at_rect(center_rect, |ui| {
+        ui.scope_builder(egui::UiBuilder::new().max_rect(center_rect), |ui| {
             ui.set_clip_rect(center_rect);
             self.show_center_board_panel(ui, center_rect);
         });
 
         if let Some(right_rect) = right_rect {
-            ui.allocate_ui_at_rect(right_rect, |ui| {
+            ui.scope_builder(egui::UiBuilder::new().max_rect(right_rect), |ui| {
                 ui.set_clip_rect(right_rect);
                 egui::Frame::group(ui.style()).show(ui, |ui| {
                     ui.set_width((right_width - 12.0).max(140.0));
@@ -3836,6 +3820,20 @@ impl Drop for UciEngine {
     }
 }
 
+fn uci_chess960_option(enabled: bool) -> String {
+    format!("setoption name UCI_Chess960 value {enabled}")
+}
+
+/// Start position index for "Random 960"; the clock is random enough for
+/// picking a casual game and keeps the GUI free of an RNG dependency.
+fn random_chess960_index() -> u16 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+    (nanos % 960) as u16
+}
+
 fn normalized_personality_axis(value: f32) -> i32 {
     (value.clamp(-1.0, 1.0) * 100.0).round() as i32
 }
@@ -3853,7 +3851,7 @@ fn looks_like_image_path(value: &str) -> bool {
 }
 
 fn show_engine_preset_settings_editor(ui: &mut egui::Ui, settings: &mut EnginePresetSettings) {
-    settings.search_depth = settings.search_depth.clamp(1, 8);
+    settings.search_depth = settings.search_depth.clamp(1, MAX_GUI_SEARCH_DEPTH);
     settings.max_threads = settings.max_threads.clamp(1, 32);
     settings.granularity = settings.granularity.clamp(1, 16);
     settings.hash_mb = settings.hash_mb.clamp(1, 4096);
@@ -3861,7 +3859,9 @@ fn show_engine_preset_settings_editor(ui: &mut egui::Ui, settings: &mut EnginePr
     settings.humanity = settings.humanity.clamp(-1.0, 1.0);
 
     ui.heading("Search");
-    ui.add(egui::Slider::new(&mut settings.search_depth, 1..=8).text("default depth"));
+    ui.add(egui::Slider::new(&mut settings.search_depth, 1..=MAX_GUI_SEARCH_DEPTH).text("default depth"));
+    search_method_selector(ui, "preset_search_method", &mut settings.search_method);
+    method_option_editors(ui, "preset_method_option", &settings.search_method, &mut settings.method_options);
     ui.checkbox(&mut settings.deterministic_multithread, "deterministic_multithread");
     ui.add(egui::Slider::new(&mut settings.max_threads, 1..=32).text("max_threads"));
     ui.add(egui::Slider::new(&mut settings.granularity, 1..=16).text("granularity"));
@@ -3905,6 +3905,72 @@ fn show_engine_preset_settings_editor(ui: &mut egui::Ui, settings: &mut EnginePr
     );
 }
 
+fn search_method_selector(ui: &mut egui::Ui, id: &str, selected: &mut String) {
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected.as_str())
+        .show_ui(ui, |ui| {
+            for method in search_methods() {
+                ui.selectable_value(selected, method.name().to_string(), method.name())
+                    .on_hover_text(method.description());
+            }
+        });
+}
+
+/// Editors for the options the method called `method` declares.
+fn method_option_editors(ui: &mut egui::Ui, id: &str, method: &str, options: &mut MethodOptions) {
+    let Some(method) = search_method(method) else {
+        return;
+    };
+    for option in method.options() {
+        ui.horizontal(|ui| {
+            ui.label(option.name);
+            match option.kind {
+                MethodOptionKind::Spin { min, max, .. } => {
+                    let mut value = options.spin(option);
+                    if ui.add(egui::DragValue::new(&mut value).range(min..=max)).changed() {
+                        options.set(option.name, &value.to_string());
+                    }
+                }
+                MethodOptionKind::Combo { values, .. } => {
+                    let mut value = options.get(option);
+                    egui::ComboBox::from_id_salt((id, option.name))
+                        .selected_text(value.as_str())
+                        .show_ui(ui, |ui| {
+                            for known in values {
+                                ui.selectable_value(&mut value, known.to_string(), *known);
+                            }
+                        });
+                    options.set(option.name, &value);
+                }
+            }
+        });
+    }
+}
+
+/// Method options as one preset field: `Name=value` pairs split by `;`.
+fn method_options_to_field(options: &MethodOptions) -> String {
+    search_methods()
+        .iter()
+        .flat_map(|method| method.options())
+        .map(|option| format!("{}={}", option.name, options.get(option)))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Reads `method_options_to_field`; unknown names and bad values are skipped.
+fn method_options_from_field(field: &str) -> MethodOptions {
+    let mut options = MethodOptions::default();
+    for (name, value) in field.split(';').filter_map(|pair| pair.split_once('=')) {
+        options.set(name, value);
+    }
+    options
+}
+
+/// The registered spelling of `name`, or the default method if there is none.
+fn normalized_search_method(name: &str) -> String {
+    search_method(name).map_or(DEFAULT_SEARCH_METHOD, |method| method.name()).to_string()
+}
+
 fn save_engine_presets_to_path(path: &str, presets: &[EnginePreset]) -> Result<(), String> {
     fs::write(path, engine_presets_to_text(presets)).map_err(|error| error.to_string())
 }
@@ -3935,6 +4001,8 @@ fn engine_presets_to_text(presets: &[EnginePreset]) -> String {
             settings.experience_min_games.to_string(),
             settings.experience_score_tolerance_cp.to_string(),
             encode_preset_field(&settings.extra_uci_options),
+            settings.search_method.clone(),
+            encode_preset_field(&method_options_to_field(&settings.method_options)),
         ];
         text.push_str("preset\t");
         text.push_str(&fields.join("\t"));
@@ -3964,6 +4032,12 @@ fn parse_engine_presets_text(text: &str, default_threads: u16) -> Vec<EnginePres
         settings.experience_min_games = parse_u32_field(fields[14], settings.experience_min_games);
         settings.experience_score_tolerance_cp = parse_i32_field(fields[15], settings.experience_score_tolerance_cp);
         settings.extra_uci_options = decode_preset_field(fields[16]);
+        if let Some(method) = fields.get(17) {
+            settings.search_method = normalized_search_method(method);
+        }
+        if let Some(options) = fields.get(18) {
+            settings.method_options = method_options_from_field(&decode_preset_field(options));
+        }
 
         let name = decode_preset_field(fields[1]).trim().to_string();
         if name.is_empty() {
@@ -4074,30 +4148,6 @@ fn color_row(ui: &mut egui::Ui, label: &str, color: &mut egui::Color32) {
     });
 }
 
-fn send_rchess_resource_options(
-    engine: &mut UciEngine,
-    deterministic_multithread: bool,
-    max_threads: u16,
-    granularity: u16,
-    hash_mb: u32,
-    avoid_draws: bool,
-    risk_level: i32,
-    humanity_level: i32,
-) -> std::io::Result<()> {
-    engine.send(&format!(
-        "setoption name deterministic_multithread value {}",
-        deterministic_multithread
-    ))?;
-    engine.send(&format!("setoption name max_threads value {}", max_threads.max(1)))?;
-    engine.send(&format!("setoption name granularity value {}", granularity.max(1)))?;
-    engine.send(&format!("setoption name Hash value {}", hash_mb.max(1)))?;
-    engine.send(&format!("setoption name AvoidDraws value {}", avoid_draws))?;
-    engine.send(&format!("setoption name RiskLevel value {}", risk_level.clamp(-100, 100)))?;
-    engine.send(&format!("setoption name HumanityLevel value {}", humanity_level.clamp(-100, 100)))?;
-    Ok(())
-}
-
-
 fn send_rchess_experience_options(engine: &mut UciEngine, config: &ExperienceConfig) -> std::io::Result<()> {
     let config = config.clone().normalized();
     engine.send(&format!("setoption name UseExperienceBook value {}", config.enabled))?;
@@ -4116,16 +4166,24 @@ fn send_rchess_resource_options_from_settings(
     settings: &EnginePresetSettings,
 ) -> std::io::Result<()> {
     let settings = settings.clone().normalized();
-    send_rchess_resource_options(
-        engine,
-        settings.deterministic_multithread,
-        settings.max_threads,
-        settings.granularity,
-        settings.hash_mb,
-        settings.avoid_draws,
-        normalized_personality_axis(settings.risk),
-        normalized_personality_axis(settings.humanity),
-    )
+    engine.send(&format!("setoption name SearchMethod value {}", settings.search_method))?;
+    for option in search_methods().iter().flat_map(|method| method.options()) {
+        engine.send(&format!("setoption name {} value {}", option.name, settings.method_options.get(option)))?;
+    }
+    engine.send(&format!(
+        "setoption name deterministic_multithread value {}",
+        settings.deterministic_multithread
+    ))?;
+    engine.send(&format!("setoption name max_threads value {}", settings.max_threads.max(1)))?;
+    engine.send(&format!("setoption name granularity value {}", settings.granularity.max(1)))?;
+    engine.send(&format!("setoption name Hash value {}", settings.hash_mb.max(1)))?;
+    engine.send(&format!("setoption name AvoidDraws value {}", settings.avoid_draws))?;
+    engine.send(&format!("setoption name RiskLevel value {}", normalized_personality_axis(settings.risk)))?;
+    engine.send(&format!(
+        "setoption name HumanityLevel value {}",
+        normalized_personality_axis(settings.humanity)
+    ))?;
+    Ok(())
 }
 
 fn send_rchess_all_options_from_settings(
@@ -4204,13 +4262,25 @@ fn parse_uci_score_cp(line: &str) -> Option<i32> {
     }
 }
 
-fn compact_uci_info_line(line: &str) -> String {
+fn parse_uci_multipv(line: &str) -> Option<usize> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let index = parts.iter().position(|part| *part == "multipv")?;
+    parts.get(index + 1)?.parse().ok()
+}
+
+fn uci_info_has_pv(line: &str) -> bool {
+    line.split_whitespace().any(|token| token == "pv")
+}
+
+/// `depth`, `score`, `nodes` and the other numbers of an `info` line, with the
+/// `pv` rewritten as numbered SAN from `position`, the searched position.
+fn compact_uci_info_line(line: &str, position: &Position) -> String {
     let mut result = Vec::new();
     let parts: Vec<&str> = line.split_whitespace().collect();
     let mut index = 0;
     while index < parts.len() {
         match parts[index] {
-            "depth" | "seldepth" | "nodes" | "nps" | "time" => {
+            "depth" | "seldepth" | "multipv" | "nodes" | "nps" | "hashfull" | "time" => {
                 if let Some(value) = parts.get(index + 1) {
                     result.push(format!("{} {}", parts[index], value));
                     index += 2;
@@ -4227,7 +4297,7 @@ fn compact_uci_info_line(line: &str) -> String {
                 }
             }
             "pv" => {
-                let pv = parts[index + 1..].join(" ");
+                let pv = pv_to_san(position, &parts[index + 1..]);
                 if !pv.is_empty() {
                     result.push(format!("pv {pv}"));
                 }
@@ -4243,6 +4313,29 @@ fn compact_uci_info_line(line: &str) -> String {
     }
 }
 
+/// Numbered SAN for a UCI move list. From the first move that is not legal
+/// in `position` (for example after the board changed) the UCI text is kept.
+fn pv_to_san(position: &Position, moves: &[&str]) -> String {
+    let mut board = position.clone();
+    let mut result = Vec::with_capacity(moves.len());
+    for (index, text) in moves.iter().enumerate() {
+        let san = board
+            .parse_uci_move(text)
+            .and_then(|chess_move| Some((chess_move, move_to_san(&board, chess_move).ok()?)));
+        let Some((chess_move, san)) = san else {
+            result.extend(moves[index..].iter().map(|text| text.to_string()));
+            break;
+        };
+        match board.side_to_move() {
+            Color::White => result.push(format!("{}. {san}", board.fullmove_number())),
+            Color::Black if index == 0 => result.push(format!("{}... {san}", board.fullmove_number())),
+            Color::Black => result.push(san),
+        }
+        board.make_move(chess_move);
+    }
+    result.join(" ")
+}
+
 fn normalize_path_input(value: &str) -> String {
     value.trim().trim_matches('\"').trim_matches('\'').to_string()
 }
@@ -4402,7 +4495,7 @@ fn terminal_score_white(position: &Position) -> Option<i32> {
             Color::White => -GUI_MATE_SCORE_CP,
             Color::Black => GUI_MATE_SCORE_CP,
         })
-    } else if position.is_stalemate() || position.is_fifty_move_rule_draw() {
+    } else if position.is_stalemate() || position.is_draw_without_history() {
         Some(0)
     } else {
         None
@@ -4412,7 +4505,7 @@ fn terminal_score_white(position: &Position) -> Option<i32> {
 fn terminal_score_side_to_move(position: &Position) -> Option<i32> {
     if position.is_checkmate() {
         Some(-GUI_MATE_SCORE_CP)
-    } else if position.is_stalemate() || position.is_fifty_move_rule_draw() {
+    } else if position.is_stalemate() || position.is_draw_without_history() {
         Some(0)
     } else {
         None
@@ -4450,10 +4543,3 @@ fn score_for_fen_side_to_move(fen: &str) -> i32 {
         Err(_) => 0,
     }
 }
-
-fn start_fullmove_number(fen: &str) -> u32 {
-    fen.split_whitespace()
-        .nth(5)
-        .and_then(|value| value.parse::<u32>().ok())
-        .unwrap_or(1)
-}
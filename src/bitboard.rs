@@ -0,0 +1,209 @@
+//! Bitboard primitives for the rules core.
+//!
+//! Squares use the same `a1 = 0 .. h8 = 63` indexing as `chess.rs`. Leaper
+//! attacks and sliding rays are precomputed at compile time; sliding attacks
+//! are resolved with the classical "ray until first blocker" method, which is
+//! simple to read and fast enough for perft and search.
+
+pub type Bitboard = u64;
+
+pub const EMPTY: Bitboard = 0;
+pub const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+pub const FILE_H: Bitboard = FILE_A << 7;
+pub const RANK_1: Bitboard = 0xff;
+pub const RANK_2: Bitboard = RANK_1 << 8;
+pub const RANK_4: Bitboard = RANK_1 << 24;
+pub const RANK_5: Bitboard = RANK_1 << 32;
+pub const RANK_7: Bitboard = RANK_1 << 48;
+pub const RANK_8: Bitboard = RANK_1 << 56;
+/// a1, c1, ..., h8: the squares a dark-squared bishop can reach.
+pub const DARK_SQUARES: Bitboard = 0xaa55_aa55_aa55_aa55;
+
+const NORTH: usize = 0;
+const NORTH_EAST: usize = 1;
+const EAST: usize = 2;
+const SOUTH_EAST: usize = 3;
+const SOUTH: usize = 4;
+const SOUTH_WEST: usize = 5;
+const WEST: usize = 6;
+const NORTH_WEST: usize = 7;
+
+const RAY_STEPS: [(i32, i32); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+const BISHOP_RAYS: [usize; 4] = [NORTH_EAST, SOUTH_EAST, SOUTH_WEST, NORTH_WEST];
+const ROOK_RAYS: [usize; 4] = [NORTH, EAST, SOUTH, WEST];
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+
+static KNIGHT_ATTACKS: [Bitboard; 64] = leaper_table(&KNIGHT_STEPS);
+static KING_ATTACKS: [Bitboard; 64] = leaper_table(&KING_STEPS);
+static WHITE_PAWN_ATTACKS: [Bitboard; 64] = leaper_table(&[(-1, 1), (1, 1)]);
+static BLACK_PAWN_ATTACKS: [Bitboard; 64] = leaper_table(&[(-1, -1), (1, -1)]);
+static RAYS: [[Bitboard; 64]; 8] = ray_table();
+
+const fn leaper_table(steps: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let file = (square % 8) as i32;
+        let rank = (square / 8) as i32;
+        let mut step = 0;
+        while step < steps.len() {
+            let to_file = file + steps[step].0;
+            let to_rank = rank + steps[step].1;
+            if to_file >= 0 && to_file < 8 && to_rank >= 0 && to_rank < 8 {
+                table[square] |= 1 << (to_rank * 8 + to_file);
+            }
+            step += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+const fn ray_table() -> [[Bitboard; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let mut file = (square % 8) as i32 + RAY_STEPS[direction].0;
+            let mut rank = (square / 8) as i32 + RAY_STEPS[direction].1;
+            while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                table[direction][square] |= 1 << (rank * 8 + file);
+                file += RAY_STEPS[direction].0;
+                rank += RAY_STEPS[direction].1;
+            }
+            square += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+pub const fn square_bit(square: u8) -> Bitboard {
+    1 << square
+}
+
+pub fn knight_attacks(square: u8) -> Bitboard {
+    KNIGHT_ATTACKS[square as usize]
+}
+
+pub fn king_attacks(square: u8) -> Bitboard {
+    KING_ATTACKS[square as usize]
+}
+
+/// Squares attacked by a pawn of `white` (true) or black (false) standing on `square`.
+pub fn pawn_attacks(white: bool, square: u8) -> Bitboard {
+    if white {
+        WHITE_PAWN_ATTACKS[square as usize]
+    } else {
+        BLACK_PAWN_ATTACKS[square as usize]
+    }
+}
+
+pub fn bishop_attacks(square: u8, occupied: Bitboard) -> Bitboard {
+    BISHOP_RAYS
+        .iter()
+        .fold(EMPTY, |attacks, direction| attacks | ray_attacks(*direction, square, occupied))
+}
+
+pub fn rook_attacks(square: u8, occupied: Bitboard) -> Bitboard {
+    ROOK_RAYS
+        .iter()
+        .fold(EMPTY, |attacks, direction| attacks | ray_attacks(*direction, square, occupied))
+}
+
+pub fn queen_attacks(square: u8, occupied: Bitboard) -> Bitboard {
+    bishop_attacks(square, occupied) | rook_attacks(square, occupied)
+}
+
+fn ray_attacks(direction: usize, square: u8, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[direction][square as usize];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let first_blocker = if matches!(direction, NORTH | NORTH_EAST | EAST | NORTH_WEST) {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    ray ^ RAYS[direction][first_blocker as usize]
+}
+
+/// Squares strictly between two squares on a shared rank, file or diagonal;
+/// empty when the squares are not aligned.
+pub fn between(from: u8, to: u8) -> Bitboard {
+    for direction in RAYS.iter() {
+        let ray = direction[from as usize];
+        if ray & square_bit(to) != 0 {
+            return ray & !direction[to as usize] & !square_bit(to);
+        }
+    }
+    EMPTY
+}
+
+/// Index of the lowest set square. The board must not be empty.
+pub fn lsb(board: Bitboard) -> u8 {
+    debug_assert!(board != 0);
+    board.trailing_zeros() as u8
+}
+
+pub fn squares(board: Bitboard) -> Squares {
+    Squares(board)
+}
+
+/// Iterator over the set squares of a bitboard, from a1 towards h8.
+#[derive(Clone, Copy, Debug)]
+pub struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = lsb(self.0);
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaper_tables_respect_board_edges() {
+        assert_eq!(knight_attacks(0).count_ones(), 2);
+        assert_eq!(knight_attacks(27).count_ones(), 8);
+        assert_eq!(king_attacks(63).count_ones(), 3);
+        assert_eq!(pawn_attacks(true, 8), square_bit(17));
+        assert_eq!(pawn_attacks(false, 15), square_bit(6));
+    }
+
+    #[test]
+    fn sliding_attacks_stop_at_first_blocker() {
+        let occupied = square_bit(3) | square_bit(24) | square_bit(36);
+        let rook = rook_attacks(0, occupied);
+        assert_eq!(squares(rook).collect::<Vec<_>>(), vec![1, 2, 3, 8, 16, 24]);
+        let bishop = bishop_attacks(0, occupied);
+        assert_eq!(squares(bishop).collect::<Vec<_>>(), vec![9, 18, 27, 36]);
+        assert_eq!(queen_attacks(0, occupied), rook | bishop);
+    }
+
+    #[test]
+    fn between_follows_shared_rays() {
+        assert_eq!(squares(between(0, 3)).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(squares(between(63, 27)).collect::<Vec<_>>(), vec![36, 45, 54]);
+        assert_eq!(between(0, 1), EMPTY);
+        assert_eq!(between(0, 17), EMPTY);
+    }
+}
@@ -1,5 +1,12 @@
 use std::fmt;
 
+use crate::bitboard::{
+    between, bishop_attacks, king_attacks, knight_attacks, lsb, pawn_attacks, queen_attacks, rook_attacks, square_bit,
+    squares, Bitboard, DARK_SQUARES, RANK_1, RANK_2, RANK_7, RANK_8,
+};
+use crate::packed_move::{MoveKind, PackedMove};
+use crate::zobrist;
+
 pub const STARTPOS_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -22,6 +29,13 @@ impl Color {
             Self::Black => 'b',
         }
     }
+
+    pub fn index(self) -> usize {
+        match self {
+            Self::White => 0,
+            Self::Black => 1,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -35,6 +49,26 @@ pub enum PieceKind {
 }
 
 impl PieceKind {
+    pub const ALL: [PieceKind; 6] = [
+        Self::Pawn,
+        Self::Knight,
+        Self::Bishop,
+        Self::Rook,
+        Self::Queen,
+        Self::King,
+    ];
+
+    pub fn index(self) -> usize {
+        match self {
+            Self::Pawn => 0,
+            Self::Knight => 1,
+            Self::Bishop => 2,
+            Self::Rook => 3,
+            Self::Queen => 4,
+            Self::King => 5,
+        }
+    }
+
     pub fn material_value(self) -> i32 {
         match self {
             Self::Pawn => 100,
@@ -125,62 +159,122 @@ impl Piece {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CastlingSide {
+    King,
+    Queen,
+}
+
+impl CastlingSide {
+    pub const BOTH: [CastlingSide; 2] = [Self::King, Self::Queen];
+
+    pub fn index(self) -> usize {
+        match self {
+            Self::King => 0,
+            Self::Queen => 1,
+        }
+    }
+
+    /// File the king ends on after castling, in classical chess and Chess960 alike.
+    pub fn king_target_file(self) -> u8 {
+        match self {
+            Self::King => 6,
+            Self::Queen => 2,
+        }
+    }
+
+    /// File the rook ends on after castling.
+    pub fn rook_target_file(self) -> u8 {
+        match self {
+            Self::King => 5,
+            Self::Queen => 3,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::King => "king-side",
+            Self::Queen => "queen-side",
+        }
+    }
+
+    fn classical_rook_file(self) -> u8 {
+        match self {
+            Self::King => 7,
+            Self::Queen => 0,
+        }
+    }
+}
+
+/// Castling rights, stored as the file of the rook each right belongs to.
+/// Classical chess uses the a/h files; Chess960 may use any file on either
+/// side of the king.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CastlingRights {
-    pub white_king_side: bool,
-    pub white_queen_side: bool,
-    pub black_king_side: bool,
-    pub black_queen_side: bool,
+    pub white_king_side: Option<u8>,
+    pub white_queen_side: Option<u8>,
+    pub black_king_side: Option<u8>,
+    pub black_queen_side: Option<u8>,
 }
 
 impl CastlingRights {
     pub fn none() -> Self {
         Self {
-            white_king_side: false,
-            white_queen_side: false,
-            black_king_side: false,
-            black_queen_side: false,
+            white_king_side: None,
+            white_queen_side: None,
+            black_king_side: None,
+            black_queen_side: None,
         }
     }
 
-    pub fn from_fen(value: &str) -> Result<Self, String> {
-        let mut rights = Self::none();
-        if value == "-" {
-            return Ok(rights);
+    pub fn rook_file(self, color: Color, side: CastlingSide) -> Option<u8> {
+        match (color, side) {
+            (Color::White, CastlingSide::King) => self.white_king_side,
+            (Color::White, CastlingSide::Queen) => self.white_queen_side,
+            (Color::Black, CastlingSide::King) => self.black_king_side,
+            (Color::Black, CastlingSide::Queen) => self.black_queen_side,
         }
-        for ch in value.chars() {
-            match ch {
-                'K' => rights.white_king_side = true,
-                'Q' => rights.white_queen_side = true,
-                'k' => rights.black_king_side = true,
-                'q' => rights.black_queen_side = true,
-                _ => return Err(format!("bad castling rights: {value}")),
-            }
-        }
-        Ok(rights)
     }
 
-    pub fn to_fen(self) -> String {
-        let mut result = String::new();
-        if self.white_king_side {
-            result.push('K');
-        }
-        if self.white_queen_side {
-            result.push('Q');
-        }
-        if self.black_king_side {
-            result.push('k');
+    pub fn is_empty(self) -> bool {
+        self == Self::none()
+    }
+
+    fn set(&mut self, color: Color, side: CastlingSide, rook_file: Option<u8>) {
+        match (color, side) {
+            (Color::White, CastlingSide::King) => self.white_king_side = rook_file,
+            (Color::White, CastlingSide::Queen) => self.white_queen_side = rook_file,
+            (Color::Black, CastlingSide::King) => self.black_king_side = rook_file,
+            (Color::Black, CastlingSide::Queen) => self.black_queen_side = rook_file,
         }
-        if self.black_queen_side {
-            result.push('q');
+    }
+
+    fn clear_color(&mut self, color: Color) {
+        self.set(color, CastlingSide::King, None);
+        self.set(color, CastlingSide::Queen, None);
+    }
+
+    fn clear_rook_square(&mut self, color: Color, square: u8) {
+        if rank_of(square) != back_rank(color) {
+            return;
         }
-        if result.is_empty() {
-            result.push('-');
+        for side in CastlingSide::BOTH {
+            if self.rook_file(color, side) == Some(square % 8) {
+                self.set(color, side, None);
+            }
         }
-        result
     }
 }
 
+/// King and rook squares of one castling move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CastlingSquares {
+    king_from: u8,
+    king_to: u8,
+    rook_from: u8,
+    rook_to: u8,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ChessMove {
     pub from: u8,
@@ -202,9 +296,37 @@ impl ChessMove {
     }
 }
 
+/// A piece that cannot leave the line between its own king and an enemy slider.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pin {
+    pub square: u8,
+    pub pinner: u8,
+    /// Where the pinned piece may still go: the squares between the king and
+    /// the pinner, and the pinner's square.
+    pub ray: Bitboard,
+}
+
+/// Which slice of the legal moves a generator call produces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveStage {
+    /// Every legal move, in the same order as `legal_moves`.
+    All,
+    /// Captures, en passant and every promotion, capturing or not.
+    CapturesAndPromotions,
+    /// Moves that neither capture nor promote, castling included.
+    Quiets,
+    /// All legal moves when the side to move is in check, none otherwise.
+    Evasions,
+    /// Quiet moves that give check.
+    QuietChecks,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DrawReason {
     Stalemate,
+    InsufficientMaterial,
+    SeventyFiveMoveRule,
+    FivefoldRepetition,
     FiftyMoveRule,
     ThreefoldRepetition,
 }
@@ -213,31 +335,244 @@ impl DrawReason {
     pub fn label(self) -> &'static str {
         match self {
             Self::Stalemate => "stalemate",
+            Self::InsufficientMaterial => "insufficient material",
+            Self::SeventyFiveMoveRule => "75-move rule",
+            Self::FivefoldRepetition => "fivefold repetition",
             Self::FiftyMoveRule => "50-move rule",
             Self::ThreefoldRepetition => "threefold repetition",
         }
     }
+
+    /// Draws that a player has to claim (50 moves, threefold). The others end
+    /// the game on their own.
+    pub fn is_claimable(self) -> bool {
+        matches!(self, Self::FiftyMoveRule | Self::ThreefoldRepetition)
+    }
+}
+
+/// Why `Position::from_fen` rejected a FEN string. Ranks are numbered 1..=8.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    FieldCount { found: usize },
+    RankCount { found: usize },
+    BadRank { rank: u8 },
+    BadPiece { rank: u8, symbol: char },
+    BadSideToMove { value: String },
+    MissingKing { color: Color },
+    BadCastling { value: String },
+    BadEnPassant { value: String },
+    BadHalfmoveClock { value: String },
+    BadFullmoveNumber { value: String },
+    Chess960Index { index: u16 },
+    Illegal { violations: Vec<FenViolation> },
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FieldCount { found } => write!(formatter, "FEN must contain 4 to 6 fields, found {found}"),
+            Self::RankCount { found } => write!(formatter, "FEN board must contain 8 ranks, found {found}"),
+            Self::BadRank { rank } => write!(formatter, "FEN rank {rank} does not contain 8 squares"),
+            Self::BadPiece { rank, symbol } => write!(formatter, "bad piece `{symbol}` in FEN rank {rank}"),
+            Self::BadSideToMove { value } => write!(formatter, "bad side-to-move field `{value}`"),
+            Self::MissingKing { color } => write!(formatter, "FEN has no {} king", color_label(*color)),
+            Self::BadCastling { value } => write!(formatter, "bad castling rights `{value}`"),
+            Self::BadEnPassant { value } => write!(formatter, "bad en-passant square `{value}`"),
+            Self::BadHalfmoveClock { value } => write!(formatter, "bad halfmove clock `{value}`"),
+            Self::BadFullmoveNumber { value } => write!(formatter, "bad fullmove number `{value}`"),
+            Self::Chess960Index { index } => write!(formatter, "Chess960 position index must be 0..959, got {index}"),
+            Self::Illegal { violations } => {
+                formatter.write_str("illegal position: ")?;
+                for (index, violation) in violations.iter().enumerate() {
+                    if index > 0 {
+                        formatter.write_str("; ")?;
+                    }
+                    write!(formatter, "{violation}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// A rule of chess that a well-formed FEN can still break. Reported by
+/// `Position::validate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenViolation {
+    TooManyKings { color: Color, count: u32 },
+    TooManyPawns { color: Color, count: u32 },
+    PawnOnBackRank { square: String },
+    KingsTouching,
+    OpponentInCheck { color: Color },
+    CastlingWithoutKing { color: Color, side: CastlingSide },
+    CastlingWithoutRook { color: Color, side: CastlingSide, file: char },
+    BadEnPassant { square: String },
+}
+
+impl FenViolation {
+    /// Violations `Position::sanitize` repairs by dropping a FEN field.
+    pub fn is_sanitizable(&self) -> bool {
+        matches!(
+            self,
+            Self::CastlingWithoutKing { .. } | Self::CastlingWithoutRook { .. } | Self::BadEnPassant { .. }
+        )
+    }
+}
+
+impl fmt::Display for FenViolation {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyKings { color, count } => write!(formatter, "{} has {count} kings", color_label(*color)),
+            Self::TooManyPawns { color, count } => write!(formatter, "{} has {count} pawns", color_label(*color)),
+            Self::PawnOnBackRank { square } => write!(formatter, "pawn on {square}"),
+            Self::KingsTouching => formatter.write_str("kings stand next to each other"),
+            Self::OpponentInCheck { color } => {
+                write!(formatter, "{} is in check but it is not their move", color_label(*color))
+            }
+            Self::CastlingWithoutKing { color, side } => write!(
+                formatter,
+                "{} {} castling right without the king on its home rank",
+                color_label(*color),
+                side.label()
+            ),
+            Self::CastlingWithoutRook { color, side, file } => write!(
+                formatter,
+                "{} {} castling right without a rook on the {file}-file",
+                color_label(*color),
+                side.label()
+            ),
+            Self::BadEnPassant { square } => {
+                write!(formatter, "en-passant square {square} does not follow a double pawn push")
+            }
+        }
+    }
+}
+
+/// A move that cannot be played in the given position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    Illegal { uci: String, fen: String },
+    NoPiece { square: String, fen: String },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Illegal { uci, fen } => write!(formatter, "illegal move {uci} in position {fen}"),
+            Self::NoPiece { square, fen } => write!(formatter, "no piece on {square} in position {fen}"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Error from replaying a move list on top of a start FEN.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChessError {
+    Fen(FenError),
+    Move(MoveError),
+}
+
+impl fmt::Display for ChessError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fen(error) => write!(formatter, "FEN error: {error}"),
+            Self::Move(error) => error.fmt(formatter),
+        }
+    }
+}
+
+impl std::error::Error for ChessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Fen(error) => Some(error),
+            Self::Move(error) => Some(error),
+        }
+    }
+}
+
+impl From<FenError> for ChessError {
+    fn from(error: FenError) -> Self {
+        Self::Fen(error)
+    }
+}
+
+impl From<MoveError> for ChessError {
+    fn from(error: MoveError) -> Self {
+        Self::Move(error)
+    }
+}
+
+fn color_label(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Position {
     board: [Option<Piece>; 64],
+    pieces: [[Bitboard; 6]; 2],
+    occupancy: [Bitboard; 2],
     side_to_move: Color,
     castling: CastlingRights,
     en_passant: Option<u8>,
     halfmove_clock: u32,
     fullmove_number: u32,
+    chess960: bool,
+    key: u64,
+}
+
+/// Everything `Position::unmake_move` needs to restore the position that
+/// existed before the matching `Position::make_move`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveUndo {
+    moved: Piece,
+    captured: Option<Piece>,
+    captured_square: u8,
+    castle: Option<CastlingSquares>,
+    castling: CastlingRights,
+    en_passant: Option<u8>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    key: u64,
+}
+
+/// What `Position::unmake_null_move` needs to restore the position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NullMoveUndo {
+    en_passant: Option<u8>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    key: u64,
+}
+
+impl MoveUndo {
+    pub fn moved(&self) -> Piece {
+        self.moved
+    }
+
+    pub fn captured(&self) -> Option<Piece> {
+        self.captured
+    }
 }
 
 impl Position {
     pub fn empty() -> Self {
         Self {
             board: [None; 64],
+            pieces: [[0; 6]; 2],
+            occupancy: [0; 2],
             side_to_move: Color::White,
             castling: CastlingRights::none(),
             en_passant: None,
             halfmove_clock: 0,
             fullmove_number: 1,
+            chess960: false,
+            key: 0,
         }
     }
 
@@ -245,85 +580,236 @@ impl Position {
         Self::from_fen(STARTPOS_FEN).expect("built-in start position must be valid")
     }
 
-    pub fn from_fen(fen: &str) -> Result<Self, String> {
+    /// Chess960 start position by its standard index (0..=959); 518 is the
+    /// classical setup. The position has Chess960 castling enabled.
+    pub fn chess960(index: u16) -> Result<Self, FenError> {
+        let mut position = Self::from_fen(&chess960_start_fen(index)?)?;
+        position.chess960 = true;
+        Ok(position)
+    }
+
+    /// Parses FEN, X-FEN (`KQkq` meaning the outermost rook) and Shredder-FEN
+    /// (`HAha` rook files). Chess960 castling is switched on when the castling
+    /// field uses rook files or the kings and rooks are not on their classical
+    /// squares.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
         let parts: Vec<&str> = fen.split_whitespace().collect();
         if parts.len() < 4 || parts.len() > 6 {
-            return Err("FEN must contain 4 to 6 fields".to_string());
+            return Err(FenError::FieldCount { found: parts.len() });
         }
 
         let mut position = Self::empty();
         let ranks: Vec<&str> = parts[0].split('/').collect();
         if ranks.len() != 8 {
-            return Err("FEN board must contain 8 ranks".to_string());
+            return Err(FenError::RankCount { found: ranks.len() });
         }
 
         for (rank_index, rank_text) in ranks.iter().enumerate() {
             let rank = 7_i32 - rank_index as i32;
+            let bad_rank = FenError::BadRank { rank: rank as u8 + 1 };
             let mut file = 0_i32;
             for ch in rank_text.chars() {
                 if let Some(empty_count) = ch.to_digit(10) {
                     if empty_count == 0 || empty_count > 8 {
-                        return Err(format!("bad empty-square count in FEN: {ch}"));
+                        return Err(bad_rank);
                     }
                     file += empty_count as i32;
                 } else if let Some(piece) = Piece::from_fen(ch) {
                     if file >= 8 {
-                        return Err("too many squares in FEN rank".to_string());
+                        return Err(bad_rank);
                     }
-                    let square = index(file, rank).ok_or_else(|| "bad FEN square".to_string())?;
-                    position.board[square as usize] = Some(piece);
+                    let square = index(file, rank).ok_or(bad_rank.clone())?;
+                    position.put_piece(square, piece);
                     file += 1;
                 } else {
-                    return Err(format!("bad piece in FEN: {ch}"));
+                    return Err(FenError::BadPiece { rank: rank as u8 + 1, symbol: ch });
                 }
             }
             if file != 8 {
-                return Err("FEN rank does not contain 8 squares".to_string());
+                return Err(bad_rank);
             }
         }
 
         position.side_to_move = match parts[1] {
             "w" => Color::White,
             "b" => Color::Black,
-            _ => return Err("bad side-to-move field".to_string()),
+            value => return Err(FenError::BadSideToMove { value: value.to_string() }),
         };
-        position.castling = CastlingRights::from_fen(parts[2])?;
+        for color in [Color::White, Color::Black] {
+            if position.king_square(color).is_none() {
+                return Err(FenError::MissingKing { color });
+            }
+        }
+        position.castling = position.parse_castling(parts[2])?;
+        position.chess960 = parts[2].chars().any(|ch| ch.is_ascii_alphabetic() && !"KQkq".contains(ch))
+            || !position.has_classical_castling_setup();
         position.en_passant = if parts[3] == "-" {
             None
         } else {
-            let square = parse_square(parts[3]).ok_or_else(|| "bad en-passant square".to_string())?;
+            let bad_en_passant = || FenError::BadEnPassant { value: parts[3].to_string() };
+            let square = parse_square(parts[3]).ok_or_else(bad_en_passant)?;
             let rank = rank_of(square);
             if rank != 2 && rank != 5 {
-                return Err("bad en-passant rank".to_string());
+                return Err(bad_en_passant());
             }
             Some(square)
         };
         position.halfmove_clock = if parts.len() >= 5 {
             parts[4]
                 .parse::<u32>()
-                .map_err(|_| "bad halfmove clock".to_string())?
+                .map_err(|_| FenError::BadHalfmoveClock { value: parts[4].to_string() })?
         } else {
             0
         };
         position.fullmove_number = if parts.len() >= 6 {
             parts[5]
                 .parse::<u32>()
-                .map_err(|_| "bad fullmove number".to_string())?
+                .map_err(|_| FenError::BadFullmoveNumber { value: parts[5].to_string() })?
         } else {
             1
         };
-        if position.king_square(Color::White).is_none() || position.king_square(Color::Black).is_none() {
-            return Err("FEN must contain both kings".to_string());
-        }
+        position.key = position.compute_key();
         Ok(position)
     }
 
+    /// `from_fen` followed by `validate`: FENs that parse but describe an
+    /// impossible position are rejected with `FenError::Illegal`.
+    pub fn from_fen_strict(fen: &str) -> Result<Self, FenError> {
+        let position = Self::from_fen(fen)?;
+        let violations = position.validate();
+        if violations.is_empty() {
+            Ok(position)
+        } else {
+            Err(FenError::Illegal { violations })
+        }
+    }
+
+    /// Like `from_fen_strict`, but bogus castling rights and en-passant squares
+    /// are dropped instead of rejected. Returns the position together with the
+    /// violations that were repaired.
+    pub fn from_fen_sanitized(fen: &str) -> Result<(Self, Vec<FenViolation>), FenError> {
+        let mut position = Self::from_fen(fen)?;
+        let repaired = position.sanitize();
+        let violations = position.validate();
+        if violations.is_empty() {
+            Ok((position, repaired))
+        } else {
+            Err(FenError::Illegal { violations })
+        }
+    }
+
+    /// Everything about the position that cannot arise in a game. Positions
+    /// reached by legal moves from a valid position always return an empty list.
+    pub fn validate(&self) -> Vec<FenViolation> {
+        let mut violations = Vec::new();
+        for color in [Color::White, Color::Black] {
+            let kings = self.pieces(color, PieceKind::King).count_ones();
+            if kings > 1 {
+                violations.push(FenViolation::TooManyKings { color, count: kings });
+            }
+            let pawns = self.pieces(color, PieceKind::Pawn).count_ones();
+            if pawns > 8 {
+                violations.push(FenViolation::TooManyPawns { color, count: pawns });
+            }
+        }
+        let pawns = self.pieces(Color::White, PieceKind::Pawn) | self.pieces(Color::Black, PieceKind::Pawn);
+        for square in squares(pawns & (RANK_1 | RANK_8)) {
+            violations.push(FenViolation::PawnOnBackRank { square: square_name(square) });
+        }
+        if let (Some(white), Some(black)) = (self.king_square(Color::White), self.king_square(Color::Black)) {
+            if king_attacks(white) & square_bit(black) != 0 {
+                violations.push(FenViolation::KingsTouching);
+            }
+        }
+        let waiting = self.side_to_move.opposite();
+        if self.is_in_check(waiting) {
+            violations.push(FenViolation::OpponentInCheck { color: waiting });
+        }
+        for color in [Color::White, Color::Black] {
+            for side in CastlingSide::BOTH {
+                let Some(file) = self.castling.rook_file(color, side) else {
+                    continue;
+                };
+                let Some(king_file) = self.home_king_file(color) else {
+                    violations.push(FenViolation::CastlingWithoutKing { color, side });
+                    continue;
+                };
+                let rook_square = back_rank(color) as usize * 8 + file as usize;
+                let on_side = match side {
+                    CastlingSide::King => file > king_file,
+                    CastlingSide::Queen => file < king_file,
+                };
+                if !on_side || self.board[rook_square] != Some(Piece { color, kind: PieceKind::Rook }) {
+                    violations.push(FenViolation::CastlingWithoutRook { color, side, file: (b'a' + file) as char });
+                }
+            }
+        }
+        if let Some(square) = self.en_passant {
+            if !self.en_passant_follows_double_push(square) {
+                violations.push(FenViolation::BadEnPassant { square: square_name(square) });
+            }
+        }
+        violations
+    }
+
+    /// Drops the castling rights and en-passant square that `validate` rejects
+    /// and returns the repaired violations. Anything else is left as it is.
+    pub fn sanitize(&mut self) -> Vec<FenViolation> {
+        let repaired: Vec<FenViolation> = self
+            .validate()
+            .into_iter()
+            .filter(FenViolation::is_sanitizable)
+            .collect();
+        for violation in &repaired {
+            match violation {
+                FenViolation::CastlingWithoutKing { color, side }
+                | FenViolation::CastlingWithoutRook { color, side, .. } => self.castling.set(*color, *side, None),
+                FenViolation::BadEnPassant { .. } => self.en_passant = None,
+                _ => {}
+            }
+        }
+        self.key = self.compute_key();
+        repaired
+    }
+
+    /// The target square sits behind an enemy pawn that just made a double
+    /// push: right rank for the side to move, pawn in front, both squares it
+    /// crossed empty.
+    fn en_passant_follows_double_push(&self, square: u8) -> bool {
+        let (expected_rank, pawn_square, origin) = match self.side_to_move {
+            Color::White => (5, square.wrapping_sub(8), square + 8),
+            Color::Black => (2, square + 8, square.wrapping_sub(8)),
+        };
+        rank_of(square) == expected_rank
+            && self.board[square as usize].is_none()
+            && self.board[origin as usize].is_none()
+            && self.board[pawn_square as usize]
+                == Some(Piece { color: self.side_to_move.opposite(), kind: PieceKind::Pawn })
+    }
+
+    /// FEN of the position. Chess960 positions write the castling field in
+    /// Shredder-FEN so that parsing it back keeps Chess960 castling on.
     pub fn to_fen(&self) -> String {
+        self.fen_with_castling(self.castling_fen(self.chess960))
+    }
+
+    /// FEN with X-FEN castling: `KQkq` for the outermost rook on each side and
+    /// a rook file letter only when an inner rook holds the right.
+    pub fn to_xfen(&self) -> String {
+        self.fen_with_castling(self.castling_fen(false))
+    }
+
+    /// FEN with Shredder-FEN castling, where every right is a rook file letter.
+    pub fn to_shredder_fen(&self) -> String {
+        self.fen_with_castling(self.castling_fen(true))
+    }
+
+    fn fen_with_castling(&self, castling: String) -> String {
         format!(
             "{} {} {} {} {} {}",
             self.board_fen(),
             self.side_to_move.fen(),
-            self.castling.to_fen(),
+            castling,
             self.en_passant.map(square_name).unwrap_or_else(|| "-".to_string()),
             self.halfmove_clock,
             self.fullmove_number
@@ -356,26 +842,148 @@ impl Position {
         board_part
     }
 
+    fn parse_castling(&self, value: &str) -> Result<CastlingRights, FenError> {
+        let bad_castling = || FenError::BadCastling { value: value.to_string() };
+        let mut rights = CastlingRights::none();
+        if value == "-" {
+            return Ok(rights);
+        }
+        for ch in value.chars() {
+            let color = if ch.is_ascii_uppercase() { Color::White } else { Color::Black };
+            let (side, rook_file) = match ch.to_ascii_lowercase() {
+                'k' => (
+                    CastlingSide::King,
+                    self.outermost_rook_file(color, CastlingSide::King).unwrap_or(7),
+                ),
+                'q' => (
+                    CastlingSide::Queen,
+                    self.outermost_rook_file(color, CastlingSide::Queen).unwrap_or(0),
+                ),
+                file @ 'a'..='h' => {
+                    let rook_file = file as u8 - b'a';
+                    let king_file = self
+                        .home_king_file(color)
+                        .ok_or_else(bad_castling)?;
+                    let side = match rook_file.cmp(&king_file) {
+                        std::cmp::Ordering::Greater => CastlingSide::King,
+                        std::cmp::Ordering::Less => CastlingSide::Queen,
+                        std::cmp::Ordering::Equal => return Err(bad_castling()),
+                    };
+                    (side, rook_file)
+                }
+                _ => return Err(bad_castling()),
+            };
+            rights.set(color, side, Some(rook_file));
+        }
+        Ok(rights)
+    }
+
+    fn castling_fen(&self, shredder: bool) -> String {
+        let mut result = String::new();
+        for color in [Color::White, Color::Black] {
+            for side in CastlingSide::BOTH {
+                let Some(rook_file) = self.castling.rook_file(color, side) else {
+                    continue;
+                };
+                let outermost = self
+                    .outermost_rook_file(color, side)
+                    .unwrap_or(side.classical_rook_file());
+                let ch = if !shredder && rook_file == outermost {
+                    match side {
+                        CastlingSide::King => 'k',
+                        CastlingSide::Queen => 'q',
+                    }
+                } else {
+                    (b'a' + rook_file) as char
+                };
+                result.push(match color {
+                    Color::White => ch.to_ascii_uppercase(),
+                    Color::Black => ch,
+                });
+            }
+        }
+        if result.is_empty() {
+            result.push('-');
+        }
+        result
+    }
+
+    /// File of the king when it stands on its own back rank.
+    fn home_king_file(&self, color: Color) -> Option<u8> {
+        self.king_square(color)
+            .filter(|square| rank_of(*square) == back_rank(color))
+            .map(|square| square % 8)
+    }
+
+    /// File of the own rook furthest from the king on one side of the back rank.
+    fn outermost_rook_file(&self, color: Color, side: CastlingSide) -> Option<u8> {
+        let king_file = self.home_king_file(color)?;
+        let base = back_rank(color) as u8 * 8;
+        let rook = Some(Piece { color, kind: PieceKind::Rook });
+        match side {
+            CastlingSide::King => (king_file + 1..8).rev().find(|file| self.board[(base + file) as usize] == rook),
+            CastlingSide::Queen => (0..king_file).find(|file| self.board[(base + file) as usize] == rook),
+        }
+    }
+
+    fn has_classical_castling_setup(&self) -> bool {
+        [Color::White, Color::Black].into_iter().all(|color| {
+            CastlingSide::BOTH.into_iter().all(|side| match self.castling.rook_file(color, side) {
+                Some(rook_file) => rook_file == side.classical_rook_file() && self.home_king_file(color) == Some(4),
+                None => true,
+            })
+        })
+    }
+
     fn legal_en_passant_square(&self) -> Option<u8> {
         let en_passant = self.en_passant?;
-        let captured_square = match self.side_to_move {
+        let us = self.side_to_move;
+        let them = us.opposite();
+        let captured_square = match us {
             Color::White => en_passant.checked_sub(8)?,
             Color::Black => en_passant.checked_add(8).filter(|square| *square < 64)?,
         };
-        let captured = self.board[captured_square as usize]?;
-        if captured.color != self.side_to_move.opposite() || captured.kind != PieceKind::Pawn {
+        if self.board[captured_square as usize] != Some(Piece { color: them, kind: PieceKind::Pawn }) {
             return None;
         }
-        self.legal_moves()
-            .into_iter()
-            .find(|chess_move| chess_move.to == en_passant && self.is_capture(*chess_move))
-            .map(|_| en_passant)
+        let king = self.king_square(us)?;
+        let capturers = pawn_attacks(us == Color::Black, en_passant) & self.pieces(us, PieceKind::Pawn);
+        let queens = self.pieces(them, PieceKind::Queen);
+        let legal = squares(capturers).any(|from| {
+            let occupied = (self.occupied() ^ square_bit(from) ^ square_bit(captured_square)) | square_bit(en_passant);
+            let attackers = (bishop_attacks(king, occupied) & (self.pieces(them, PieceKind::Bishop) | queens))
+                | (rook_attacks(king, occupied) & (self.pieces(them, PieceKind::Rook) | queens))
+                | (knight_attacks(king) & self.pieces(them, PieceKind::Knight))
+                | (pawn_attacks(us == Color::White, king)
+                    & self.pieces(them, PieceKind::Pawn)
+                    & !square_bit(captured_square))
+                | (king_attacks(king) & self.pieces(them, PieceKind::King));
+            attackers == 0
+        });
+        legal.then_some(en_passant)
     }
 
     pub fn side_to_move(&self) -> Color {
         self.side_to_move
     }
 
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling
+    }
+
+    /// Whether castling moves use Chess960 king-takes-rook encoding
+    /// (`e1h1`, `b1a1`) instead of the classical king step (`e1g1`).
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    /// Switches the castling move encoding. Classical encoding can only express
+    /// castling with the king on the e-file and rooks on the a/h files; other
+    /// castling rights are kept but generate no moves while it is off.
+    pub fn set_chess960(&mut self, enabled: bool) {
+        self.chess960 = enabled;
+    }
+
     pub fn halfmove_clock(&self) -> u32 {
         self.halfmove_clock
     }
@@ -385,60 +993,111 @@ impl Position {
     }
 
     pub fn king_square(&self, color: Color) -> Option<u8> {
-        self.board.iter().enumerate().find_map(|(square, piece)| {
-            if *piece == Some(Piece { color, kind: PieceKind::King }) {
-                Some(square as u8)
-            } else {
-                None
+        let kings = self.pieces(color, PieceKind::King);
+        if kings == 0 {
+            None
+        } else {
+            Some(lsb(kings))
+        }
+    }
+
+    /// Bitboard of all pieces of one kind and color.
+    pub fn pieces(&self, color: Color, kind: PieceKind) -> Bitboard {
+        self.pieces[color.index()][kind.index()]
+    }
+
+    /// Bitboard of every piece of one color.
+    pub fn occupancy(&self, color: Color) -> Bitboard {
+        self.occupancy[color.index()]
+    }
+
+    /// Bitboard of every occupied square.
+    pub fn occupied(&self) -> Bitboard {
+        self.occupancy[0] | self.occupancy[1]
+    }
+
+    /// Incrementally maintained Zobrist key of the position identity: pieces,
+    /// side to move, castling rights and the en-passant file when a legal
+    /// en-passant capture exists. Move counters are not part of the key.
+    pub fn zobrist_key(&self) -> u64 {
+        self.key
+    }
+
+    fn compute_key(&self) -> u64 {
+        let mut key = 0;
+        for square in 0_u8..64 {
+            if let Some(piece) = self.board[square as usize] {
+                key ^= zobrist::piece(piece.color.index(), piece.kind.index(), square);
             }
-        })
+        }
+        if self.side_to_move == Color::Black {
+            key ^= zobrist::side_to_move();
+        }
+        key ^= castling_key(self.castling);
+        if let Some(square) = self.legal_en_passant_square() {
+            key ^= zobrist::en_passant_file(file_of(square));
+        }
+        key
     }
 
     pub fn is_fifty_move_rule_draw(&self) -> bool {
         self.halfmove_clock >= 100 && !self.is_checkmate()
     }
 
+    pub fn is_seventy_five_move_rule_draw(&self) -> bool {
+        self.halfmove_clock >= 150 && !self.is_checkmate()
+    }
+
+    /// Neither side can ever mate: bare kings, a single knight, or any number
+    /// of bishops that all stand on squares of one color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy_or_pawns = [PieceKind::Pawn, PieceKind::Rook, PieceKind::Queen]
+            .into_iter()
+            .any(|kind| self.pieces(Color::White, kind) | self.pieces(Color::Black, kind) != 0);
+        if heavy_or_pawns {
+            return false;
+        }
+        let knights = self.pieces(Color::White, PieceKind::Knight) | self.pieces(Color::Black, PieceKind::Knight);
+        let bishops = self.pieces(Color::White, PieceKind::Bishop) | self.pieces(Color::Black, PieceKind::Bishop);
+        if knights == 0 {
+            bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0
+        } else {
+            bishops == 0 && knights.count_ones() == 1
+        }
+    }
+
+    /// Draws that do not depend on the game history.
+    pub fn is_draw_without_history(&self) -> bool {
+        self.is_fifty_move_rule_draw() || self.is_insufficient_material()
+    }
+
     pub fn repetition_key(&self) -> String {
         format!(
             "{} {} {} {}",
             self.board_fen(),
             self.side_to_move.fen(),
-            self.castling.to_fen(),
+            self.castling_fen(self.chess960),
             self.legal_en_passant_square()
                 .map(square_name)
                 .unwrap_or_else(|| "-".to_string())
         )
     }
 
-    pub fn repetition_count_from_history(start_fen: &str, moves: &[ChessMove]) -> Result<usize, String> {
-        let mut position = Self::from_fen(start_fen)?;
-        let mut keys = vec![position.repetition_key()];
-        for chess_move in moves {
-            position.make_legal_move(*chess_move)?;
-            keys.push(position.repetition_key());
-        }
-        let current = position.repetition_key();
-        Ok(keys.iter().filter(|key| key.as_str() == current.as_str()).count())
+    /// Replays `moves` from `start_fen`. Use `Game` to follow a game move by
+    /// move instead of replaying it for every query.
+    pub fn repetition_count_from_history(start_fen: &str, moves: &[ChessMove]) -> Result<usize, ChessError> {
+        Ok(Game::from_moves(start_fen, moves)?.repetition_count())
     }
 
-    pub fn is_threefold_repetition_from_history(start_fen: &str, moves: &[ChessMove]) -> Result<bool, String> {
+    pub fn is_threefold_repetition_from_history(start_fen: &str, moves: &[ChessMove]) -> Result<bool, ChessError> {
         Ok(Self::repetition_count_from_history(start_fen, moves)? >= 3)
     }
 
-    pub fn draw_reason_from_history(start_fen: &str, moves: &[ChessMove]) -> Result<Option<DrawReason>, String> {
-        let mut position = Self::from_fen(start_fen)?;
-        for chess_move in moves {
-            position.make_legal_move(*chess_move)?;
-        }
-        if position.is_stalemate() {
-            Ok(Some(DrawReason::Stalemate))
-        } else if position.is_fifty_move_rule_draw() {
-            Ok(Some(DrawReason::FiftyMoveRule))
-        } else if Self::repetition_count_from_history(start_fen, moves)? >= 3 {
-            Ok(Some(DrawReason::ThreefoldRepetition))
-        } else {
-            Ok(None)
-        }
+    pub fn draw_reason_from_history(
+        start_fen: &str,
+        moves: &[ChessMove],
+    ) -> Result<Option<DrawReason>, ChessError> {
+        Ok(Game::from_moves(start_fen, moves)?.draw_reason())
     }
 
     pub fn piece_at(&self, square: u8) -> Option<Piece> {
@@ -446,28 +1105,166 @@ impl Position {
     }
 
     pub fn legal_moves(&self) -> Vec<ChessMove> {
-        let mut result = Vec::new();
-        for chess_move in self.pseudo_legal_moves() {
-            let mut next = self.clone();
-            if next.apply_unchecked(chess_move).is_ok() && !next.is_in_check(self.side_to_move) {
-                result.push(chess_move);
+        self.legal_moves_in(MoveStage::All)
+    }
+
+    /// Legal captures plus all promotions, for quiescence and capture-first ordering.
+    pub fn legal_captures(&self) -> Vec<ChessMove> {
+        self.legal_moves_in(MoveStage::CapturesAndPromotions)
+    }
+
+    pub fn legal_quiets(&self) -> Vec<ChessMove> {
+        self.legal_moves_in(MoveStage::Quiets)
+    }
+
+    pub fn legal_evasions(&self) -> Vec<ChessMove> {
+        self.legal_moves_in(MoveStage::Evasions)
+    }
+
+    pub fn legal_quiet_checks(&self) -> Vec<ChessMove> {
+        self.legal_moves_in(MoveStage::QuietChecks)
+    }
+
+    pub fn legal_moves_in(&self, stage: MoveStage) -> Vec<ChessMove> {
+        let mut moves = Vec::with_capacity(if stage == MoveStage::All { 64 } else { 16 });
+        self.generate_moves(stage, &mut moves);
+        moves
+    }
+
+    /// Legal moves with their kind flags, in `legal_moves` order.
+    pub fn legal_packed_moves(&self) -> Vec<PackedMove> {
+        let mut moves = Vec::with_capacity(64);
+        self.generate_packed_moves(MoveStage::All, &mut moves);
+        moves
+    }
+
+    /// Appends the legal moves of `stage` to `moves`.
+    ///
+    /// Legality comes from the checker and pin masks of the side to move, so
+    /// no move is played to test it. A side without a king has no moves.
+    pub fn generate_moves(&self, stage: MoveStage, moves: &mut Vec<ChessMove>) {
+        self.generate_into(stage, moves);
+    }
+
+    /// Same as `generate_moves`, keeping the kind flags the generator knows.
+    pub fn generate_packed_moves(&self, stage: MoveStage, moves: &mut Vec<PackedMove>) {
+        self.generate_into(stage, moves);
+    }
+
+    fn generate_into<S: MoveSink>(&self, stage: MoveStage, moves: &mut S) {
+        if stage == MoveStage::QuietChecks {
+            let mut quiets: Vec<PackedMove> = Vec::new();
+            self.generate_into(MoveStage::Quiets, &mut quiets);
+            for packed in quiets {
+                if self.gives_check(packed.to_move()) {
+                    moves.add(packed);
+                }
             }
+            return;
+        }
+        let us = self.side_to_move;
+        let them = us.opposite();
+        let Some(king) = self.king_square(us) else {
+            return;
+        };
+        let masks = self.move_masks(us, king);
+        if stage == MoveStage::Evasions && masks.checkers == 0 {
+            return;
+        }
+        let occupied = self.occupied();
+        let enemies = self.occupancy(them) & !self.pieces(them, PieceKind::King);
+        let targets = match stage {
+            MoveStage::All | MoveStage::Evasions => enemies | !occupied,
+            MoveStage::CapturesAndPromotions => enemies,
+            MoveStage::Quiets | MoveStage::QuietChecks => !occupied,
+        };
+
+        // In double check only the king may move.
+        if masks.checkers.count_ones() < 2 {
+            self.add_pawn_moves(stage, &masks, moves);
+            for kind in [PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen] {
+                for from in squares(self.pieces(us, kind)) {
+                    let to = piece_attacks(kind, us, from, occupied) & targets & masks.allowed(from);
+                    push_target_moves(from, to, enemies, moves);
+                }
+            }
+        }
+        let without_king = occupied & !square_bit(king);
+        for to in squares(king_attacks(king) & targets) {
+            if self.attackers_to_with_occupancy(to, them, without_king) == 0 {
+                let kind = if enemies & square_bit(to) != 0 { MoveKind::Capture } else { MoveKind::Quiet };
+                moves.add(PackedMove::new(king, to, kind));
+            }
+        }
+        if masks.checkers == 0 && matches!(stage, MoveStage::All | MoveStage::Quiets) {
+            self.add_castling_moves(us, moves);
         }
-        result
     }
 
-    pub fn legal_captures(&self) -> Vec<ChessMove> {
-        self.legal_moves()
-            .into_iter()
-            .filter(|chess_move| self.is_capture(*chess_move))
-            .collect()
+    /// Encodes a move of this position with its kind flags. The move should be
+    /// legal here; anything else is packed as a quiet move or capture.
+    pub fn pack(&self, chess_move: ChessMove) -> PackedMove {
+        let ChessMove { from, to, promotion } = chess_move;
+        let capture = self.is_capture(chess_move);
+        let kind = if let Some(piece) = promotion {
+            MoveKind::Promotion { piece, capture }
+        } else if let Some(side) = self.castling_side(chess_move) {
+            MoveKind::Castle(side)
+        } else if capture {
+            let pawn = self.piece_at(from).is_some_and(|piece| piece.kind == PieceKind::Pawn);
+            if pawn && self.piece_at(to).is_none() {
+                MoveKind::EnPassant
+            } else {
+                MoveKind::Capture
+            }
+        } else if self.piece_at(from).is_some_and(|piece| piece.kind == PieceKind::Pawn) && rank_distance(from, to) == 2 {
+            MoveKind::DoublePawnPush
+        } else {
+            MoveKind::Quiet
+        };
+        PackedMove::new(from, to, kind)
+    }
+
+    /// `parse_uci_move` followed by `pack`.
+    pub fn parse_uci_packed(&self, value: &str) -> Option<PackedMove> {
+        self.parse_uci_move(value).map(|chess_move| self.pack(chess_move))
+    }
+
+    /// Whether a legal move checks the opponent, directly or by uncovering a
+    /// slider. The move is not played.
+    pub fn gives_check(&self, chess_move: ChessMove) -> bool {
+        let Some(piece) = self.piece_at(chess_move.from) else {
+            return false;
+        };
+        let us = piece.color;
+        let Some(king) = self.king_square(us.opposite()) else {
+            return false;
+        };
+        let mut occupied = self.occupied() & !square_bit(chess_move.from);
+        let mut moved_away = square_bit(chess_move.from);
+        let direct = if let Some(castle) = self.castling_squares_for_move(chess_move) {
+            occupied = (occupied & !square_bit(castle.rook_from)) | square_bit(castle.king_to) | square_bit(castle.rook_to);
+            moved_away |= square_bit(castle.rook_from);
+            rook_attacks(castle.rook_to, occupied)
+        } else {
+            let captured = self.captured_square_for_move(piece, chess_move);
+            occupied = (occupied & !square_bit(captured)) | square_bit(chess_move.to);
+            piece_attacks(chess_move.promotion.unwrap_or(piece.kind), us, chess_move.to, occupied)
+        };
+        if direct & square_bit(king) != 0 {
+            return true;
+        }
+        let queens = self.pieces(us, PieceKind::Queen);
+        let diagonal = (self.pieces(us, PieceKind::Bishop) | queens) & !moved_away;
+        let straight = (self.pieces(us, PieceKind::Rook) | queens) & !moved_away;
+        (bishop_attacks(king, occupied) & diagonal) | (rook_attacks(king, occupied) & straight) != 0
     }
 
-    pub fn make_legal_move(&mut self, chess_move: ChessMove) -> Result<(), String> {
+    pub fn make_legal_move(&mut self, chess_move: ChessMove) -> Result<(), MoveError> {
         if self.legal_moves().into_iter().any(|candidate| candidate == chess_move) {
             self.apply_unchecked(chess_move)
         } else {
-            Err(format!("illegal move: {}", chess_move.to_uci()))
+            Err(MoveError::Illegal { uci: chess_move.to_uci(), fen: self.to_fen() })
         }
     }
 
@@ -482,9 +1279,36 @@ impl Position {
         } else {
             None
         };
-        self.legal_moves()
-            .into_iter()
+        let legal = self.legal_moves();
+        if let Some(chess_move) = legal
+            .iter()
+            .copied()
             .find(|chess_move| chess_move.from == from && chess_move.to == to && chess_move.promotion == promotion)
+        {
+            return Some(chess_move);
+        }
+        // Chess960 castling may also arrive as the king's destination square
+        // from GUIs and engines that do not use king-takes-rook notation.
+        if !self.chess960 || promotion.is_some() || from == to {
+            return None;
+        }
+        legal.into_iter().find(|chess_move| {
+            chess_move.from == from
+                && self
+                    .castling_squares_for_move(*chess_move)
+                    .is_some_and(|castle| castle.king_to == to)
+        })
+    }
+
+    /// Whether the move is a castling move in this position's encoding.
+    pub fn is_castling(&self, chess_move: ChessMove) -> bool {
+        self.castling_squares_for_move(chess_move).is_some()
+    }
+
+    /// Side of a castling move, or `None` for any other move.
+    pub fn castling_side(&self, chess_move: ChessMove) -> Option<CastlingSide> {
+        self.castling_squares_for_move(chess_move)
+            .map(|castle| if castle.rook_from > castle.king_from { CastlingSide::King } else { CastlingSide::Queen })
     }
 
     pub fn is_checkmate(&self) -> bool {
@@ -502,12 +1326,67 @@ impl Position {
         }
     }
 
+    pub fn is_square_attacked(&self, square: u8, by_color: Color) -> bool {
+        self.attackers_to(square, by_color) != 0
+    }
+
+    /// Pieces of `by_color` attacking `square`, whatever stands on it.
+    pub fn attackers_to(&self, square: u8, by_color: Color) -> Bitboard {
+        self.attackers_to_with_occupancy(square, by_color, self.occupied())
+    }
+
+    /// Same as `attackers_to`, with sliders traced through `occupied` instead
+    /// of the board. Clearing squares from it shows what stands behind them,
+    /// which is what an exchange sequence needs.
+    pub fn attackers_to_with_occupancy(&self, square: u8, by_color: Color, occupied: Bitboard) -> Bitboard {
+        let queens = self.pieces(by_color, PieceKind::Queen);
+        (pawn_attacks(by_color == Color::Black, square) & self.pieces(by_color, PieceKind::Pawn))
+            | (knight_attacks(square) & self.pieces(by_color, PieceKind::Knight))
+            | (king_attacks(square) & self.pieces(by_color, PieceKind::King))
+            | (bishop_attacks(square, occupied) & (self.pieces(by_color, PieceKind::Bishop) | queens))
+            | (rook_attacks(square, occupied) & (self.pieces(by_color, PieceKind::Rook) | queens))
+    }
+
+    /// Sliders of `by_color` that would attack `square` once the first piece
+    /// on their line, of either colour, is gone.
+    pub fn xray_attackers_to(&self, square: u8, by_color: Color) -> Bitboard {
+        let occupied = self.occupied();
+        let first_blockers = (bishop_attacks(square, occupied) | rook_attacks(square, occupied)) & occupied;
+        self.attackers_to_with_occupancy(square, by_color, occupied & !first_blockers)
+            & !self.attackers_to(square, by_color)
+    }
+
+    /// Squares attacked by the piece on `square`, or none for an empty square.
+    pub fn attacks_from(&self, square: u8) -> Bitboard {
+        self.piece_at(square)
+            .map(|piece| piece_attacks(piece.kind, piece.color, square, self.occupied()))
+            .unwrap_or(0)
+    }
+
+    /// Pieces giving check to the side to move.
+    pub fn checkers(&self) -> Bitboard {
+        let us = self.side_to_move;
+        self.king_square(us)
+            .map(|king| self.attackers_to(king, us.opposite()))
+            .unwrap_or(0)
+    }
+
+    /// Pieces of `color` pinned against their own king, in square order.
+    pub fn pinned_pieces(&self, color: Color) -> Vec<Pin> {
+        let Some(king) = self.king_square(color) else {
+            return Vec::new();
+        };
+        let mut pins: Vec<Pin> = self.pins_against(color, king).collect();
+        pins.sort_by_key(|pin| pin.square);
+        pins
+    }
+
     pub fn is_capture(&self, chess_move: ChessMove) -> bool {
         let Some(piece) = self.piece_at(chess_move.from) else {
             return false;
         };
-        if self.piece_at(chess_move.to).is_some() {
-            return true;
+        if let Some(target) = self.piece_at(chess_move.to) {
+            return target.color != piece.color;
         }
         piece.kind == PieceKind::Pawn
             && self.en_passant == Some(chess_move.to)
@@ -532,6 +1411,10 @@ impl Position {
     }
 
     pub fn perft(&self, depth: u32) -> u64 {
+        self.clone().perft_in_place(depth)
+    }
+
+    fn perft_in_place(&mut self, depth: u32) -> u64 {
         if depth == 0 {
             return 1;
         }
@@ -541,61 +1424,93 @@ impl Position {
         }
         let mut nodes = 0;
         for chess_move in moves {
-            let mut next = self.clone();
-            next.apply_unchecked(chess_move).expect("legal move must apply");
-            nodes += next.perft(depth - 1);
+            let undo = self.make_move(chess_move);
+            nodes += self.perft_in_place(depth - 1);
+            self.unmake_move(chess_move, undo);
         }
         nodes
     }
 
-    pub fn perft_divide(&self, depth: u32) -> Vec<(ChessMove, u64)> {
+    pub fn perft_divide(&self, depth: u32) -> Vec<(PackedMove, u64)> {
         if depth == 0 {
             return Vec::new();
         }
 
+        let mut position = self.clone();
         let mut rows = Vec::new();
-        for chess_move in self.legal_moves() {
-            let mut next = self.clone();
-            next.apply_unchecked(chess_move).expect("legal move must apply");
-            rows.push((chess_move, next.perft(depth - 1)));
+        for packed in position.legal_packed_moves() {
+            let chess_move = packed.to_move();
+            let undo = position.make_move(chess_move);
+            rows.push((packed, position.perft_in_place(depth - 1)));
+            position.unmake_move(chess_move, undo);
         }
         rows
     }
 
-    pub(crate) fn apply_unchecked(&mut self, chess_move: ChessMove) -> Result<(), String> {
-        let mut piece = self.board[chess_move.from as usize]
-            .take()
-            .ok_or_else(|| format!("no piece on {}", square_name(chess_move.from)))?;
-        let was_pawn = piece.kind == PieceKind::Pawn;
-        let captured = self.capture_for_move(piece, chess_move);
-
-        self.update_castling_rights(piece, chess_move, captured);
-
-        if piece.kind == PieceKind::Pawn
-            && self.en_passant == Some(chess_move.to)
-            && file_of(chess_move.from) != file_of(chess_move.to)
-            && self.board[chess_move.to as usize].is_none()
-        {
-            let captured_square = match piece.color {
-                Color::White => chess_move.to.saturating_sub(8),
-                Color::Black => chess_move.to + 8,
-            };
-            self.board[captured_square as usize] = None;
-        } else {
-            self.board[chess_move.to as usize] = None;
+    pub(crate) fn apply_unchecked(&mut self, chess_move: ChessMove) -> Result<(), MoveError> {
+        if self.board[chess_move.from as usize].is_none() {
+            return Err(MoveError::NoPiece { square: square_name(chess_move.from), fen: self.to_fen() });
         }
+        self.make_move(chess_move);
+        Ok(())
+    }
 
-        if piece.kind == PieceKind::King && file_distance(chess_move.from, chess_move.to) == 2 {
-            self.move_castling_rook(piece.color, chess_move.to)?;
+    /// Plays a move in place and returns the record needed to take it back.
+    ///
+    /// The move must come from the move generator for this position; legality
+    /// is not re-checked here. Use `make_legal_move` for untrusted input.
+    pub fn make_move(&mut self, chess_move: ChessMove) -> MoveUndo {
+        let piece = self.board[chess_move.from as usize].expect("make_move requires a piece on the from-square");
+        let castle = self.castling_squares_for_move(chess_move);
+        let captured_square = self.captured_square_for_move(piece, chess_move);
+        let key = self.key;
+        if let Some(square) = self.legal_en_passant_square() {
+            self.key ^= zobrist::en_passant_file(file_of(square));
         }
+        let captured = if castle.is_some() {
+            None
+        } else {
+            self.remove_piece(captured_square)
+        };
+        let undo = MoveUndo {
+            moved: piece,
+            captured,
+            captured_square,
+            castle,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            key,
+        };
 
-        if piece.kind == PieceKind::Pawn && is_promotion_rank(chess_move.to, piece.color) {
-            piece.kind = chess_move.promotion.unwrap_or(PieceKind::Queen);
+        self.key ^= castling_key(self.castling);
+        self.update_castling_rights(piece, chess_move, captured);
+        self.key ^= castling_key(self.castling);
+
+        if let Some(castle) = castle {
+            // King and rook may land on each other's start squares in Chess960,
+            // so both leave the board before either is placed.
+            self.remove_piece(castle.king_from);
+            let rook = self.remove_piece(castle.rook_from);
+            self.put_piece(castle.king_to, piece);
+            if let Some(rook) = rook {
+                self.put_piece(castle.rook_to, rook);
+            }
+        } else {
+            self.remove_piece(chess_move.from);
+            let placed = if piece.kind == PieceKind::Pawn && is_promotion_rank(chess_move.to, piece.color) {
+                Piece {
+                    color: piece.color,
+                    kind: chess_move.promotion.unwrap_or(PieceKind::Queen),
+                }
+            } else {
+                piece
+            };
+            self.put_piece(chess_move.to, placed);
         }
 
-        self.board[chess_move.to as usize] = Some(piece);
-
-        if was_pawn && rank_distance(chess_move.from, chess_move.to) == 2 {
+        if piece.kind == PieceKind::Pawn && rank_distance(chess_move.from, chess_move.to) == 2 {
             self.en_passant = Some(match piece.color {
                 Color::White => chess_move.from + 8,
                 Color::Black => chess_move.from - 8,
@@ -604,7 +1519,7 @@ impl Position {
             self.en_passant = None;
         }
 
-        if was_pawn || captured.is_some() {
+        if piece.kind == PieceKind::Pawn || captured.is_some() {
             self.halfmove_clock = 0;
         } else {
             self.halfmove_clock += 1;
@@ -613,321 +1528,509 @@ impl Position {
             self.fullmove_number += 1;
         }
         self.side_to_move = self.side_to_move.opposite();
-        Ok(())
+        self.key ^= zobrist::side_to_move();
+        if let Some(square) = self.legal_en_passant_square() {
+            self.key ^= zobrist::en_passant_file(file_of(square));
+        }
+        debug_assert_eq!(self.key, self.compute_key(), "incremental Zobrist key diverged after {chess_move}");
+        undo
+    }
+
+    /// Takes back a move played with `make_move`, using the record it returned.
+    pub fn unmake_move(&mut self, chess_move: ChessMove, undo: MoveUndo) {
+        if let Some(castle) = undo.castle {
+            self.remove_piece(castle.king_to);
+            let rook = self.remove_piece(castle.rook_to);
+            self.put_piece(castle.king_from, undo.moved);
+            if let Some(rook) = rook {
+                self.put_piece(castle.rook_from, rook);
+            }
+        } else {
+            self.remove_piece(chess_move.to);
+            self.put_piece(chess_move.from, undo.moved);
+            if let Some(captured) = undo.captured {
+                self.put_piece(undo.captured_square, captured);
+            }
+        }
+
+        self.side_to_move = undo.moved.color;
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.key = undo.key;
+        debug_assert_eq!(self.key, self.compute_key(), "Zobrist key diverged after unmaking {chess_move}");
     }
 
-    fn capture_for_move(&self, piece: Piece, chess_move: ChessMove) -> Option<Piece> {
+    /// Hands the move to the opponent without moving a piece, for null-move
+    /// pruning. The side to move must not be in check.
+    pub fn make_null_move(&mut self) -> NullMoveUndo {
+        let undo = NullMoveUndo {
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            key: self.key,
+        };
+        if let Some(square) = self.legal_en_passant_square() {
+            self.key ^= zobrist::en_passant_file(file_of(square));
+        }
+        self.en_passant = None;
+        self.halfmove_clock += 1;
+        if self.side_to_move == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = self.side_to_move.opposite();
+        self.key ^= zobrist::side_to_move();
+        debug_assert_eq!(self.key, self.compute_key(), "incremental Zobrist key diverged after a null move");
+        undo
+    }
+
+    pub fn unmake_null_move(&mut self, undo: NullMoveUndo) {
+        self.side_to_move = self.side_to_move.opposite();
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.key = undo.key;
+    }
+
+    fn put_piece(&mut self, square: u8, piece: Piece) {
+        let bit = square_bit(square);
+        self.key ^= zobrist::piece(piece.color.index(), piece.kind.index(), square);
+        self.board[square as usize] = Some(piece);
+        self.pieces[piece.color.index()][piece.kind.index()] |= bit;
+        self.occupancy[piece.color.index()] |= bit;
+    }
+
+    fn remove_piece(&mut self, square: u8) -> Option<Piece> {
+        let piece = self.board[square as usize].take()?;
+        let bit = square_bit(square);
+        self.key ^= zobrist::piece(piece.color.index(), piece.kind.index(), square);
+        self.pieces[piece.color.index()][piece.kind.index()] &= !bit;
+        self.occupancy[piece.color.index()] &= !bit;
+        Some(piece)
+    }
+
+    fn captured_square_for_move(&self, piece: Piece, chess_move: ChessMove) -> u8 {
         if piece.kind == PieceKind::Pawn
             && self.en_passant == Some(chess_move.to)
             && file_of(chess_move.from) != file_of(chess_move.to)
             && self.board[chess_move.to as usize].is_none()
         {
-            let captured_square = match piece.color {
-                Color::White => chess_move.to.saturating_sub(8),
+            match piece.color {
+                Color::White => chess_move.to - 8,
                 Color::Black => chess_move.to + 8,
-            };
-            self.board[captured_square as usize]
+            }
         } else {
-            self.board[chess_move.to as usize]
+            chess_move.to
         }
     }
 
     fn update_castling_rights(&mut self, piece: Piece, chess_move: ChessMove, captured: Option<Piece>) {
-        match (piece.color, piece.kind) {
-            (Color::White, PieceKind::King) => {
-                self.castling.white_king_side = false;
-                self.castling.white_queen_side = false;
+        match piece.kind {
+            PieceKind::King => self.castling.clear_color(piece.color),
+            PieceKind::Rook => self.castling.clear_rook_square(piece.color, chess_move.from),
+            _ => {}
+        }
+        if let Some(captured_piece) = captured.filter(|captured| captured.kind == PieceKind::Rook) {
+            self.castling.clear_rook_square(captured_piece.color, chess_move.to);
+        }
+    }
+
+    /// Squares for castling on `side` under the current rights, or `None` when
+    /// the right is gone or cannot be expressed in this position's encoding.
+    fn castling_squares(&self, color: Color, side: CastlingSide) -> Option<CastlingSquares> {
+        let rook_file = self.castling.rook_file(color, side)?;
+        let king_file = self.home_king_file(color)?;
+        if !self.chess960 && (king_file != 4 || rook_file != side.classical_rook_file()) {
+            return None;
+        }
+        let base = back_rank(color) as u8 * 8;
+        Some(CastlingSquares {
+            king_from: base + king_file,
+            king_to: base + side.king_target_file(),
+            rook_from: base + rook_file,
+            rook_to: base + side.rook_target_file(),
+        })
+    }
+
+    fn castling_squares_for_move(&self, chess_move: ChessMove) -> Option<CastlingSquares> {
+        let king = self.board[chess_move.from as usize].filter(|piece| piece.kind == PieceKind::King)?;
+        if rank_of(chess_move.from) != back_rank(king.color) || rank_of(chess_move.to) != back_rank(king.color) {
+            return None;
+        }
+        let base = back_rank(king.color) as u8 * 8;
+        let side = if chess_move.to > chess_move.from {
+            CastlingSide::King
+        } else {
+            CastlingSide::Queen
+        };
+        let rook_from = if self.chess960 {
+            let rook = Piece { color: king.color, kind: PieceKind::Rook };
+            if self.board[chess_move.to as usize] != Some(rook) {
+                return None;
             }
-            (Color::Black, PieceKind::King) => {
-                self.castling.black_king_side = false;
-                self.castling.black_queen_side = false;
+            chess_move.to
+        } else {
+            if file_distance(chess_move.from, chess_move.to) != 2 {
+                return None;
             }
-            (Color::White, PieceKind::Rook) => match chess_move.from {
-                0 => self.castling.white_queen_side = false,
-                7 => self.castling.white_king_side = false,
-                _ => {}
-            },
-            (Color::Black, PieceKind::Rook) => match chess_move.from {
-                56 => self.castling.black_queen_side = false,
-                63 => self.castling.black_king_side = false,
-                _ => {}
-            },
-            _ => {}
+            base + side.classical_rook_file()
+        };
+        Some(CastlingSquares {
+            king_from: chess_move.from,
+            king_to: base + side.king_target_file(),
+            rook_from,
+            rook_to: base + side.rook_target_file(),
+        })
+    }
+
+    /// Checkers of `color`'s king and the pieces pinned against it.
+    fn move_masks(&self, color: Color, king: u8) -> MoveMasks {
+        let checkers = self.attackers_to(king, color.opposite());
+        let check_mask = match checkers.count_ones() {
+            0 => !0,
+            1 => between(king, lsb(checkers)) | checkers,
+            _ => 0,
+        };
+        let mut masks = MoveMasks { checkers, check_mask, pinned: 0, pin_rays: [0; 64] };
+        for pin in self.pins_against(color, king) {
+            masks.pinned |= square_bit(pin.square);
+            masks.pin_rays[pin.square as usize] = pin.ray;
+        }
+        masks
+    }
+
+    fn pins_against(&self, color: Color, king: u8) -> impl Iterator<Item = Pin> + '_ {
+        let them = color.opposite();
+        let occupied = self.occupied();
+        let own = self.occupancy(color);
+        let queens = self.pieces(them, PieceKind::Queen);
+        let snipers = (rook_attacks(king, 0) & (self.pieces(them, PieceKind::Rook) | queens))
+            | (bishop_attacks(king, 0) & (self.pieces(them, PieceKind::Bishop) | queens));
+        squares(snipers).filter_map(move |pinner| {
+            let ray = between(king, pinner);
+            let blockers = ray & occupied;
+            (blockers.count_ones() == 1 && blockers & own != 0).then(|| Pin {
+                square: lsb(blockers),
+                pinner,
+                ray: ray | square_bit(pinner),
+            })
+        })
+    }
+
+    fn add_pawn_moves<S: MoveSink>(&self, stage: MoveStage, masks: &MoveMasks, moves: &mut S) {
+        let us = self.side_to_move;
+        let them = us.opposite();
+        let white = us == Color::White;
+        let empty = !self.occupied();
+        let enemies = self.occupancy(them) & !self.pieces(them, PieceKind::King);
+        let en_passant = self.en_passant.filter(|square| {
+            let captured_square = if white { square - 8 } else { square + 8 };
+            self.board[captured_square as usize] == Some(Piece { color: them, kind: PieceKind::Pawn })
+        });
+        let start_rank = if white { RANK_2 } else { RANK_7 };
+        let captures = !matches!(stage, MoveStage::Quiets | MoveStage::QuietChecks);
+        let quiets = stage != MoveStage::CapturesAndPromotions;
+
+        for from in squares(self.pieces(us, PieceKind::Pawn)) {
+            let allowed = masks.allowed(from);
+            let single = pawn_push(white, square_bit(from)) & empty;
+            if single != 0 {
+                let to = lsb(single);
+                if single & allowed != 0 && (if is_promotion_rank(to, us) { captures } else { quiets }) {
+                    push_pawn_move(from, to, us, MoveKind::Quiet, moves);
+                }
+                if quiets && square_bit(from) & start_rank != 0 {
+                    let double = pawn_push(white, single) & empty & allowed;
+                    if double != 0 {
+                        moves.add(PackedMove::new(from, lsb(double), MoveKind::DoublePawnPush));
+                    }
+                }
+            }
+            if !captures {
+                continue;
+            }
+            let mut targets = pawn_attacks(white, from) & enemies & allowed;
+            if let Some(square) = en_passant {
+                if pawn_attacks(white, from) & square_bit(square) != 0 && self.en_passant_is_safe(from, square) {
+                    targets |= square_bit(square);
+                }
+            }
+            for to in squares(targets) {
+                let kind = if en_passant == Some(to) { MoveKind::EnPassant } else { MoveKind::Capture };
+                push_pawn_move(from, to, us, kind, moves);
+            }
+        }
+    }
+
+    /// En passant empties two squares on the capturing pawn's rank, so pins
+    /// are checked against the occupancy after the capture instead of the masks.
+    fn en_passant_is_safe(&self, from: u8, to: u8) -> bool {
+        let us = self.side_to_move;
+        let Some(king) = self.king_square(us) else {
+            return false;
+        };
+        let captured = if us == Color::White { to - 8 } else { to + 8 };
+        let occupied = (self.occupied() & !square_bit(from) & !square_bit(captured)) | square_bit(to);
+        self.attackers_to_with_occupancy(king, us.opposite(), occupied) & !square_bit(captured) == 0
+    }
+
+    fn add_castling_moves<S: MoveSink>(&self, color: Color, moves: &mut S) {
+        for side in CastlingSide::BOTH {
+            let Some(castle) = self.castling_squares(color, side) else {
+                continue;
+            };
+            if self.can_castle(color, castle) {
+                let to = if self.chess960 { castle.rook_from } else { castle.king_to };
+                moves.add(PackedMove::new(castle.king_from, to, MoveKind::Castle(side)));
+            }
+        }
+    }
+
+    /// Every square the king or rook crosses must be empty apart from the two
+    /// castling pieces, and the king may not pass through an attacked square.
+    /// The king's final square is tested with the castled occupancy, so a
+    /// Chess960 rook that leaves the back rank open cannot expose the king.
+    fn can_castle(&self, color: Color, castle: CastlingSquares) -> bool {
+        if self.board[castle.rook_from as usize] != Some(Piece { color, kind: PieceKind::Rook }) {
+            return false;
+        }
+        let others = self.occupied() & !square_bit(castle.king_from) & !square_bit(castle.rook_from);
+        let crossed = rank_span(castle.king_from, castle.king_to) | rank_span(castle.rook_from, castle.rook_to);
+        if crossed & others != 0 {
+            return false;
         }
+        let them = color.opposite();
+        let path_is_safe = squares(rank_span(castle.king_from, castle.king_to) & !square_bit(castle.king_from))
+            .all(|square| !self.is_square_attacked(square, them));
+        let castled = others | square_bit(castle.king_to) | square_bit(castle.rook_to);
+        path_is_safe && self.attackers_to_with_occupancy(castle.king_to, them, castled) == 0
+    }
+}
+
+/// Check and pin information for the side to move.
+struct MoveMasks {
+    checkers: Bitboard,
+    /// Where a non-king move must land: the checker or a square between it
+    /// and the king, the whole board out of check, nothing in double check.
+    check_mask: Bitboard,
+    pinned: Bitboard,
+    /// For each pinned piece, the squares from the king (exclusive) to the pinner.
+    pin_rays: [Bitboard; 64],
+}
+
+impl MoveMasks {
+    fn allowed(&self, from: u8) -> Bitboard {
+        if self.pinned & square_bit(from) != 0 {
+            self.check_mask & self.pin_rays[from as usize]
+        } else {
+            self.check_mask
+        }
+    }
+}
+
+/// How a finished game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate { winner: Color },
+    Draw(DrawReason),
+}
+
+impl Termination {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Checkmate { .. } => "checkmate",
+            Self::Draw(reason) => reason.label(),
+        }
+    }
+
+    /// PGN result token.
+    pub fn result(self) -> &'static str {
+        match self {
+            Self::Checkmate { winner: Color::White } => "1-0",
+            Self::Checkmate { winner: Color::Black } => "0-1",
+            Self::Draw(_) => "1/2-1/2",
+        }
+    }
+}
+
+/// A game in progress: start position, moves played, the current position and
+/// the Zobrist key of every position on the way. Each pushed move records how
+/// often its position has occurred so far, looking back only as far as the
+/// last capture or pawn move, so repetition and draw queries never replay the
+/// game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game {
+    start: Position,
+    position: Position,
+    moves: Vec<ChessMove>,
+    undos: Vec<MoveUndo>,
+    keys: Vec<u64>,
+    repetitions: Vec<u8>,
+    termination: Option<Termination>,
+}
+
+impl Game {
+    pub fn new(start: Position) -> Self {
+        let key = start.zobrist_key();
+        let mut game = Self {
+            position: start.clone(),
+            start,
+            moves: Vec::new(),
+            undos: Vec::new(),
+            keys: vec![key],
+            repetitions: vec![1],
+            termination: None,
+        };
+        game.termination = game.compute_termination();
+        game
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        Ok(Self::new(Position::from_fen(fen.trim())?))
+    }
 
-        if let Some(captured_piece) = captured {
-            if captured_piece.kind == PieceKind::Rook {
-                match chess_move.to {
-                    0 => self.castling.white_queen_side = false,
-                    7 => self.castling.white_king_side = false,
-                    56 => self.castling.black_queen_side = false,
-                    63 => self.castling.black_king_side = false,
-                    _ => {}
-                }
-            }
+    /// Game from a start FEN and a list of moves, each checked for legality.
+    pub fn from_moves(start_fen: &str, moves: &[ChessMove]) -> Result<Self, ChessError> {
+        let mut game = Self::from_fen(start_fen)?;
+        for chess_move in moves {
+            game.push(*chess_move)?;
         }
+        Ok(game)
     }
 
-    fn move_castling_rook(&mut self, color: Color, king_to: u8) -> Result<(), String> {
-        let (rook_from, rook_to) = match (color, king_to) {
-            (Color::White, 6) => (7, 5),
-            (Color::White, 2) => (0, 3),
-            (Color::Black, 62) => (63, 61),
-            (Color::Black, 58) => (56, 59),
-            _ => return Err("bad castling move".to_string()),
-        };
-        let rook = self.board[rook_from as usize]
-            .take()
-            .ok_or_else(|| "castling rook is absent".to_string())?;
-        self.board[rook_to as usize] = Some(rook);
-        Ok(())
+    pub fn start_position(&self) -> &Position {
+        &self.start
     }
 
-    fn pseudo_legal_moves(&self) -> Vec<ChessMove> {
-        let mut moves = Vec::with_capacity(64);
-        for square in 0_u8..64 {
-            let Some(piece) = self.board[square as usize] else {
-                continue;
-            };
-            if piece.color != self.side_to_move {
-                continue;
-            }
-            match piece.kind {
-                PieceKind::Pawn => self.add_pawn_moves(square, piece, &mut moves),
-                PieceKind::Knight => self.add_knight_moves(square, piece, &mut moves),
-                PieceKind::Bishop => self.add_slider_moves(square, piece, &mut moves, &BISHOP_DIRS),
-                PieceKind::Rook => self.add_slider_moves(square, piece, &mut moves, &ROOK_DIRS),
-                PieceKind::Queen => self.add_slider_moves(square, piece, &mut moves, &QUEEN_DIRS),
-                PieceKind::King => self.add_king_moves(square, piece, &mut moves),
-            }
-        }
-        moves
+    pub fn start_fen(&self) -> String {
+        self.start.to_fen()
     }
 
-    fn add_pawn_moves(&self, square: u8, piece: Piece, moves: &mut Vec<ChessMove>) {
-        let direction = match piece.color {
-            Color::White => 1,
-            Color::Black => -1,
-        };
-        let rank = rank_of(square);
-        let file = file_of(square);
-        let one_rank = rank + direction;
-        if let Some(to) = index(file, one_rank) {
-            if self.board[to as usize].is_none() {
-                self.push_pawn_move(square, to, piece.color, moves);
-                let start_rank = match piece.color {
-                    Color::White => 1,
-                    Color::Black => 6,
-                };
-                if rank == start_rank {
-                    if let Some(two) = index(file, rank + direction * 2) {
-                        if self.board[two as usize].is_none() {
-                            moves.push(ChessMove::new(square, two, None));
-                        }
-                    }
-                }
-            }
-        }
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
 
-        for df in [-1, 1] {
-            if let Some(to) = index(file + df, one_rank) {
-                let can_capture = self.board[to as usize]
-                    .map(|target| target.color != piece.color && target.kind != PieceKind::King)
-                    .unwrap_or(false)
-                    || self.en_passant == Some(to);
-                if can_capture {
-                    self.push_pawn_move(square, to, piece.color, moves);
-                }
-            }
-        }
+    pub fn moves(&self) -> &[ChessMove] {
+        &self.moves
     }
 
-    fn push_pawn_move(&self, from: u8, to: u8, color: Color, moves: &mut Vec<ChessMove>) {
-        if is_promotion_rank(to, color) {
-            for promotion in [PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight] {
-                moves.push(ChessMove::new(from, to, Some(promotion)));
-            }
-        } else {
-            moves.push(ChessMove::new(from, to, None));
-        }
+    pub fn last_move(&self) -> Option<ChessMove> {
+        self.moves.last().copied()
     }
 
-    fn add_knight_moves(&self, square: u8, piece: Piece, moves: &mut Vec<ChessMove>) {
-        for (df, dr) in KNIGHT_DIRS {
-            if let Some(to) = index(file_of(square) + df, rank_of(square) + dr) {
-                self.push_if_free_or_enemy(square, to, piece.color, moves);
-            }
+    /// Plays a legal move.
+    pub fn push(&mut self, chess_move: ChessMove) -> Result<(), MoveError> {
+        if !self.position.legal_moves().contains(&chess_move) {
+            return Err(MoveError::Illegal { uci: chess_move.to_uci(), fen: self.position.to_fen() });
         }
+        let undo = self.position.make_move(chess_move);
+        let key = self.position.zobrist_key();
+        let repetitions = self
+            .previous_occurrence(key)
+            .map(|ply| self.repetitions[ply].saturating_add(1))
+            .unwrap_or(1);
+        self.moves.push(chess_move);
+        self.undos.push(undo);
+        self.keys.push(key);
+        self.repetitions.push(repetitions);
+        self.termination = self.compute_termination();
+        Ok(())
     }
 
-    fn add_slider_moves(&self, square: u8, piece: Piece, moves: &mut Vec<ChessMove>, dirs: &[(i32, i32)]) {
-        for &(df, dr) in dirs {
-            let mut file = file_of(square) + df;
-            let mut rank = rank_of(square) + dr;
-            while let Some(to) = index(file, rank) {
-                if let Some(target) = self.board[to as usize] {
-                    if target.color != piece.color && target.kind != PieceKind::King {
-                        moves.push(ChessMove::new(square, to, None));
-                    }
-                    break;
-                }
-                moves.push(ChessMove::new(square, to, None));
-                file += df;
-                rank += dr;
-            }
-        }
+    /// Parses a UCI move in the current position and plays it.
+    pub fn push_uci(&mut self, text: &str) -> Result<ChessMove, MoveError> {
+        let chess_move = self
+            .position
+            .parse_uci_move(text)
+            .ok_or_else(|| MoveError::Illegal { uci: text.to_string(), fen: self.position.to_fen() })?;
+        self.push(chess_move)?;
+        Ok(chess_move)
     }
 
-    fn add_king_moves(&self, square: u8, piece: Piece, moves: &mut Vec<ChessMove>) {
-        for (df, dr) in KING_DIRS {
-            if let Some(to) = index(file_of(square) + df, rank_of(square) + dr) {
-                self.push_if_free_or_enemy(square, to, piece.color, moves);
-            }
-        }
-        self.add_castling_moves(piece.color, moves);
+    /// Takes back the last move.
+    pub fn pop(&mut self) -> Option<ChessMove> {
+        let chess_move = self.moves.pop()?;
+        let undo = self.undos.pop().expect("one undo record per move");
+        self.position.unmake_move(chess_move, undo);
+        self.keys.pop();
+        self.repetitions.pop();
+        self.termination = self.compute_termination();
+        Some(chess_move)
     }
 
-    fn add_castling_moves(&self, color: Color, moves: &mut Vec<ChessMove>) {
-        if self.is_in_check(color) {
-            return;
-        }
-        match color {
-            Color::White => {
-                if self.castling.white_king_side
-                    && self.can_castle(Color::White, 4, 7, &[5, 6], &[5, 6])
-                {
-                    moves.push(ChessMove::new(4, 6, None));
-                }
-                if self.castling.white_queen_side
-                    && self.can_castle(Color::White, 4, 0, &[1, 2, 3], &[3, 2])
-                {
-                    moves.push(ChessMove::new(4, 2, None));
-                }
-            }
-            Color::Black => {
-                if self.castling.black_king_side
-                    && self.can_castle(Color::Black, 60, 63, &[61, 62], &[61, 62])
-                {
-                    moves.push(ChessMove::new(60, 62, None));
-                }
-                if self.castling.black_queen_side
-                    && self.can_castle(Color::Black, 60, 56, &[57, 58, 59], &[59, 58])
-                {
-                    moves.push(ChessMove::new(60, 58, None));
-                }
-            }
-        }
+    /// Keys of the positions since the last capture or pawn move, oldest
+    /// first, ending with the current position. Only these can repeat.
+    pub fn keys_since_irreversible(&self) -> &[u64] {
+        let current = self.keys.len() - 1;
+        let window = (self.position.halfmove_clock() as usize).min(current);
+        &self.keys[current - window..]
     }
 
-    fn can_castle(
-        &self,
-        color: Color,
-        king_square: u8,
-        rook_square: u8,
-        empty_squares: &[u8],
-        safe_squares: &[u8],
-    ) -> bool {
-        self.board[king_square as usize]
-            == Some(Piece {
-                color,
-                kind: PieceKind::King,
-            })
-            && self.board[rook_square as usize]
-                == Some(Piece {
-                    color,
-                    kind: PieceKind::Rook,
-                })
-            && empty_squares.iter().all(|square| self.board[*square as usize].is_none())
-            && safe_squares
-                .iter()
-                .all(|square| !self.is_square_attacked(*square, color.opposite()))
-    }
-
-    fn push_if_free_or_enemy(&self, from: u8, to: u8, color: Color, moves: &mut Vec<ChessMove>) {
-        if self.board[to as usize]
-            .map(|target| target.color != color && target.kind != PieceKind::King)
-            .unwrap_or(true)
-        {
-            moves.push(ChessMove::new(from, to, None));
+    /// How many times the current position has occurred, this time included.
+    pub fn repetition_count(&self) -> usize {
+        self.repetitions.last().copied().unwrap_or(1) as usize
+    }
+
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        match self.termination {
+            Some(Termination::Draw(reason)) => Some(reason),
+            _ => None,
         }
     }
 
-    fn is_square_attacked(&self, square: u8, by_color: Color) -> bool {
-        let file = file_of(square);
-        let rank = rank_of(square);
+    /// Checkmate or the draw the rules give for the current position. Claimable
+    /// draws (50 moves, threefold) count as the end of the game here.
+    pub fn termination(&self) -> Option<Termination> {
+        self.termination
+    }
 
-        let pawn_source_rank = match by_color {
-            Color::White => rank - 1,
-            Color::Black => rank + 1,
-        };
-        for df in [-1, 1] {
-            if let Some(from) = index(file + df, pawn_source_rank) {
-                if self.board[from as usize]
-                    == Some(Piece {
-                        color: by_color,
-                        kind: PieceKind::Pawn,
-                    })
-                {
-                    return true;
-                }
-            }
-        }
+    pub fn is_over(&self) -> bool {
+        self.termination.is_some()
+    }
 
-        for (df, dr) in KNIGHT_DIRS {
-            if let Some(from) = index(file + df, rank + dr) {
-                if self.board[from as usize]
-                    == Some(Piece {
-                        color: by_color,
-                        kind: PieceKind::Knight,
-                    })
-                {
-                    return true;
-                }
-            }
-        }
+    /// PGN result token: `1-0`, `0-1`, `1/2-1/2` or `*`.
+    pub fn result(&self) -> &'static str {
+        self.termination.map(Termination::result).unwrap_or("*")
+    }
 
-        for (df, dr) in KING_DIRS {
-            if let Some(from) = index(file + df, rank + dr) {
-                if self.board[from as usize]
-                    == Some(Piece {
-                        color: by_color,
-                        kind: PieceKind::King,
-                    })
-                {
-                    return true;
-                }
-            }
-        }
+    /// Ply of the latest earlier position with `key`. Positions repeat with the
+    /// same side to move, so only every second key is compared.
+    fn previous_occurrence(&self, key: u64) -> Option<usize> {
+        let next = self.keys.len();
+        let window = self.position.halfmove_clock() as usize;
+        let first = next.saturating_sub(window);
+        (first..next.saturating_sub(1)).rev().step_by(2).find(|ply| self.keys[*ply] == key)
+    }
 
-        self.attacked_by_slider(square, by_color, &BISHOP_DIRS, &[PieceKind::Bishop, PieceKind::Queen])
-            || self.attacked_by_slider(square, by_color, &ROOK_DIRS, &[PieceKind::Rook, PieceKind::Queen])
-    }
-
-    fn attacked_by_slider(
-        &self,
-        square: u8,
-        by_color: Color,
-        dirs: &[(i32, i32)],
-        attackers: &[PieceKind],
-    ) -> bool {
-        for &(df, dr) in dirs {
-            let mut file = file_of(square) + df;
-            let mut rank = rank_of(square) + dr;
-            while let Some(from) = index(file, rank) {
-                if let Some(piece) = self.board[from as usize] {
-                    if piece.color == by_color && attackers.contains(&piece.kind) {
-                        return true;
-                    }
-                    break;
-                }
-                file += df;
-                rank += dr;
-            }
+    fn compute_termination(&self) -> Option<Termination> {
+        let position = &self.position;
+        if position.legal_moves().is_empty() {
+            return Some(if position.is_in_check(position.side_to_move()) {
+                Termination::Checkmate { winner: position.side_to_move().opposite() }
+            } else {
+                Termination::Draw(DrawReason::Stalemate)
+            });
         }
-        false
+        let repetitions = self.repetition_count();
+        let reason = if position.is_insufficient_material() {
+            DrawReason::InsufficientMaterial
+        } else if position.is_seventy_five_move_rule_draw() {
+            DrawReason::SeventyFiveMoveRule
+        } else if repetitions >= 5 {
+            DrawReason::FivefoldRepetition
+        } else if position.is_fifty_move_rule_draw() {
+            DrawReason::FiftyMoveRule
+        } else if repetitions >= 3 {
+            DrawReason::ThreefoldRepetition
+        } else {
+            return None;
+        };
+        Some(Termination::Draw(reason))
     }
 }
 
+
 pub fn parse_square(value: &str) -> Option<u8> {
     let bytes = value.as_bytes();
     if bytes.len() != 2 {
@@ -978,38 +2081,122 @@ fn rank_distance(from: u8, to: u8) -> i32 {
     (rank_of(from) - rank_of(to)).abs()
 }
 
-const KNIGHT_DIRS: [(i32, i32); 8] = [
-    (1, 2),
-    (2, 1),
-    (2, -1),
-    (1, -2),
-    (-1, -2),
-    (-2, -1),
-    (-2, 1),
-    (-1, 2),
-];
-const KING_DIRS: [(i32, i32); 8] = [
-    (1, 0),
-    (1, 1),
-    (0, 1),
-    (-1, 1),
-    (-1, 0),
-    (-1, -1),
-    (0, -1),
-    (1, -1),
-];
-const BISHOP_DIRS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
-const ROOK_DIRS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
-const QUEEN_DIRS: [(i32, i32); 8] = [
-    (1, 1),
-    (1, -1),
-    (-1, 1),
-    (-1, -1),
-    (1, 0),
-    (-1, 0),
-    (0, 1),
-    (0, -1),
-];
+fn castling_key(rights: CastlingRights) -> u64 {
+    let mut key = 0;
+    for color in [Color::White, Color::Black] {
+        for side in CastlingSide::BOTH {
+            if let Some(file) = rights.rook_file(color, side) {
+                let right = color.index() * 2 + side.index();
+                key ^= zobrist::castling(right) ^ zobrist::castling_rook_file(right, file);
+            }
+        }
+    }
+    key
+}
+
+fn back_rank(color: Color) -> i32 {
+    match color {
+        Color::White => 0,
+        Color::Black => 7,
+    }
+}
+
+/// All squares between `from` and `to` on one rank, both ends included.
+fn rank_span(from: u8, to: u8) -> Bitboard {
+    let (low, high) = (from.min(to), from.max(to));
+    (u64::MAX >> (63 - high)) & (u64::MAX << low)
+}
+
+/// Standard Chess960 numbering: bishops, queen and knights are placed by the
+/// digits of `index`, then rook, king and rook fill the remaining files.
+pub fn chess960_start_fen(index: u16) -> Result<String, FenError> {
+    if index > 959 {
+        return Err(FenError::Chess960Index { index });
+    }
+    const KNIGHTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+    let mut rank: [Option<char>; 8] = [None; 8];
+    let mut rest = index as usize;
+    rank[rest % 4 * 2 + 1] = Some('b');
+    rest /= 4;
+    rank[rest % 4 * 2] = Some('b');
+    rest /= 4;
+    let empty_files = |rank: &[Option<char>; 8]| (0..8).filter(|file| rank[*file].is_none()).collect::<Vec<_>>();
+    let queen_file = empty_files(&rank)[rest % 6];
+    rank[queen_file] = Some('q');
+    rest /= 6;
+    let (first_knight, second_knight) = KNIGHTS[rest];
+    let free = empty_files(&rank);
+    rank[free[first_knight]] = Some('n');
+    rank[free[second_knight]] = Some('n');
+    let free = empty_files(&rank);
+    rank[free[0]] = Some('r');
+    rank[free[1]] = Some('k');
+    rank[free[2]] = Some('r');
+
+    let black: String = rank.iter().map(|piece| piece.unwrap_or('?')).collect();
+    let white = black.to_ascii_uppercase();
+    let rook_files: String = [free[2], free[0]].iter().map(|file| (b'a' + *file as u8) as char).collect();
+    Ok(format!(
+        "{black}/pppppppp/8/8/8/8/PPPPPPPP/{white} w {}{} - 0 1",
+        rook_files.to_ascii_uppercase(),
+        rook_files
+    ))
+}
+
+fn pawn_push(white: bool, pawns: Bitboard) -> Bitboard {
+    if white {
+        pawns << 8
+    } else {
+        pawns >> 8
+    }
+}
+
+fn piece_attacks(kind: PieceKind, color: Color, square: u8, occupied: Bitboard) -> Bitboard {
+    match kind {
+        PieceKind::Pawn => pawn_attacks(color == Color::White, square),
+        PieceKind::Knight => knight_attacks(square),
+        PieceKind::Bishop => bishop_attacks(square, occupied),
+        PieceKind::Rook => rook_attacks(square, occupied),
+        PieceKind::Queen => queen_attacks(square, occupied),
+        PieceKind::King => king_attacks(square),
+    }
+}
+
+/// Where the generator writes its moves: `ChessMove` lists drop the kind flags.
+trait MoveSink {
+    fn add(&mut self, packed: PackedMove);
+}
+
+impl MoveSink for Vec<ChessMove> {
+    fn add(&mut self, packed: PackedMove) {
+        self.push(packed.to_move());
+    }
+}
+
+impl MoveSink for Vec<PackedMove> {
+    fn add(&mut self, packed: PackedMove) {
+        self.push(packed);
+    }
+}
+
+/// `kind` is the move's kind without promotion; promotions get all four pieces.
+fn push_pawn_move<S: MoveSink>(from: u8, to: u8, color: Color, kind: MoveKind, moves: &mut S) {
+    if is_promotion_rank(to, color) {
+        let capture = kind == MoveKind::Capture;
+        for piece in [PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight] {
+            moves.add(PackedMove::new(from, to, MoveKind::Promotion { piece, capture }));
+        }
+    } else {
+        moves.add(PackedMove::new(from, to, kind));
+    }
+}
+
+fn push_target_moves<S: MoveSink>(from: u8, targets: Bitboard, enemies: Bitboard, moves: &mut S) {
+    for to in squares(targets) {
+        let kind = if enemies & square_bit(to) != 0 { MoveKind::Capture } else { MoveKind::Quiet };
+        moves.add(PackedMove::new(from, to, kind));
+    }
+}
 
 impl fmt::Display for ChessMove {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1105,6 +2292,150 @@ mod tests {
         }
     }
 
+    #[test]
+    fn staged_generation_partitions_legal_moves_on_perft_positions() {
+        fn sorted(mut moves: Vec<ChessMove>) -> Vec<ChessMove> {
+            moves.sort_by_key(|chess_move| (chess_move.from, chess_move.to, chess_move.promotion.map(PieceKind::index)));
+            moves
+        }
+
+        fn walk(position: &mut Position, depth: u32) {
+            let fen = position.to_fen();
+            let legal = position.legal_moves();
+            let captures = position.legal_captures();
+            let quiets = position.legal_quiets();
+            assert!(captures.iter().all(|chess_move| position.is_capture(*chess_move) || chess_move.promotion.is_some()), "{fen}");
+            assert!(quiets.iter().all(|chess_move| !position.is_capture(*chess_move) && chess_move.promotion.is_none()), "{fen}");
+            let union = captures.iter().chain(&quiets).copied().collect();
+            assert_eq!(sorted(union), sorted(legal.clone()), "captures + quiets differ from legal_moves in {fen}");
+
+            let in_check = position.is_in_check(position.side_to_move());
+            let expected_evasions = if in_check { legal.clone() } else { Vec::new() };
+            assert_eq!(position.legal_evasions(), expected_evasions, "{fen}");
+
+            let mut quiet_checks = Vec::new();
+            for chess_move in legal {
+                let gives_check = position.gives_check(chess_move);
+                let undo = position.make_move(chess_move);
+                assert_eq!(gives_check, position.is_in_check(position.side_to_move()), "{chess_move} in {fen}");
+                if depth > 1 {
+                    walk(position, depth - 1);
+                }
+                position.unmake_move(chess_move, undo);
+                if gives_check && quiets.contains(&chess_move) {
+                    quiet_checks.push(chess_move);
+                }
+            }
+            assert_eq!(sorted(position.legal_quiet_checks()), sorted(quiet_checks), "{fen}");
+        }
+
+        let fens = [
+            STARTPOS_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        ];
+        for fen in fens {
+            walk(&mut Position::from_fen(fen).unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn attack_maps_report_attackers_checkers_pins_and_xrays() {
+        let square = |name: &str| parse_square(name).unwrap();
+        let bits = |names: &[&str]| names.iter().fold(0, |board, name| board | square_bit(square(name)));
+
+        let position = Position::from_fen("4r1k1/8/8/8/1b6/8/3NR3/4K3 w - - 0 1").unwrap();
+        assert_eq!(position.checkers(), 0);
+        assert_eq!(
+            position.pinned_pieces(Color::White),
+            vec![
+                Pin { square: square("d2"), pinner: square("b4"), ray: bits(&["d2", "c3", "b4"]) },
+                Pin { square: square("e2"), pinner: square("e8"), ray: bits(&["e2", "e3", "e4", "e5", "e6", "e7", "e8"]) },
+            ]
+        );
+        assert!(position.pinned_pieces(Color::Black).is_empty());
+        assert!(position.is_square_attacked(square("c3"), Color::Black));
+        assert!(!position.is_square_attacked(square("e1"), Color::Black));
+        assert_ne!(position.attacks_from(square("b4")) & bits(&["d2"]), 0);
+        assert_eq!(position.attacks_from(square("b4")) & bits(&["e1"]), 0);
+        assert_eq!(position.attackers_to(square("d2"), Color::Black), bits(&["b4"]));
+        assert_eq!(position.attackers_to(square("e4"), Color::White), bits(&["d2", "e2"]));
+
+        let battery = Position::from_fen("4k3/8/8/8/3q4/8/3R4/3RK3 b - - 0 1").unwrap();
+        assert_eq!(battery.attackers_to(square("d4"), Color::White), bits(&["d2"]));
+        assert_eq!(battery.xray_attackers_to(square("d4"), Color::White), bits(&["d1"]));
+        assert_eq!(battery.xray_attackers_to(square("d4"), Color::Black), 0);
+        let without_front_rook = battery.occupied() & !bits(&["d2"]);
+        assert_eq!(
+            battery.attackers_to_with_occupancy(square("d4"), Color::White, without_front_rook),
+            bits(&["d1", "d2"])
+        );
+
+        let check = Position::from_fen("4k3/8/8/8/1b6/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(check.checkers(), bits(&["b4"]));
+    }
+
+    #[test]
+    fn make_unmake_restores_position_for_every_legal_move() {
+        let fens = [
+            STARTPOS_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1",
+        ];
+        for fen in fens {
+            let original = Position::from_fen(fen).unwrap();
+            let mut position = original.clone();
+            for chess_move in original.legal_moves() {
+                let undo = position.make_move(chess_move);
+                for reply in position.legal_moves() {
+                    let reply_undo = position.make_move(reply);
+                    position.unmake_move(reply, reply_undo);
+                }
+                position.unmake_move(chess_move, undo);
+                assert_eq!(position, original, "make/unmake of {chess_move} changed {fen}");
+            }
+        }
+    }
+
+    #[test]
+    fn null_move_passes_the_turn_and_unmakes_cleanly() {
+        let original = Position::from_fen("rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").unwrap();
+        let mut position = original.clone();
+        let undo = position.make_null_move();
+        assert_eq!(position.to_fen(), "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 1 3");
+        assert_eq!(position.zobrist_key(), Position::from_fen(&position.to_fen()).unwrap().zobrist_key());
+        let reply = position.parse_uci_move("g8f6").unwrap();
+        let reply_undo = position.make_move(reply);
+        position.unmake_move(reply, reply_undo);
+        position.unmake_null_move(undo);
+        assert_eq!(position, original);
+    }
+
+    #[test]
+    fn bitboards_follow_the_mailbox_after_moves() {
+        let mut position = Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for move_text in ["e1c1", "h3g2", "d5e6", "g2h1q"] {
+            let chess_move = position.parse_uci_move(move_text).unwrap();
+            position.make_legal_move(chess_move).unwrap();
+            for square in 0_u8..64 {
+                let from_bitboards = [Color::White, Color::Black].into_iter().find_map(|color| {
+                    PieceKind::ALL
+                        .into_iter()
+                        .find(|kind| position.pieces(color, *kind) & square_bit(square) != 0)
+                        .map(|kind| Piece { color, kind })
+                });
+                assert_eq!(from_bitboards, position.piece_at(square), "square {} after {move_text}", square_name(square));
+            }
+        }
+    }
+
     #[test]
     fn pinned_rook_can_only_move_along_pin_line() {
         let position = Position::from_fen("4r2k/8/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
@@ -1202,6 +2533,40 @@ mod tests {
         assert_ne!(with_legal_ep.repetition_key(), same_without_ep.repetition_key());
     }
 
+    #[test]
+    fn zobrist_key_ignores_counters_and_non_legal_en_passant_targets() {
+        let with_spurious_ep = Position::from_fen("4k3/8/8/8/P7/8/8/4K3 b - a3 0 1").unwrap();
+        let without_ep = Position::from_fen("4k3/8/8/8/P7/8/8/4K3 b - - 17 30").unwrap();
+        assert_eq!(with_spurious_ep.zobrist_key(), without_ep.zobrist_key());
+
+        let with_legal_ep = Position::from_fen("4k3/8/8/8/pP6/8/8/4K3 b - b3 0 1").unwrap();
+        let same_without_ep = Position::from_fen("4k3/8/8/8/pP6/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(with_legal_ep.zobrist_key(), same_without_ep.zobrist_key());
+
+        let pinned_ep = Position::from_fen("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1").unwrap();
+        let pinned_without_ep = Position::from_fen("8/8/8/K2pP2r/8/8/8/7k w - - 0 1").unwrap();
+        assert_eq!(pinned_ep.zobrist_key(), pinned_without_ep.zobrist_key());
+    }
+
+    #[test]
+    fn incremental_zobrist_key_matches_key_from_scratch() {
+        let mut position = Position::startpos();
+        for move_text in ["e2e4", "d7d5", "e4e5", "f7f5", "e5f6", "g8f6", "g1f3", "b8c6", "f1b5", "c8d7", "e1g1", "e7e5"] {
+            let chess_move = position.parse_uci_move(move_text).unwrap();
+            let before = position.zobrist_key();
+            let undo = position.make_move(chess_move);
+            assert_eq!(position.zobrist_key(), position.compute_key(), "after {move_text}");
+            assert_eq!(
+                position.zobrist_key(),
+                Position::from_fen(&position.to_fen()).unwrap().zobrist_key(),
+                "FEN roundtrip after {move_text}"
+            );
+            position.unmake_move(chess_move, undo);
+            assert_eq!(position.zobrist_key(), before);
+            position.make_move(chess_move);
+        }
+    }
+
     #[test]
     fn detects_threefold_repetition_from_history() {
         let mut position = Position::startpos();
@@ -1220,6 +2585,206 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fen_and_move_errors_are_typed() {
+        let cases = [
+            ("8/8/8/8/8/8/8/8 w -", FenError::FieldCount { found: 3 }),
+            ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::RankCount { found: 7 }),
+            ("4k3/8/8/8/8/9/8/4K3 w - - 0 1", FenError::BadRank { rank: 3 }),
+            ("4k3/8/8/8/8/8/8/4K4 w - - 0 1", FenError::BadRank { rank: 1 }),
+            ("4k3/8/8/8/8/8/8/4KX2 w - - 0 1", FenError::BadPiece { rank: 1, symbol: 'X' }),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::BadSideToMove { value: "x".to_string() }),
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", FenError::MissingKing { color: Color::Black }),
+            ("4k3/8/8/8/8/8/8/4K3 w Z - 0 1", FenError::BadCastling { value: "Z".to_string() }),
+            ("4k3/8/8/8/8/8/8/4K3 w - e4 0 1", FenError::BadEnPassant { value: "e4".to_string() }),
+            ("4k3/8/8/8/8/8/8/4K3 w - - x 1", FenError::BadHalfmoveClock { value: "x".to_string() }),
+        ];
+        for (fen, expected) in cases {
+            assert_eq!(Position::from_fen(fen), Err(expected), "{fen}");
+        }
+        assert_eq!(
+            Position::from_fen("4k3/8/8/8/8/9/8/4K3 w - - 0 1").unwrap_err().to_string(),
+            "FEN rank 3 does not contain 8 squares"
+        );
+
+        let mut position = Position::startpos();
+        let illegal = ChessMove::new(parse_square("e2").unwrap(), parse_square("e5").unwrap(), None);
+        assert_eq!(
+            position.make_legal_move(illegal),
+            Err(MoveError::Illegal { uci: "e2e5".to_string(), fen: STARTPOS_FEN.to_string() })
+        );
+        let history_error = Position::draw_reason_from_history(STARTPOS_FEN, &[illegal]).unwrap_err();
+        assert!(matches!(history_error, ChessError::Move(MoveError::Illegal { .. })));
+    }
+
+    #[test]
+    fn validate_reports_impossible_positions() {
+        let violations = |fen: &str| Position::from_fen(fen).unwrap().validate();
+        assert!(violations(STARTPOS_FEN).is_empty());
+        assert!(violations("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").is_empty());
+        assert!(violations("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").is_empty());
+        assert!(violations("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").is_empty());
+
+        assert_eq!(
+            violations("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"),
+            vec![FenViolation::PawnOnBackRank { square: "a1".to_string() }]
+        );
+        assert_eq!(
+            violations("8/8/8/8/8/8/4k3/4K3 w - - 0 1"),
+            vec![FenViolation::KingsTouching, FenViolation::OpponentInCheck { color: Color::Black }]
+        );
+        assert_eq!(
+            violations("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            vec![FenViolation::OpponentInCheck { color: Color::Black }]
+        );
+        assert_eq!(
+            violations("4k3/pppppppp/p7/8/8/8/8/4K3 w - - 0 1"),
+            vec![FenViolation::TooManyPawns { color: Color::Black, count: 9 }]
+        );
+        assert_eq!(
+            violations("4k3/8/8/8/8/8/8/4K3 w K - 0 1"),
+            vec![FenViolation::CastlingWithoutRook { color: Color::White, side: CastlingSide::King, file: 'h' }]
+        );
+        assert_eq!(
+            violations("4k3/8/8/8/8/8/4K3/7R w K - 0 1"),
+            vec![FenViolation::CastlingWithoutKing { color: Color::White, side: CastlingSide::King }]
+        );
+        assert_eq!(
+            violations("4k3/8/8/8/4P3/8/4N3/4K3 b - e3 0 1"),
+            vec![FenViolation::BadEnPassant { square: "e3".to_string() }]
+        );
+        assert_eq!(
+            violations("4k3/8/8/8/8/4P3/8/4K3 b - e3 0 1"),
+            vec![FenViolation::BadEnPassant { square: "e3".to_string() }]
+        );
+    }
+
+    #[test]
+    fn strict_fen_rejects_and_sanitized_fen_repairs_bogus_fields() {
+        let bogus = "4k3/8/8/8/8/8/8/4K2R w KQk e6 0 1";
+        let error = Position::from_fen_strict(bogus).unwrap_err();
+        let FenError::Illegal { violations } = &error else {
+            panic!("expected illegal position, got {error}");
+        };
+        assert_eq!(violations.len(), 3);
+        assert!(error.to_string().starts_with("illegal position: "));
+
+        let (position, repaired) = Position::from_fen_sanitized(bogus).unwrap();
+        assert_eq!(repaired.len(), 3);
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert_eq!(position.zobrist_key(), Position::from_fen(&position.to_fen()).unwrap().zobrist_key());
+
+        let unfixable = Position::from_fen_sanitized("4k3/8/8/8/8/8/8/P3K3 w - - 0 1").unwrap_err();
+        assert_eq!(
+            unfixable,
+            FenError::Illegal { violations: vec![FenViolation::PawnOnBackRank { square: "a1".to_string() }] }
+        );
+    }
+
+    #[test]
+    fn game_tracks_repetitions_incrementally_and_pops_back() {
+        let mut game = Game::new(Position::startpos());
+        game.push_uci("e2e4").unwrap();
+        assert_eq!(game.keys_since_irreversible().len(), 1);
+        for (index, move_text) in ["g8f6", "g1f3", "f6g8", "f3g1", "g8f6", "g1f3", "f6g8", "f3g1"].iter().enumerate() {
+            game.push_uci(move_text).unwrap();
+            assert_eq!(game.keys_since_irreversible().len(), index + 2);
+        }
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(game.draw_reason(), Some(DrawReason::ThreefoldRepetition));
+        assert_eq!(game.result(), "1/2-1/2");
+        assert_eq!(
+            game.keys_since_irreversible().last().copied(),
+            Some(game.position().zobrist_key())
+        );
+        assert_eq!(
+            Position::repetition_count_from_history(&game.start_fen(), game.moves()).unwrap(),
+            game.repetition_count()
+        );
+
+        assert_eq!(game.pop().map(|chess_move| chess_move.to_uci()), Some("f3g1".to_string()));
+        assert_eq!(game.repetition_count(), 2);
+        assert_eq!(game.termination(), None);
+        while game.pop().is_some() {}
+        assert_eq!(game.position(), game.start_position());
+        assert_eq!(game.position().zobrist_key(), Position::startpos().zobrist_key());
+
+        assert!(matches!(game.push_uci("e2e5"), Err(MoveError::Illegal { .. })));
+        assert!(game.moves().is_empty());
+    }
+
+    #[test]
+    fn game_reports_checkmate_termination() {
+        let mut game = Game::new(Position::startpos());
+        for move_text in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            game.push_uci(move_text).unwrap();
+        }
+        assert_eq!(game.termination(), Some(Termination::Checkmate { winner: Color::Black }));
+        assert_eq!(game.result(), "0-1");
+        assert_eq!(game.termination().map(Termination::label), Some("checkmate"));
+        assert_eq!(game.draw_reason(), None);
+        game.pop();
+        assert!(!game.is_over());
+        assert_eq!(game.result(), "*");
+
+        let stalemate = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(stalemate.termination(), Some(Termination::Draw(DrawReason::Stalemate)));
+    }
+
+    #[test]
+    fn detects_insufficient_material() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1",
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1",
+        ] {
+            assert!(Position::from_fen(fen).unwrap().is_insufficient_material(), "{fen}");
+        }
+        for fen in [
+            "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+            "1n2k3/8/8/8/8/8/8/1N2K3 w - - 0 1",
+            "1n2k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/P7/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        ] {
+            assert!(!Position::from_fen(fen).unwrap().is_insufficient_material(), "{fen}");
+        }
+        assert_eq!(
+            Position::draw_reason_from_history("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", &[]).unwrap(),
+            Some(DrawReason::InsufficientMaterial)
+        );
+    }
+
+    #[test]
+    fn automatic_draws_take_priority_over_claimable_ones() {
+        let mut position = Position::startpos();
+        let mut moves = Vec::new();
+        for _ in 0..4 {
+            for move_text in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                let chess_move = position.parse_uci_move(move_text).unwrap();
+                position.make_legal_move(chess_move).unwrap();
+                moves.push(chess_move);
+            }
+        }
+        assert_eq!(Position::repetition_count_from_history(STARTPOS_FEN, &moves).unwrap(), 5);
+        let reason = Position::draw_reason_from_history(STARTPOS_FEN, &moves).unwrap();
+        assert_eq!(reason, Some(DrawReason::FivefoldRepetition));
+        assert!(!DrawReason::FivefoldRepetition.is_claimable());
+        assert!(DrawReason::ThreefoldRepetition.is_claimable());
+
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 150 120";
+        assert!(Position::from_fen(fen).unwrap().is_seventy_five_move_rule_draw());
+        assert_eq!(
+            Position::draw_reason_from_history(fen, &[]).unwrap(),
+            Some(DrawReason::SeventyFiveMoveRule)
+        );
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 149 120";
+        assert_eq!(Position::draw_reason_from_history(fen, &[]).unwrap(), Some(DrawReason::FiftyMoveRule));
+    }
+
     #[test]
     fn pinned_piece_cannot_move_and_expose_king() {
         let position = Position::from_fen("4r2k/8/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
@@ -1313,6 +2878,93 @@ mod tests {
         assert!(position.parse_uci_move("e1g1").is_none());
     }
 
+    #[test]
+    fn chess960_start_positions_follow_standard_numbering() {
+        assert_eq!(chess960_start_fen(518).unwrap(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+        assert_eq!(chess960_start_fen(0).unwrap(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1");
+        assert_eq!(chess960_start_fen(959).unwrap(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1");
+        assert_eq!(chess960_start_fen(960), Err(FenError::Chess960Index { index: 960 }));
+
+        let classical = Position::chess960(518).unwrap();
+        assert!(classical.is_chess960());
+        assert_eq!(classical.to_xfen(), STARTPOS_FEN);
+        assert_eq!(classical.perft(2), 400);
+    }
+
+    #[test]
+    fn shredder_and_x_fen_castling_fields_round_trip() {
+        let shredder = "1r2k1rr/8/8/8/8/8/8/1R2K1RR w GBgb - 0 1";
+        let position = Position::from_fen(shredder).unwrap();
+        assert!(position.is_chess960());
+        assert_eq!(position.to_fen(), shredder);
+        assert_eq!(position.to_xfen(), "1r2k1rr/8/8/8/8/8/8/1R2K1RR w GQgq - 0 1");
+        assert_eq!(Position::from_fen(&position.to_xfen()).unwrap().castling_rights(), position.castling_rights());
+
+        let classical = Position::from_fen(STARTPOS_FEN).unwrap();
+        assert!(!classical.is_chess960());
+        assert_eq!(classical.to_shredder_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+        assert!(Position::from_fen("4k3/8/8/8/8/8/8/4K2R w E - 0 1").is_err());
+    }
+
+    #[test]
+    fn chess960_perft_matches_reference_counts() {
+        let cases = [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", [21, 528, 12189]),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", [21, 807, 18002]),
+            ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", [20, 479, 10471]),
+        ];
+        for (fen, expected) in cases {
+            let position = Position::from_fen(fen).unwrap();
+            for (depth, nodes) in expected.into_iter().enumerate() {
+                assert_eq!(position.perft(depth as u32 + 1), nodes, "{fen} depth {}", depth + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn chess960_castling_uses_king_takes_rook_and_final_squares() {
+        let mut position = Position::from_fen("4k3/8/8/8/8/8/8/6KR w H - 0 1").unwrap();
+        let short = position.parse_uci_move("g1h1").unwrap();
+        assert_eq!(position.castling_side(short), Some(CastlingSide::King));
+        let undo = position.make_move(short);
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+        position.unmake_move(short, undo);
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/8/8/6KR w H - 0 1");
+
+        let mut position = Position::from_fen("4k3/8/8/8/8/8/8/RK6 w A - 0 1").unwrap();
+        let long = position.parse_uci_move("b1a1").unwrap();
+        position.make_legal_move(long).unwrap();
+        assert_eq!(position.to_fen(), "4k3/8/8/8/8/8/8/2KR4 b - - 1 1");
+
+        let mut position = Position::chess960(518).unwrap();
+        for move_text in ["g1f3", "g8f6", "e2e3", "e7e6", "f1e2", "f8e7"] {
+            let chess_move = position.parse_uci_move(move_text).unwrap();
+            position.make_legal_move(chess_move).unwrap();
+        }
+        let castle = position.parse_uci_move("e1g1").unwrap();
+        assert_eq!(castle.to_uci(), "e1h1");
+        assert!(!position.is_capture(castle));
+    }
+
+    #[test]
+    fn chess960_castling_cannot_uncover_a_check_along_the_back_rank() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/rRK5 w B - 0 1").unwrap();
+        assert!(position.parse_uci_move("c1b1").is_none());
+        let safe = Position::from_fen("4k3/8/8/8/8/8/8/1RK5 w B - 0 1").unwrap();
+        assert!(safe.parse_uci_move("c1b1").is_some());
+    }
+
+    #[test]
+    fn zobrist_key_depends_on_the_castling_rook_file() {
+        let b_rook = Position::from_fen("4k3/8/8/8/8/8/8/1RR1K3 w B - 0 1").unwrap();
+        let c_rook = Position::from_fen("4k3/8/8/8/8/8/8/1RR1K3 w C - 0 1").unwrap();
+        assert_ne!(b_rook.zobrist_key(), c_rook.zobrist_key());
+
+        let classical = Position::from_fen(STARTPOS_FEN).unwrap();
+        let shredder = Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1").unwrap();
+        assert_eq!(shredder.zobrist_key(), classical.zobrist_key());
+    }
+
     #[test]
     fn en_passant_removes_captured_pawn() {
         let mut position = Position::from_fen("rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").unwrap();
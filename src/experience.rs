@@ -1,10 +1,12 @@
 use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::{self, OpenOptions};
-use std::io::Write;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 use crate::analysis::{move_accuracy_from_loss_cp, GameAnalysis, MoveAnalysis};
-use crate::chess::{ChessMove, Color, Position};
+use crate::chess::{ChessError, ChessMove, Color, Position};
+use crate::packed_move::PackedMove;
 use crate::pgn::move_to_san;
 use crate::search::{evaluate_tactical_for_side_to_move, RootCandidate};
 
@@ -80,9 +82,9 @@ impl ExperienceRecord {
 
     pub fn experience_score(self) -> i64 {
         let result_part = self.wins as i64 * 1_000 + self.draws as i64 * 120 - self.losses as i64 * 1_000;
-        let loss_penalty = self.average_loss_cp().unwrap_or(0).max(0).min(3_000) as i64;
+        let loss_penalty = self.average_loss_cp().unwrap_or(0).clamp(0, 3_000) as i64;
         let eval_error_penalty = self.average_eval_error_cp().unwrap_or(0).abs().min(3_000) as i64 / 4;
-        let terminal_penalty = self.average_terminal_loss_cp().unwrap_or(0).max(0).min(3_000) as i64;
+        let terminal_penalty = self.average_terminal_loss_cp().unwrap_or(0).clamp(0, 3_000) as i64;
         result_part - loss_penalty - eval_error_penalty - terminal_penalty
     }
 
@@ -127,21 +129,62 @@ impl ExperienceRecord {
     }
 }
 
+/// Experience book failure: a file system error on `path`, or a game that
+/// cannot be replayed from its start FEN.
+#[derive(Debug)]
+pub enum ExperienceError {
+    Io { action: &'static str, path: PathBuf, source: io::Error },
+    Game(ChessError),
+}
+
+impl ExperienceError {
+    fn io(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Self {
+        let path = path.to_path_buf();
+        move |source| Self::Io { action, path, source }
+    }
+}
+
+impl fmt::Display for ExperienceError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { action, path, source } => {
+                write!(formatter, "experience book {action} error {}: {source}", path.display())
+            }
+            Self::Game(error) => write!(formatter, "experience book game error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ExperienceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Game(error) => Some(error),
+        }
+    }
+}
+
+impl<T: Into<ChessError>> From<T> for ExperienceError {
+    fn from(error: T) -> Self {
+        Self::Game(error.into())
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct ExperienceBook {
-    records: BTreeMap<(String, String), ExperienceRecord>,
+    records: BTreeMap<(String, PackedMove), ExperienceRecord>,
 }
 
 impl ExperienceBook {
-    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, String> {
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, ExperienceError> {
         let path = path.as_ref();
         if !path.exists() {
             return Ok(Self::default());
         }
         if path.is_dir() {
             let mut paths = Vec::new();
-            for entry in fs::read_dir(path).map_err(|error| format!("experience directory read error: {error}"))? {
-                let entry = entry.map_err(|error| format!("experience directory entry error: {error}"))?;
+            for entry in fs::read_dir(path).map_err(ExperienceError::io("directory read", path))? {
+                let entry = entry.map_err(ExperienceError::io("directory entry", path))?;
                 let entry_path = entry.path();
                 if entry_path.is_file() {
                     paths.push(entry_path);
@@ -150,13 +193,12 @@ impl ExperienceBook {
             paths.sort();
             let mut book = Self::default();
             for entry_path in paths {
-                let text = fs::read_to_string(&entry_path)
-                    .map_err(|error| format!("experience book read error {}: {error}", entry_path.display()))?;
+                let text = fs::read_to_string(&entry_path).map_err(ExperienceError::io("read", &entry_path))?;
                 book.merge(Self::parse(&text));
             }
             return Ok(book);
         }
-        let text = fs::read_to_string(path).map_err(|error| format!("experience book read error: {error}"))?;
+        let text = fs::read_to_string(path).map_err(ExperienceError::io("read", path))?;
         Ok(Self::parse(&text))
     }
 
@@ -174,10 +216,12 @@ impl ExperienceBook {
         book
     }
 
-    pub fn record_for(&self, key: &str, chess_move: ChessMove) -> Option<ExperienceRecord> {
-        self.records.get(&(key.to_string(), chess_move.to_uci())).copied()
+    pub fn record_for(&self, key: &str, chess_move: PackedMove) -> Option<ExperienceRecord> {
+        self.records.get(&(key.to_string(), chess_move)).copied()
     }
 
+    /// Only candidates with an exact score inside `score_tolerance_cp` of the
+    /// best one are weighed; an upper bound says too little about a move.
     pub fn choose_move(
         &self,
         position: &Position,
@@ -192,14 +236,14 @@ impl ExperienceBook {
 
         let considered_candidates = candidates
             .iter()
-            .filter(|item| item.score >= threshold)
+            .filter(|item| item.exact && item.score >= threshold)
             .count();
 
-        for candidate in candidates.iter().copied() {
-            if candidate.score < threshold {
+        for candidate in candidates {
+            if !candidate.exact || candidate.score < threshold {
                 continue;
             }
-            let stored_record = self.record_for(&key, candidate.chess_move);
+            let stored_record = self.record_for(&key, position.pack(candidate.chess_move));
             let record_is_known = stored_record
                 .map(|record| record.games >= min_games.max(1))
                 .unwrap_or(false);
@@ -308,7 +352,7 @@ impl MoveResult {
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct ExperienceSample {
     key: String,
-    chess_move: String,
+    chess_move: PackedMove,
     result: MoveResult,
     loss_cp: Option<i32>,
     eval_error_cp: Option<i32>,
@@ -316,10 +360,11 @@ struct ExperienceSample {
 }
 
 impl ExperienceSample {
+    /// Lines whose key is not a position or whose move is illegal there are skipped.
     fn parse(line: &str) -> Option<Self> {
         let fields = parse_fields(line);
         let key = fields.get("key")?.to_string();
-        let chess_move = fields.get("move")?.to_string();
+        let chess_move = Position::from_fen(&key).ok()?.parse_uci_packed(fields.get("move")?)?;
         let result = fields
             .get("result")
             .map(|value| MoveResult::from_str(value))
@@ -350,7 +395,7 @@ pub fn append_game_to_experience_book(
     white_name: &str,
     black_name: &str,
     analysis: Option<&GameAnalysis>,
-) -> Result<usize, String> {
+) -> Result<usize, ExperienceError> {
     let requested_path = path.as_ref();
     let mut path_buf = requested_path.to_path_buf();
     if path_buf.exists() && path_buf.is_dir() {
@@ -359,14 +404,14 @@ pub fn append_game_to_experience_book(
     let path = path_buf.as_path();
     if let Some(parent) = path.parent() {
         if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent).map_err(|error| format!("experience book dir error: {error}"))?;
+            fs::create_dir_all(parent).map_err(ExperienceError::io("dir", parent))?;
         }
     }
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
         .open(path)
-        .map_err(|error| format!("experience book append error: {error}"))?;
+        .map_err(ExperienceError::io("append", path))?;
 
     let terminal = terminal_summary(start_fen.trim(), moves, result);
     let mut position = Position::from_fen(start_fen.trim())?;
@@ -378,7 +423,7 @@ pub fn append_game_to_experience_book(
         field(black_name),
         field(start_fen.trim())
     )
-    .map_err(|error| format!("experience book write error: {error}"))?;
+    .map_err(ExperienceError::io("write", path))?;
 
     let mut written = 0_usize;
     for (index, chess_move) in moves.iter().copied().enumerate() {
@@ -418,11 +463,11 @@ pub fn append_game_to_experience_book(
             field(terminal_detail.reason.as_deref().unwrap_or("-")),
             field(&reason),
         )
-        .map_err(|error| format!("experience book write error: {error}"))?;
+        .map_err(ExperienceError::io("write", path))?;
         written += 1;
     }
     writeln!(file, "endgame\tmoves={written}")
-        .map_err(|error| format!("experience book write error: {error}"))?;
+        .map_err(ExperienceError::io("write", path))?;
     Ok(written)
 }
 
@@ -648,9 +693,16 @@ mod tests {
 
     #[test]
     fn parses_move_samples_into_position_move_records() {
-        let text = "move\tkey=abc\tmove=e2e4\tresult=win\tloss_cp=12\teval_error_cp=20\nmove\tkey=abc\tmove=e2e4\tresult=loss\tloss_cp=50\n";
-        let book = ExperienceBook::parse(text);
-        let record = book.records.get(&("abc".to_string(), "e2e4".to_string())).copied().unwrap();
+        let position = Position::startpos();
+        let key = position.repetition_key();
+        let text = format!(
+            "move\tkey={key}\tmove=e2e4\tresult=win\tloss_cp=12\teval_error_cp=20\nmove\tkey={key}\tmove=e2e4\tresult=loss\tloss_cp=50\nmove\tkey={key}\tmove=e2e5\tresult=win\n"
+        );
+        let book = ExperienceBook::parse(&text);
+        assert_eq!(book.records.len(), 1);
+        let e4 = position.parse_uci_packed("e2e4").unwrap();
+        assert!(e4.is_double_pawn_push());
+        let record = book.record_for(&key, e4).unwrap();
         assert_eq!(record.games, 2);
         assert_eq!(record.wins, 1);
         assert_eq!(record.losses, 1);
@@ -666,7 +718,7 @@ mod tests {
         let mut book = ExperienceBook::default();
         book.add_sample(ExperienceSample {
             key,
-            chess_move: d4.to_uci(),
+            chess_move: position.pack(d4),
             result: MoveResult::Win,
             loss_cp: Some(0),
             eval_error_cp: Some(0),
@@ -674,19 +726,22 @@ mod tests {
         });
         book.add_sample(ExperienceSample {
             key: position.repetition_key(),
-            chess_move: d4.to_uci(),
+            chess_move: position.pack(d4),
             result: MoveResult::Win,
             loss_cp: Some(0),
             eval_error_cp: Some(0),
             terminal_loss_cp: None,
         });
         let candidates = vec![
-            RootCandidate { root_index: 0, chess_move: e4, score: 20 },
-            RootCandidate { root_index: 1, chess_move: d4, score: 5 },
+            RootCandidate { root_index: 0, chess_move: e4, score: 20, pv: vec![e4], exact: true },
+            RootCandidate { root_index: 1, chess_move: d4, score: 5, pv: vec![d4], exact: true },
         ];
         assert!(book.choose_move(&position, &candidates, 2, 10).is_none());
         let decision = book.choose_move(&position, &candidates, 2, 20).unwrap();
         assert_eq!(decision.chosen_move, d4);
+
+        let bounded = vec![candidates[0].clone(), RootCandidate { exact: false, ..candidates[1].clone() }];
+        assert!(book.choose_move(&position, &bounded, 2, 20).is_none());
     }
 
     #[test]
@@ -697,15 +752,15 @@ mod tests {
         let mut book = ExperienceBook::default();
         book.add_sample(ExperienceSample {
             key: position.repetition_key(),
-            chess_move: e4.to_uci(),
+            chess_move: position.pack(e4),
             result: MoveResult::Loss,
             loss_cp: Some(0),
             eval_error_cp: Some(0),
             terminal_loss_cp: Some(700),
         });
         let candidates = vec![
-            RootCandidate { root_index: 0, chess_move: e4, score: 20 },
-            RootCandidate { root_index: 1, chess_move: d4, score: 0 },
+            RootCandidate { root_index: 0, chess_move: e4, score: 20, pv: vec![e4], exact: true },
+            RootCandidate { root_index: 1, chess_move: d4, score: 0, pv: vec![d4], exact: true },
         ];
         let decision = book.choose_move(&position, &candidates, 1, 30).unwrap();
         assert_eq!(decision.chosen_move, d4);
@@ -0,0 +1,577 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::chess::{ChessMove, Color, PieceKind, Position};
+use crate::search::{
+    mate_score_to_uci_moves, score_is_mate, AlphaBeta, RootCandidate, SearchInfo, SearchSettings, TranspositionTable,
+};
+use crate::search_method::{
+    MethodOption, MethodOptionKind, MethodOptions, SearchInput, SearchLimits, SearchMethod, SearchOutput, SearchStats,
+};
+
+/// Upper limit of the `Hybrid*Weight` options.
+const MAX_HYBRID_WEIGHT: i32 = 10;
+
+const RULE_OPTION: MethodOption = MethodOption {
+    name: "HybridRule",
+    kind: MethodOptionKind::Combo { default: "Weighted", values: &["Weighted", "Vote"] },
+};
+const TACTICAL_WEIGHT_OPTION: MethodOption = weight_option("HybridTacticalWeight");
+const SOLID_WEIGHT_OPTION: MethodOption = weight_option("HybridSolidWeight");
+const ENDGAME_WEIGHT_OPTION: MethodOption = weight_option("HybridEndgameWeight");
+static HYBRID_OPTIONS: [MethodOption; 4] =
+    [RULE_OPTION, TACTICAL_WEIGHT_OPTION, SOLID_WEIGHT_OPTION, ENDGAME_WEIGHT_OPTION];
+
+/// Non-pawn material per side, in centipawns, up to which the endgame
+/// member takes part: a rook and a minor piece.
+const ENDGAME_MATERIAL: i32 = 830;
+
+/// Hash size, in megabytes, of each member's private table. The shared
+/// table stays untouched, so a large `Hash` costs nothing per move here.
+const MEMBER_HASH_MB: usize = 16;
+
+/// What a mate score counts as, in centipawns, when not every member that
+/// scored the move sees the same mate. Above any material balance but far
+/// below the mate range, so the average stays a centipawn score.
+const MATE_AS_CENTIPAWNS: i32 = 3_000;
+
+/// How the hybrid controller turns the members' root scores into one choice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HybridRule {
+    /// Highest weighted average score.
+    Weighted,
+    /// Each member gives its weight to its own best move; most weight wins,
+    /// the weighted score breaks ties.
+    Vote,
+}
+
+impl HybridRule {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Weighted => "Weighted",
+            Self::Vote => "Vote",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Self::Weighted, Self::Vote]
+            .into_iter()
+            .find(|rule| rule.name().eq_ignore_ascii_case(value.trim()))
+    }
+}
+
+/// One search method with its own settings and weight.
+#[derive(Clone, Debug)]
+pub struct HybridMember {
+    pub label: &'static str,
+    pub method: &'static dyn SearchMethod,
+    pub settings: SearchSettings,
+    pub weight: u32,
+    /// Why the member sits this position out, if it does.
+    pub skipped: Option<&'static str>,
+}
+
+/// Runs every member on the same position, each with its own transposition
+/// table, so a member's scores do not depend on the others.
+#[derive(Clone, Debug)]
+pub struct HybridController {
+    pub rule: HybridRule,
+    pub members: Vec<HybridMember>,
+}
+
+/// The combined root candidates, best first, and the audit lines explaining
+/// them.
+#[derive(Clone, Debug, Default)]
+pub struct HybridDecision {
+    pub candidates: Vec<RootCandidate>,
+    pub stats: SearchStats,
+    pub audit: Vec<String>,
+}
+
+struct MemberResult<'a> {
+    member: &'a HybridMember,
+    candidates: Vec<RootCandidate>,
+}
+
+impl MemberResult<'_> {
+    fn score_of(&self, chess_move: ChessMove) -> Option<i32> {
+        self.candidates
+            .iter()
+            .find(|candidate| candidate.chess_move == chess_move)
+            .map(|candidate| candidate.score)
+    }
+
+    fn best_move(&self) -> Option<ChessMove> {
+        self.candidates.first().map(|candidate| candidate.chess_move)
+    }
+}
+
+struct Tally {
+    candidate: RootCandidate,
+    votes: u32,
+}
+
+impl HybridController {
+    /// The members the `Hybrid` search method runs:
+    ///
+    /// - `tactical`: check, one-reply and recapture extensions and quiet
+    ///   checks in quiescence;
+    /// - `solid`: plain alpha-beta with a cautious `RiskLevel` of -50;
+    /// - `endgame`: passed-pawn extensions and no null move, only when each
+    ///   side has at most a rook and a minor piece besides pawns.
+    pub fn from_settings(position: &Position, settings: SearchSettings, options: &MethodOptions) -> Self {
+        let base = SearchSettings { multi_pv: 1, ..settings };
+        let endgame = [Color::White, Color::Black]
+            .into_iter()
+            .all(|color| non_pawn_material(position, color) <= ENDGAME_MATERIAL);
+        let members = vec![
+            HybridMember {
+                label: "tactical",
+                method: &AlphaBeta,
+                settings: SearchSettings {
+                    check_extensions: true,
+                    one_reply_extensions: true,
+                    recapture_extensions: true,
+                    quiescence_checks: true,
+                    ..base
+                },
+                weight: weight(options, &TACTICAL_WEIGHT_OPTION),
+                skipped: None,
+            },
+            HybridMember {
+                label: "solid",
+                method: &AlphaBeta,
+                settings: SearchSettings { risk_level: -50, ..base },
+                weight: weight(options, &SOLID_WEIGHT_OPTION),
+                skipped: None,
+            },
+            HybridMember {
+                label: "endgame",
+                method: &AlphaBeta,
+                settings: SearchSettings { null_move_pruning: false, passed_pawn_extensions: true, ..base },
+                weight: weight(options, &ENDGAME_WEIGHT_OPTION),
+                skipped: (!endgame).then_some("not an endgame"),
+            },
+        ];
+        let rule = HybridRule::parse(&options.get(&RULE_OPTION)).unwrap_or(HybridRule::Weighted);
+        Self { rule, members }
+    }
+
+    /// Runs the members depth by depth: every member searches depth 1, then
+    /// every member depth 2, and so on. Members share the parent's control,
+    /// so its clock and stop flag hold for the round as a whole, and a round
+    /// that one member cannot finish is dropped. Scores therefore always
+    /// come from a depth all members completed. Each member keeps a small
+    /// private table for the whole search, which makes the repeated lower
+    /// depths cheap; allocating it is part of the searched time.
+    pub fn run(&self, input: &SearchInput, report: &mut dyn FnMut(&SearchInfo)) -> HybridDecision {
+        let started = Instant::now();
+        let active: Vec<(&HybridMember, Arc<TranspositionTable>)> = self
+            .members
+            .iter()
+            .filter(|member| member.skipped.is_none() && member.weight > 0)
+            .map(|member| (member, Arc::new(TranspositionTable::new(member.settings.hash_mb.min(MEMBER_HASH_MB)))))
+            .collect();
+        let control = &input.limits.control;
+        let mut stats = SearchStats::default();
+        let mut results = Vec::new();
+        let mut dropped_depth = None;
+        if !input.position.legal_moves().is_empty() {
+            for depth in 1..=input.limits.max_depth.max(1) {
+                if active.is_empty() {
+                    break;
+                }
+                let mut round = Vec::new();
+                for (member, table) in &active {
+                    let member_input = SearchInput {
+                        position: input.position,
+                        history: input.history,
+                        limits: SearchLimits { max_depth: depth, control: control.clone() },
+                        settings: member.settings,
+                        exact_root_scores: true,
+                        options: input.options,
+                        tt: table.clone(),
+                    };
+                    let output = member.method.search(&member_input, &mut |_| {});
+                    stats.seldepth = stats.seldepth.max(output.stats.seldepth);
+                    stats.nodes += output.stats.nodes;
+                    if output.stats.depth < depth {
+                        break;
+                    }
+                    round.push(MemberResult { member, candidates: output.candidates });
+                }
+                if round.len() < active.len() {
+                    dropped_depth = Some(depth);
+                    break;
+                }
+                results = round;
+                stats.depth = depth;
+                stats.elapsed = started.elapsed();
+                let hashfull = active.iter().map(|(_, table)| table.hashfull()).max().unwrap_or(0);
+                for (index, tally) in self.ranked(input.position, &results).iter().take(input.settings.multi_pv).enumerate() {
+                    report(&SearchInfo {
+                        depth,
+                        seldepth: stats.seldepth.max(depth),
+                        multipv: index + 1,
+                        score: tally.candidate.score,
+                        nodes: stats.nodes,
+                        hashfull,
+                        elapsed: stats.elapsed,
+                        pv: tally.candidate.pv.clone(),
+                    });
+                }
+                if !control.may_deepen() {
+                    break;
+                }
+            }
+        }
+        stats.elapsed = started.elapsed();
+
+        let roster: Vec<String> = self
+            .members
+            .iter()
+            .map(|member| {
+                if let Some(reason) = member.skipped {
+                    return format!("{}(skipped: {reason})", member.label);
+                }
+                if member.weight == 0 {
+                    return format!("{}(weight 0)", member.label);
+                }
+                let best = results
+                    .iter()
+                    .find(|result| std::ptr::eq(result.member, member))
+                    .and_then(MemberResult::best_move)
+                    .map_or("none".to_string(), |chess_move| chess_move.to_uci());
+                format!("{}(weight {}, {}, best {best})", member.label, member.weight, member.method.name())
+            })
+            .collect();
+        let mut audit =
+            vec![format!("hybrid rule={} depth {} members {}", self.rule.name(), stats.depth, roster.join(" "))];
+        if let Some(depth) = dropped_depth {
+            audit.push(format!(
+                "hybrid dropped depth {depth}: not every member completed it, scores are from depth {}",
+                stats.depth
+            ));
+        }
+        if active.is_empty() {
+            audit.push("hybrid has no member to run".to_string());
+            return HybridDecision { candidates: Vec::new(), stats, audit };
+        }
+        if results.is_empty() {
+            audit.push("hybrid has no legal move to score".to_string());
+            return HybridDecision { candidates: Vec::new(), stats, audit };
+        }
+
+        let tallies = self.ranked(input.position, &results);
+        for tally in &tallies {
+            let scores: Vec<String> = results
+                .iter()
+                .map(|result| {
+                    let score = result.score_of(tally.candidate.chess_move).map_or("-".to_string(), format_score);
+                    format!("{} {score}", result.member.label)
+                })
+                .collect();
+            audit.push(format!(
+                "hybrid {} combined {} votes {} | {}",
+                tally.candidate.chess_move.to_uci(),
+                format_score(tally.candidate.score),
+                tally.votes,
+                scores.join(" | ")
+            ));
+        }
+        audit.push(self.explain(&tallies, &results));
+
+        let candidates = tallies.into_iter().map(|tally| tally.candidate).collect();
+        HybridDecision { candidates, stats, audit }
+    }
+
+    /// `tally` sorted by the rule, best first.
+    fn ranked(&self, position: &Position, results: &[MemberResult]) -> Vec<Tally> {
+        let mut tallies = self.tally(position, results);
+        tallies.sort_by(|left, right| match self.rule {
+            HybridRule::Weighted => compare_weighted(left, right),
+            HybridRule::Vote => right.votes.cmp(&left.votes).then_with(|| compare_weighted(left, right)),
+        });
+        tallies
+    }
+
+    /// The weighted score and the vote weight of every legal move. Members
+    /// that have no score for a move leave it out of that move's average.
+    fn tally(&self, position: &Position, results: &[MemberResult]) -> Vec<Tally> {
+        position
+            .legal_moves()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(root_index, chess_move)| {
+                let mut scores = Vec::new();
+                let mut votes = 0;
+                let mut pv = None;
+                for result in results {
+                    let weight = result.member.weight;
+                    if result.best_move() == Some(chess_move) {
+                        votes += weight;
+                        pv.get_or_insert_with(|| result.candidates[0].pv.clone());
+                    }
+                    if let Some(score) = result.score_of(chess_move) {
+                        scores.push((score, weight));
+                    }
+                }
+                let score = combined_score(&scores)?;
+                let pv = pv.unwrap_or_else(|| {
+                    results
+                        .iter()
+                        .find_map(|result| result.candidates.iter().find(|candidate| candidate.chess_move == chess_move))
+                        .map_or_else(|| vec![chess_move], |candidate| candidate.pv.clone())
+                });
+                Some(Tally { candidate: RootCandidate { root_index, chess_move, score, pv, exact: true }, votes })
+            })
+            .collect()
+    }
+
+    fn explain(&self, tallies: &[Tally], results: &[MemberResult]) -> String {
+        let winner = &tallies[0];
+        let runner_up = tallies.get(1);
+        let chosen = winner.candidate.chess_move.to_uci();
+        match self.rule {
+            HybridRule::Weighted => match runner_up {
+                Some(next) => format!(
+                    "hybrid chose {chosen}: highest weighted score {} against {} for {}",
+                    format_score(winner.candidate.score),
+                    format_score(next.candidate.score),
+                    next.candidate.chess_move.to_uci()
+                ),
+                None => format!("hybrid chose {chosen}: the only legal move"),
+            },
+            HybridRule::Vote => {
+                let voters: Vec<&str> = results
+                    .iter()
+                    .filter(|result| result.best_move() == Some(winner.candidate.chess_move))
+                    .map(|result| result.member.label)
+                    .collect();
+                let total: u32 = results.iter().map(|result| result.member.weight).sum();
+                let tie = runner_up.is_some_and(|next| next.votes == winner.votes);
+                format!(
+                    "hybrid chose {chosen}: {} of {total} vote weight ({}){}",
+                    winner.votes,
+                    if voters.is_empty() { "no votes".to_string() } else { voters.join(", ") },
+                    if tie { ", tie broken by weighted score" } else { "" }
+                )
+            }
+        }
+    }
+}
+
+/// Several search methods combined by `HybridController`, selected like any
+/// other method. The audit comes back as `SearchOutput::notes`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hybrid;
+
+impl SearchMethod for Hybrid {
+    fn name(&self) -> &'static str {
+        "Hybrid"
+    }
+
+    fn description(&self) -> &'static str {
+        "Tactical, solid and endgame alpha-beta combined by weights or votes, with an audit of every score"
+    }
+
+    fn options(&self) -> &'static [MethodOption] {
+        &HYBRID_OPTIONS
+    }
+
+    fn search(&self, input: &SearchInput, report: &mut dyn FnMut(&SearchInfo)) -> SearchOutput {
+        let controller = HybridController::from_settings(input.position, input.settings, input.options);
+        let decision = controller.run(input, report);
+        SearchOutput { candidates: decision.candidates, stats: decision.stats, notes: decision.audit }
+    }
+}
+
+const fn weight_option(name: &'static str) -> MethodOption {
+    MethodOption { name, kind: MethodOptionKind::Spin { default: 1, min: 0, max: MAX_HYBRID_WEIGHT } }
+}
+
+/// A member's weight; 0 leaves it out.
+fn weight(options: &MethodOptions, option: &MethodOption) -> u32 {
+    options.spin(option).clamp(0, MAX_HYBRID_WEIGHT) as u32
+}
+
+/// Weighted average of `(score, weight)` pairs, `None` without weight.
+///
+/// A mate every scoring member agrees on stays a mate, so such moves are
+/// ranked by mate distance: the slowest of the mates when winning, the
+/// fastest when losing. Otherwise mate scores count as
+/// `MATE_AS_CENTIPAWNS` before averaging.
+fn combined_score(scores: &[(i32, u32)]) -> Option<i32> {
+    let scores: Vec<(i32, u32)> = scores.iter().copied().filter(|(_, weight)| *weight > 0).collect();
+    let (first, _) = *scores.first()?;
+    if scores.iter().all(|(score, _)| score_is_mate(*score) && score.signum() == first.signum()) {
+        return scores.iter().map(|(score, _)| *score).min();
+    }
+    let mut weighted = 0_i64;
+    let mut total_weight = 0_i64;
+    for (score, weight) in scores {
+        let score = score.clamp(-MATE_AS_CENTIPAWNS, MATE_AS_CENTIPAWNS);
+        weighted += i64::from(score) * i64::from(weight);
+        total_weight += i64::from(weight);
+    }
+    Some(weighted.div_euclid(total_weight) as i32)
+}
+
+fn compare_weighted(left: &Tally, right: &Tally) -> std::cmp::Ordering {
+    right
+        .candidate
+        .score
+        .cmp(&left.candidate.score)
+        .then_with(|| left.candidate.root_index.cmp(&right.candidate.root_index))
+}
+
+fn format_score(score: i32) -> String {
+    match mate_score_to_uci_moves(score) {
+        Some(moves) => format!("mate {moves}"),
+        None => format!("{score:+}"),
+    }
+}
+
+fn non_pawn_material(position: &Position, color: Color) -> i32 {
+    [PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen]
+        .into_iter()
+        .map(|kind| position.pieces(color, kind).count_ones() as i32 * kind.material_value())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::search::{Engine, SearchControl, MAX_SEARCH_DEPTH};
+    use crate::time_manager::SearchDeadlines;
+
+    fn hybrid_engine(rule: HybridRule) -> Engine {
+        let mut engine = Engine::new(3);
+        engine.set_deterministic_multithread(false);
+        assert!(engine.set_method_option("HybridRule", rule.name()));
+        assert!(engine.set_search_method("hybrid"));
+        engine
+    }
+
+    #[test]
+    fn hybrid_audits_every_member_score_and_is_deterministic() {
+        let position = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut engine = hybrid_engine(HybridRule::Weighted);
+        let first = engine.root_candidates(&position);
+        let audit = engine.search_notes().to_vec();
+        assert_eq!(first.len(), position.legal_moves().len());
+        assert!(audit[0].starts_with("hybrid rule=Weighted depth 3 members tactical(weight 1, AlphaBeta, best "));
+        assert!(audit[0].contains("endgame(skipped: not an endgame)"));
+        assert_eq!(audit.len(), first.len() + 2);
+        let best_line = &audit[1];
+        assert!(best_line.starts_with(&format!("hybrid {} combined", first[0].chess_move.to_uci())));
+        assert!(best_line.contains("| tactical ") && best_line.contains("| solid "));
+        assert!(audit.last().unwrap().starts_with(&format!("hybrid chose {}", first[0].chess_move.to_uci())));
+        assert!(first.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        let again = engine.root_candidates(&position);
+        assert_eq!(again, first);
+        assert_eq!(engine.search_notes(), audit.as_slice());
+    }
+
+    #[test]
+    fn members_search_depth_by_depth_under_the_parent_control() {
+        let position = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut engine = hybrid_engine(HybridRule::Weighted);
+        engine.set_depth(MAX_SEARCH_DEPTH);
+        let stopped = Arc::new(SearchControl::new(None));
+        stopped.stop();
+        engine.set_control(stopped);
+        let mut depths = Vec::new();
+        assert!(!engine.root_candidates_with_info(&position, |info| depths.push(info.depth)).is_empty());
+        assert_eq!(depths, [1]);
+        assert!(engine.search_notes()[0].starts_with("hybrid rule=Weighted depth 1 members"));
+
+        depths.clear();
+        let started = Instant::now();
+        engine.set_deadlines(Some(SearchDeadlines { soft: Duration::from_millis(150), hard: Duration::from_millis(300) }));
+        engine.root_candidates_with_info(&position, |info| depths.push(info.depth));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        let depth = *depths.last().unwrap();
+        assert_eq!(depths, (1..=depth).collect::<Vec<_>>());
+        let notes = engine.search_notes();
+        assert!(notes[0].starts_with(&format!("hybrid rule=Weighted depth {depth} members")));
+        if notes[1].starts_with("hybrid dropped") {
+            assert_eq!(
+                notes[1],
+                format!("hybrid dropped depth {}: not every member completed it, scores are from depth {depth}", depth + 1)
+            );
+        }
+    }
+
+    #[test]
+    fn vote_rule_follows_member_weights() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/3P4/R3K3 w - - 0 1").unwrap();
+        let mut engine = hybrid_engine(HybridRule::Vote);
+        assert!(engine.set_method_option("HybridTacticalWeight", "0"));
+        let candidates = engine.root_candidates(&position);
+        let notes = engine.search_notes();
+        assert!(notes[0].contains("tactical(weight 0)"));
+        assert!(notes[0].contains("endgame(weight 1"));
+        assert!(notes.last().unwrap().starts_with(&format!("hybrid chose {}", candidates[0].chess_move.to_uci())));
+        assert!(notes.last().unwrap().contains("of 2 vote weight"));
+
+        assert!(engine.set_method_option("HybridSolidWeight", "0"));
+        assert!(engine.set_method_option("HybridEndgameWeight", "0"));
+        assert!(engine.root_candidates(&position).is_empty());
+        assert_eq!(engine.search_notes().last().unwrap(), "hybrid has no member to run");
+    }
+
+    #[test]
+    fn weighted_rule_averages_member_scores() {
+        let position = Position::startpos();
+        let mut controller = HybridController::from_settings(&position, SearchSettings::default(), &MethodOptions::default());
+        controller.members.truncate(2);
+        let e4 = position.parse_uci_move("e2e4").unwrap();
+        let d4 = position.parse_uci_move("d2d4").unwrap();
+        let candidate = |chess_move, score| RootCandidate { root_index: 0, chess_move, score, pv: vec![chess_move], exact: true };
+        controller.members[0].weight = 3;
+        let results = [
+            MemberResult { member: &controller.members[0], candidates: vec![candidate(e4, 40), candidate(d4, 10)] },
+            MemberResult { member: &controller.members[1], candidates: vec![candidate(d4, 30), candidate(e4, 0)] },
+        ];
+        let tallies = controller.tally(&position, &results);
+        let score = |chess_move| tallies.iter().find(|tally| tally.candidate.chess_move == chess_move).unwrap();
+        assert_eq!(score(e4).candidate.score, 30);
+        assert_eq!(score(e4).votes, 3);
+        assert_eq!(score(d4).candidate.score, 15);
+        assert_eq!(score(d4).votes, 1);
+        assert_eq!(tallies.len(), 2);
+    }
+
+    #[test]
+    fn mate_scores_are_bounded_unless_every_member_sees_the_mate() {
+        use crate::search::MATE_SCORE;
+
+        let mate_in_two = MATE_SCORE - 3;
+        let mate_in_three = MATE_SCORE - 5;
+        let position = Position::startpos();
+        let mut controller = HybridController::from_settings(&position, SearchSettings::default(), &MethodOptions::default());
+        controller.members.truncate(2);
+        let e4 = position.parse_uci_move("e2e4").unwrap();
+        let d4 = position.parse_uci_move("d2d4").unwrap();
+        let candidate = |chess_move, score| RootCandidate { root_index: 0, chess_move, score, pv: vec![chess_move], exact: true };
+        let results = [
+            MemberResult { member: &controller.members[0], candidates: vec![candidate(e4, mate_in_two), candidate(d4, mate_in_two)] },
+            MemberResult { member: &controller.members[1], candidates: vec![candidate(e4, 500), candidate(d4, mate_in_three)] },
+        ];
+        let tallies = controller.tally(&position, &results);
+        let score = |chess_move| tallies.iter().find(|tally| tally.candidate.chess_move == chess_move).unwrap().candidate.score;
+        assert_eq!(score(e4), (MATE_AS_CENTIPAWNS + 500) / 2);
+        assert!(!score_is_mate(score(e4)));
+        assert_eq!(format_score(score(e4)), "+1750");
+        assert_eq!(score(d4), mate_in_three);
+        assert_eq!(format_score(score(d4)), "mate 3");
+
+        assert_eq!(combined_score(&[(-mate_in_two, 1), (-mate_in_three, 2)]), Some(-mate_in_two));
+        assert_eq!(combined_score(&[(-mate_in_two, 1), (mate_in_three, 1)]), Some(0));
+        assert_eq!(combined_score(&[(40, 0)]), None);
+    }
+}
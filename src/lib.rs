@@ -1,7 +1,14 @@
 pub mod analysis;
+pub mod bitboard;
 pub mod chess;
 pub mod experience;
+pub mod hybrid;
 pub mod pgn;
 pub mod matchplay;
+pub mod packed_move;
+pub mod perft;
 pub mod search;
+pub mod search_method;
+pub mod time_manager;
 pub mod uci;
+pub mod zobrist;
@@ -2,55 +2,135 @@ use std::env;
 use std::fs;
 use std::io::{self, Read};
 use std::process;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use rchess::chess::Position;
-use rchess::pgn::{export_pgn_with_tags, parse_pgn, position_after_moves};
+use rchess::chess::{ChessError, FenError, Position};
+use rchess::perft::{
+    nodes_per_second, parse_perft_epd, perft, perft_diff, perft_divide, run_perft_suite, EpdError, PerftSettings,
+    UciPerftEngine,
+};
+use rchess::pgn::{export_pgn_with_tags, parse_pgn_games, position_after_moves};
 use rchess::search::Engine;
 
+/// Process exit codes, one per error family, so that scripts can tell a bad
+/// FEN from an illegal move or an unreadable file without parsing stderr.
+const EXIT_FEN_ERROR: i32 = 2;
+const EXIT_MOVE_ERROR: i32 = 3;
+const EXIT_PGN_ERROR: i32 = 4;
+const EXIT_IO_ERROR: i32 = 5;
+const EXIT_EPD_ERROR: i32 = 6;
+const EXIT_PERFT_MISMATCH: i32 = 7;
+const EXIT_USAGE_ERROR: i32 = 8;
+
 fn main() {
     let mut args = env::args().skip(1);
 
     match args.next().as_deref() {
         Some("perft") => {
-            let depth = args
-                .next()
-                .and_then(|value| value.parse::<u32>().ok())
-                .unwrap_or(3);
-            let fen = args.collect::<Vec<_>>().join(" ");
-            let position = parse_optional_fen(&fen);
-            println!("{}", position.perft(depth));
+            let mut rest: Vec<String> = args.collect();
+            let settings = take_perft_settings(&mut rest);
+            let (depth, position) = depth_and_position(rest, 3);
+            println!("{}", perft(&position, depth, settings));
         }
         Some("divide") => {
-            let depth = args
-                .next()
-                .and_then(|value| value.parse::<u32>().ok())
-                .unwrap_or(1);
-            let fen = args.collect::<Vec<_>>().join(" ");
-            let position = parse_optional_fen(&fen);
-            let rows = position.perft_divide(depth);
+            let mut rest: Vec<String> = args.collect();
+            let settings = take_perft_settings(&mut rest);
+            let (depth, position) = depth_and_position(rest, 1);
+            let rows = perft_divide(&position, depth, settings);
             let total: u64 = rows.iter().map(|(_, nodes)| *nodes).sum();
             for (chess_move, nodes) in rows {
                 println!("{:<5} {nodes}", chess_move.to_uci());
             }
             println!("total {total}");
         }
+        Some("perft-suite") => {
+            let mut rest: Vec<String> = args.collect();
+            let settings = take_perft_settings(&mut rest);
+            let max_depth = take_flag::<u32>(&mut rest, "--max-depth");
+            let input = match read_input(rest.into_iter().next()) {
+                Ok(input) => input,
+                Err(error) => {
+                    eprintln!("EPD read error: {error}");
+                    process::exit(EXIT_IO_ERROR);
+                }
+            };
+            let cases = match parse_perft_epd(&input) {
+                Ok(cases) => cases,
+                Err(error) => {
+                    eprintln!("EPD parse error: {error}");
+                    process::exit(match error {
+                        EpdError::Fen { .. } => EXIT_FEN_ERROR,
+                        EpdError::BadField { .. } => EXIT_EPD_ERROR,
+                    });
+                }
+            };
+            let started = Instant::now();
+            let results = run_perft_suite(&cases, max_depth, settings, |result| {
+                let status = if result.passed() { "ok" } else { "FAIL" };
+                println!(
+                    "{status:<4} line {} D{} expected {} got {} {:.3}s {} nps",
+                    result.line,
+                    result.depth,
+                    result.expected,
+                    result.nodes,
+                    result.elapsed.as_secs_f64(),
+                    result.nodes_per_second()
+                );
+            });
+            let failures: Vec<_> = results.iter().filter(|result| !result.passed()).collect();
+            let nodes: u64 = results.iter().map(|result| result.nodes).sum();
+            let searched: Duration = results.iter().map(|result| result.elapsed).sum();
+            println!(
+                "{} checks, {} mismatches, {nodes} nodes in {:.3}s ({} nps)",
+                results.len(),
+                failures.len(),
+                started.elapsed().as_secs_f64(),
+                nodes_per_second(nodes, searched)
+            );
+            for failure in &failures {
+                println!("mismatch: line {} D{} {}", failure.line, failure.depth, failure.fen);
+            }
+            if !failures.is_empty() {
+                process::exit(EXIT_PERFT_MISMATCH);
+            }
+        }
+        Some("perft-diff") => {
+            let mut rest: Vec<String> = args.collect();
+            let settings = take_perft_settings(&mut rest);
+            let Some(program) = take_flag::<String>(&mut rest, "--engine") else {
+                eprintln!("usage: rchess perft-diff <depth> [fen] --engine <path>");
+                process::exit(EXIT_USAGE_ERROR);
+            };
+            let (depth, position) = depth_and_position(rest, 3);
+            let diff = UciPerftEngine::spawn(&program)
+                .and_then(|mut engine| perft_diff(&position, depth, settings, |position, depth| engine.divide(position, depth)));
+            match diff {
+                Ok(None) => println!("perft {depth} agrees with {program}"),
+                Ok(Some(diff)) => {
+                    let path = if diff.path.is_empty() { "(root)".to_string() } else { diff.path.join(" ") };
+                    println!("path:  {path}");
+                    println!("fen:   {}", diff.fen);
+                    println!("depth: {}", diff.depth);
+                    let count = |nodes: Option<u64>| nodes.map_or_else(|| "-".to_string(), |nodes| nodes.to_string());
+                    for row in &diff.moves {
+                        println!("{:<5} rchess {} engine {}", row.uci, count(row.ours), count(row.theirs));
+                    }
+                    process::exit(EXIT_PERFT_MISMATCH);
+                }
+                Err(error) => {
+                    eprintln!("{program}: {error}");
+                    process::exit(EXIT_IO_ERROR);
+                }
+            }
+        }
         Some("bestmove") => {
             let depth = args
                 .next()
                 .and_then(|value| value.parse::<u8>().ok())
                 .unwrap_or(4);
             let fen = args.collect::<Vec<_>>().join(" ");
-            let position = if fen.trim().is_empty() {
-                Position::startpos()
-            } else {
-                match Position::from_fen(&fen) {
-                    Ok(position) => position,
-                    Err(error) => {
-                        eprintln!("FEN error: {error}");
-                        process::exit(2);
-                    }
-                }
-            };
+            let position = parse_optional_fen(&fen);
             let mut engine = Engine::new(depth);
             match engine.best_move(&position) {
                 Some(best_move) => println!("{}", best_move.to_uci()),
@@ -58,28 +138,47 @@ fn main() {
             }
         }
         Some("fen") => {
+            let mut args = args.peekable();
+            let sanitize = args.next_if(|arg| arg == "--sanitize").is_some();
             let fen = args.collect::<Vec<_>>().join(" ");
-            match Position::from_fen(&fen) {
+            let parsed = if sanitize {
+                Position::from_fen_sanitized(&fen).map(|(position, repaired)| {
+                    for violation in repaired {
+                        eprintln!("repaired: {violation}");
+                    }
+                    position
+                })
+            } else {
+                Position::from_fen_strict(&fen)
+            };
+            match parsed {
                 Ok(position) => {
                     println!("{}", position.to_fen());
                     println!("{}", position.ascii_board());
                 }
-                Err(error) => {
-                    eprintln!("FEN error: {error}");
-                    process::exit(2);
-                }
+                Err(error) => exit_with_fen_error(&error),
             }
         }
         Some("pgn") => {
-            let input = match read_pgn_input(args.next()) {
+            let input = match read_input(args.next()) {
                 Ok(input) => input,
                 Err(error) => {
                     eprintln!("PGN read error: {error}");
-                    process::exit(2);
+                    process::exit(EXIT_IO_ERROR);
                 }
             };
-            match parse_pgn(&input) {
-                Ok(game) => match position_after_moves(&game.start_fen, &game.moves) {
+            let games = match parse_pgn_games(&input) {
+                Ok(games) => games,
+                Err(error) => {
+                    eprintln!("PGN parse error: {error}");
+                    process::exit(EXIT_PGN_ERROR);
+                }
+            };
+            for (index, game) in games.iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                match position_after_moves(&game.start_fen, &game.moves) {
                     Ok(position) => {
                         println!("Result: {}", game.result);
                         println!("Final FEN: {}", position.to_fen());
@@ -97,14 +196,11 @@ fn main() {
                             Err(error) => eprintln!("PGN export error: {error}"),
                         }
                     }
-                    Err(error) => {
+                    Err(ChessError::Fen(error)) => exit_with_fen_error(&error),
+                    Err(ChessError::Move(error)) => {
                         eprintln!("PGN replay error: {error}");
-                        process::exit(2);
+                        process::exit(EXIT_MOVE_ERROR);
                     }
-                },
-                Err(error) => {
-                    eprintln!("PGN parse error: {error}");
-                    process::exit(2);
                 }
             }
         }
@@ -118,15 +214,47 @@ fn parse_optional_fen(fen: &str) -> Position {
     } else {
         match Position::from_fen(fen) {
             Ok(position) => position,
-            Err(error) => {
-                eprintln!("FEN error: {error}");
-                process::exit(2);
-            }
+            Err(error) => exit_with_fen_error(&error),
         }
     }
 }
 
-fn read_pgn_input(path: Option<String>) -> Result<String, String> {
+fn exit_with_fen_error(error: &FenError) -> ! {
+    eprintln!("FEN error: {error}");
+    process::exit(EXIT_FEN_ERROR);
+}
+
+/// `--threads N`, `--granularity N` and `--hash MB` from anywhere in `args`.
+fn take_perft_settings(args: &mut Vec<String>) -> PerftSettings {
+    let defaults = PerftSettings::default();
+    PerftSettings {
+        threads: take_flag(args, "--threads").unwrap_or(defaults.threads),
+        granularity: take_flag(args, "--granularity").unwrap_or(defaults.granularity),
+        hash_mb: take_flag(args, "--hash").unwrap_or(defaults.hash_mb),
+    }
+}
+
+/// Removes `name` and its value from `args`. A value that does not parse is
+/// dropped as well.
+fn take_flag<T: FromStr>(args: &mut Vec<String>, name: &str) -> Option<T> {
+    let index = args.iter().position(|arg| arg == name)?;
+    args.remove(index);
+    if index < args.len() {
+        args.remove(index).parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Leading depth argument, then an optional FEN made of the remaining words.
+fn depth_and_position(args: Vec<String>, default_depth: u32) -> (u32, Position) {
+    let mut args = args.into_iter();
+    let depth = args.next().and_then(|value| value.parse::<u32>().ok()).unwrap_or(default_depth);
+    let fen = args.collect::<Vec<_>>().join(" ");
+    (depth, parse_optional_fen(&fen))
+}
+
+fn read_input(path: Option<String>) -> Result<String, String> {
     if let Some(path) = path {
         fs::read_to_string(&path).map_err(|error| format!("{path}: {error}"))
     } else {
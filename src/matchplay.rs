@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 
-use crate::chess::{ChessMove, Color, DrawReason, Position, STARTPOS_FEN};
-use crate::pgn::export_pgn_with_tags;
+use crate::chess::{chess960_start_fen, ChessError, ChessMove, Color, FenError, Game, MoveError, Position, STARTPOS_FEN};
+use crate::pgn::{export_pgn_with_tags, fen_tag_value};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SearchLimit {
@@ -86,14 +86,10 @@ pub enum MatchStatus {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EngineMatchController {
-    pub start_fen: String,
-    pub position: Position,
-    pub played_moves: Vec<ChessMove>,
+    pub game: Game,
     pub white: UciEngineSlot,
     pub black: UciEngineSlot,
     pub status: MatchStatus,
-    pub result: String,
-    pub termination: Option<String>,
 }
 
 impl EngineMatchController {
@@ -101,28 +97,65 @@ impl EngineMatchController {
         Self::from_fen(STARTPOS_FEN, white, black).expect("STARTPOS_FEN must be valid")
     }
 
+    /// Rejects FENs that describe impossible positions (see `Position::validate`).
+    /// Use `Position::from_fen_sanitized` first to repair bogus castling or
+    /// en-passant fields.
     pub fn from_fen(
         start_fen: &str,
         white: UciEngineSlot,
         black: UciEngineSlot,
-    ) -> Result<Self, String> {
-        let position = Position::from_fen(start_fen.trim())?;
+    ) -> Result<Self, FenError> {
+        let position = Position::from_fen_strict(start_fen.trim())?;
         let mut controller = Self {
-            start_fen: position.to_fen(),
-            position,
-            played_moves: Vec::new(),
+            game: Game::new(position),
             white,
             black,
             status: MatchStatus::Ready,
-            result: "*".to_string(),
-            termination: None,
         };
         controller.finish_if_game_over();
         Ok(controller)
     }
 
+    /// Match from Chess960 start position `index` (0..=959).
+    pub fn chess960(index: u16, white: UciEngineSlot, black: UciEngineSlot) -> Result<Self, FenError> {
+        Self::from_fen(&chess960_start_fen(index)?, white, black)
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.game.position().is_chess960()
+    }
+
+    pub fn start_fen(&self) -> String {
+        self.game.start_fen()
+    }
+
+    pub fn position(&self) -> &Position {
+        self.game.position()
+    }
+
+    pub fn played_moves(&self) -> &[ChessMove] {
+        self.game.moves()
+    }
+
+    /// PGN result token, `*` while the game is running.
+    pub fn result(&self) -> &'static str {
+        self.game.result()
+    }
+
+    /// `Termination` tag value of a finished game, e.g. `checkmate` or `fivefold repetition`.
+    pub fn termination(&self) -> Option<&'static str> {
+        self.game.termination().map(|termination| termination.label())
+    }
+
+    /// Option both engines need before the first `position` command, so that
+    /// they read and write castling as king-takes-rook.
+    pub fn chess960_setoption_command(&self) -> Option<String> {
+        self.is_chess960()
+            .then(|| "setoption name UCI_Chess960 value true".to_string())
+    }
+
     pub fn current_slot(&self) -> &UciEngineSlot {
-        match self.position.side_to_move() {
+        match self.game.position().side_to_move() {
             Color::White => &self.white,
             Color::Black => &self.black,
         }
@@ -133,10 +166,10 @@ impl EngineMatchController {
     }
 
     pub fn position_command(&self) -> String {
-        uci_position_command_from_history(&self.start_fen, &self.played_moves)
+        uci_position_command_from_history(&self.start_fen(), self.game.moves())
     }
 
-    pub fn record_bestmove(&mut self, bestmove: &str) -> Result<(), String> {
+    pub fn record_bestmove(&mut self, bestmove: &str) -> Result<(), MoveError> {
         let move_text = bestmove
             .strip_prefix("bestmove ")
             .unwrap_or(bestmove)
@@ -145,30 +178,26 @@ impl EngineMatchController {
             .unwrap_or("0000");
         if move_text == "0000" {
             self.finish_if_game_over();
-            if self.result == "*" {
+            if !self.game.is_over() {
                 self.status = MatchStatus::Error("engine returned 0000 before terminal position".to_string());
             }
             return Ok(());
         }
 
-        let chess_move = self
-            .position
-            .parse_uci_move(move_text)
-            .ok_or_else(|| format!("illegal UCI move from match engine: {move_text}"))?;
-        self.position.make_legal_move(chess_move)?;
-        self.played_moves.push(chess_move);
+        self.game.push_uci(move_text)?;
         self.finish_if_game_over();
-        if self.result == "*" {
+        if !self.game.is_over() {
             self.status = MatchStatus::Ready;
         }
         Ok(())
     }
 
     pub fn start_thinking(&mut self) {
-        self.status = MatchStatus::Thinking(self.position.side_to_move());
+        self.status = MatchStatus::Thinking(self.game.position().side_to_move());
     }
 
-    pub fn pgn_log(&self) -> Result<String, String> {
+    pub fn pgn_log(&self) -> Result<String, ChessError> {
+        let start_fen = self.start_fen();
         let mut tags = BTreeMap::new();
         tags.insert("Event".to_string(), "rchess engine match".to_string());
         tags.insert("Site".to_string(), "?".to_string());
@@ -176,40 +205,22 @@ impl EngineMatchController {
         tags.insert("Round".to_string(), "?".to_string());
         tags.insert("White".to_string(), self.white.name.clone());
         tags.insert("Black".to_string(), self.black.name.clone());
-        tags.insert("Result".to_string(), self.result.clone());
-        if let Some(termination) = &self.termination {
-            tags.insert("Termination".to_string(), termination.clone());
+        tags.insert("Result".to_string(), self.result().to_string());
+        if let Some(termination) = self.termination() {
+            tags.insert("Termination".to_string(), termination.to_string());
         }
-        if self.start_fen.trim() != STARTPOS_FEN {
+        if start_fen != STARTPOS_FEN {
             tags.insert("SetUp".to_string(), "1".to_string());
-            tags.insert("FEN".to_string(), self.start_fen.clone());
+            tags.insert("FEN".to_string(), fen_tag_value(&start_fen));
         }
-        export_pgn_with_tags(&self.start_fen, &self.played_moves, &self.result, &tags)
+        export_pgn_with_tags(&start_fen, self.game.moves(), self.result(), &tags)
     }
 
     fn finish_if_game_over(&mut self) {
-        if self.position.is_checkmate() {
-            self.result = match self.position.side_to_move() {
-                Color::White => "0-1".to_string(),
-                Color::Black => "1-0".to_string(),
-            };
-            self.termination = Some("checkmate".to_string());
-            self.status = MatchStatus::Finished(format!("{} by checkmate", self.result));
-        } else if let Some(reason) = self.draw_reason() {
-            self.result = "1/2-1/2".to_string();
-            self.termination = Some(reason.label().to_string());
-            self.status = MatchStatus::Finished(format!("{} by {}", self.result, reason.label()));
-        } else {
-            self.result = "*".to_string();
-            self.termination = None;
+        if let Some(termination) = self.game.termination() {
+            self.status = MatchStatus::Finished(format!("{} by {}", termination.result(), termination.label()));
         }
     }
-
-    fn draw_reason(&self) -> Option<DrawReason> {
-        Position::draw_reason_from_history(&self.start_fen, &self.played_moves)
-            .ok()
-            .flatten()
-    }
 }
 
 pub fn uci_position_command_from_history(start_fen: &str, moves: &[ChessMove]) -> String {
@@ -275,28 +286,82 @@ mod tests {
         let black = UciEngineSlot::new("black", "rchess");
         let mut controller = EngineMatchController::new(white, black);
         for move_text in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"] {
-            assert_eq!(controller.result, "*");
+            assert_eq!(controller.result(), "*");
             controller.record_bestmove(move_text).unwrap();
         }
-        assert_eq!(controller.result, "1/2-1/2");
-        assert_eq!(controller.termination.as_deref(), Some("threefold repetition"));
+        assert_eq!(controller.result(), "1/2-1/2");
+        assert_eq!(controller.termination(), Some("threefold repetition"));
         let pgn = controller.pgn_log().unwrap();
         assert!(pgn.contains("[Termination \"threefold repetition\"]"));
         assert!(pgn.contains("1/2-1/2"));
     }
 
+    #[test]
+    fn chess960_controller_uses_king_takes_rook_castling() {
+        let white = UciEngineSlot::new("white", "rchess");
+        let black = UciEngineSlot::new("black", "rchess");
+        let mut controller = EngineMatchController::chess960(0, white, black).unwrap();
+        assert!(controller.is_chess960());
+        assert_eq!(
+            controller.chess960_setoption_command().as_deref(),
+            Some("setoption name UCI_Chess960 value true")
+        );
+        assert_eq!(controller.start_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1");
+        for move_text in ["e1d3", "e8d6", "d1e3", "d8e6", "b2b3", "b7b6", "c1b2", "c8b7", "g1f1"] {
+            controller.record_bestmove(move_text).unwrap();
+        }
+        assert!(controller.position_command().ends_with("c8b7 g1f1"));
+        assert_eq!(controller.position().piece_at(2).map(|piece| piece.kind), Some(crate::chess::PieceKind::King));
+        let pgn = controller.pgn_log().unwrap();
+        assert!(pgn.contains("[Variant \"Chess960\"]"));
+        assert!(pgn.contains("[FEN \"bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1\"]"));
+        assert!(pgn.contains("5. O-O-O *"));
+        assert!(EngineMatchController::chess960(960, UciEngineSlot::new("w", "e"), UciEngineSlot::new("b", "e")).is_err());
+    }
+
+    #[test]
+    fn controller_rejects_impossible_start_positions() {
+        let white = UciEngineSlot::new("white", "rchess");
+        let black = UciEngineSlot::new("black", "rchess");
+        let error = EngineMatchController::from_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", white, black).unwrap_err();
+        assert!(matches!(error, FenError::Illegal { .. }));
+    }
+
     #[test]
     fn controller_adjudicates_fifty_move_rule_as_draw() {
         let white = UciEngineSlot::new("white", "rchess");
         let black = UciEngineSlot::new("black", "rchess");
         let mut controller = EngineMatchController::from_fen(
-            "4k3/8/8/8/8/8/8/4K3 w - - 99 42",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 99 42",
             white,
             black,
         )
         .unwrap();
         controller.record_bestmove("e1f1").unwrap();
-        assert_eq!(controller.result, "1/2-1/2");
-        assert_eq!(controller.termination.as_deref(), Some("50-move rule"));
+        assert_eq!(controller.result(), "1/2-1/2");
+        assert_eq!(controller.termination(), Some("50-move rule"));
+    }
+
+    #[test]
+    fn controller_adjudicates_insufficient_material_after_last_capture() {
+        let white = UciEngineSlot::new("white", "rchess");
+        let black = UciEngineSlot::new("black", "rchess");
+        let mut controller =
+            EngineMatchController::from_fen("4k3/8/8/8/8/8/4r3/3NK3 w - - 10 60", white, black).unwrap();
+        assert_eq!(controller.result(), "*");
+        controller.record_bestmove("e1e2").unwrap();
+        assert_eq!(controller.result(), "1/2-1/2");
+        assert_eq!(controller.termination(), Some("insufficient material"));
+        assert!(controller.pgn_log().unwrap().contains("[Termination \"insufficient material\"]"));
+    }
+
+    #[test]
+    fn controller_stops_at_seventy_five_moves_without_claim() {
+        let white = UciEngineSlot::new("white", "rchess");
+        let black = UciEngineSlot::new("black", "rchess");
+        let controller =
+            EngineMatchController::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 150 120", white, black).unwrap();
+        assert_eq!(controller.result(), "1/2-1/2");
+        assert_eq!(controller.termination(), Some("75-move rule"));
     }
 }
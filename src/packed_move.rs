@@ -0,0 +1,202 @@
+//! 16-bit move encoding with explicit move-kind flags.
+//!
+//! Bits 0-5 hold the origin square, 6-11 the target square and 12-15 the kind.
+//! Castling keeps the target square of `ChessMove`: the king's destination in
+//! classical chess, the castling rook's square in Chess960. The raw value 0
+//! (a1a1, quiet) never encodes a real move, so tables use it for "no move".
+
+use std::fmt;
+
+use crate::chess::{CastlingSide, ChessMove, PieceKind};
+
+const QUIET: u16 = 0;
+const DOUBLE_PAWN_PUSH: u16 = 1;
+const KING_CASTLE: u16 = 2;
+const QUEEN_CASTLE: u16 = 3;
+const CAPTURE: u16 = 4;
+const EN_PASSANT: u16 = 5;
+const PROMOTION: u16 = 8;
+const PROMOTION_CAPTURE: u16 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveKind {
+    Quiet,
+    DoublePawnPush,
+    Castle(CastlingSide),
+    Capture,
+    EnPassant,
+    Promotion { piece: PieceKind, capture: bool },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PackedMove(u16);
+
+impl PackedMove {
+    /// Promotion kinds other than knight, bishop, rook and queen are stored as queen.
+    pub fn new(from: u8, to: u8, kind: MoveKind) -> Self {
+        let flags = match kind {
+            MoveKind::Quiet => QUIET,
+            MoveKind::DoublePawnPush => DOUBLE_PAWN_PUSH,
+            MoveKind::Castle(CastlingSide::King) => KING_CASTLE,
+            MoveKind::Castle(CastlingSide::Queen) => QUEEN_CASTLE,
+            MoveKind::Capture => CAPTURE,
+            MoveKind::EnPassant => EN_PASSANT,
+            MoveKind::Promotion { piece, capture } => {
+                let base = if capture { PROMOTION_CAPTURE } else { PROMOTION };
+                base + match piece {
+                    PieceKind::Knight => 0,
+                    PieceKind::Bishop => 1,
+                    PieceKind::Rook => 2,
+                    _ => 3,
+                }
+            }
+        };
+        Self((from as u16 & 0x3f) | ((to as u16 & 0x3f) << 6) | (flags << 12))
+    }
+
+    /// Decodes a value stored with `raw`. Returns `None` for 0 and for the two
+    /// unused kind codes.
+    pub fn from_raw(raw: u16) -> Option<Self> {
+        let flags = raw >> 12;
+        (raw != 0 && flags != 6 && flags != 7).then_some(Self(raw))
+    }
+
+    pub fn raw(self) -> u16 {
+        self.0
+    }
+
+    pub fn from(self) -> u8 {
+        (self.0 & 0x3f) as u8
+    }
+
+    pub fn to(self) -> u8 {
+        ((self.0 >> 6) & 0x3f) as u8
+    }
+
+    pub fn kind(self) -> MoveKind {
+        match self.flags() {
+            DOUBLE_PAWN_PUSH => MoveKind::DoublePawnPush,
+            KING_CASTLE => MoveKind::Castle(CastlingSide::King),
+            QUEEN_CASTLE => MoveKind::Castle(CastlingSide::Queen),
+            CAPTURE => MoveKind::Capture,
+            EN_PASSANT => MoveKind::EnPassant,
+            flags if flags >= PROMOTION => MoveKind::Promotion {
+                piece: [PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen][(flags & 3) as usize],
+                capture: flags >= PROMOTION_CAPTURE,
+            },
+            _ => MoveKind::Quiet,
+        }
+    }
+
+    /// Captures, en passant included.
+    pub fn is_capture(self) -> bool {
+        matches!(self.flags(), CAPTURE | EN_PASSANT) || self.flags() >= PROMOTION_CAPTURE
+    }
+
+    pub fn is_en_passant(self) -> bool {
+        self.flags() == EN_PASSANT
+    }
+
+    pub fn is_double_pawn_push(self) -> bool {
+        self.flags() == DOUBLE_PAWN_PUSH
+    }
+
+    pub fn castling_side(self) -> Option<CastlingSide> {
+        match self.flags() {
+            KING_CASTLE => Some(CastlingSide::King),
+            QUEEN_CASTLE => Some(CastlingSide::Queen),
+            _ => None,
+        }
+    }
+
+    pub fn promotion(self) -> Option<PieceKind> {
+        match self.kind() {
+            MoveKind::Promotion { piece, .. } => Some(piece),
+            _ => None,
+        }
+    }
+
+    /// Neither a capture nor a promotion.
+    pub fn is_quiet(self) -> bool {
+        self.flags() <= QUEEN_CASTLE
+    }
+
+    pub fn to_move(self) -> ChessMove {
+        ChessMove::new(self.from(), self.to(), self.promotion())
+    }
+
+    pub fn to_uci(self) -> String {
+        self.to_move().to_uci()
+    }
+
+    fn flags(self) -> u16 {
+        self.0 >> 12
+    }
+}
+
+impl From<PackedMove> for ChessMove {
+    fn from(packed: PackedMove) -> Self {
+        packed.to_move()
+    }
+}
+
+impl fmt::Display for PackedMove {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.to_uci())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::Position;
+
+    #[test]
+    fn packs_every_move_kind_and_round_trips_through_raw() {
+        let kinds = [
+            MoveKind::Quiet,
+            MoveKind::DoublePawnPush,
+            MoveKind::Castle(CastlingSide::King),
+            MoveKind::Castle(CastlingSide::Queen),
+            MoveKind::Capture,
+            MoveKind::EnPassant,
+            MoveKind::Promotion { piece: PieceKind::Knight, capture: false },
+            MoveKind::Promotion { piece: PieceKind::Queen, capture: true },
+        ];
+        for kind in kinds {
+            let packed = PackedMove::new(52, 63, kind);
+            assert_eq!((packed.from(), packed.to(), packed.kind()), (52, 63, kind));
+            assert_eq!(PackedMove::from_raw(packed.raw()), Some(packed));
+        }
+        assert_eq!(PackedMove::from_raw(0), None);
+        assert_eq!(PackedMove::from_raw(6 << 12 | 1), None);
+        assert_eq!(std::mem::size_of::<PackedMove>(), 2);
+    }
+
+    #[test]
+    fn generator_flags_match_the_position() {
+        let position =
+            Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let packed = position.legal_packed_moves();
+        assert_eq!(packed.len(), 48);
+        for chess_move in packed.iter().copied() {
+            assert_eq!(position.pack(chess_move.to_move()), chess_move, "{chess_move}");
+            assert_eq!(chess_move.is_capture(), position.is_capture(chess_move.to_move()), "{chess_move}");
+        }
+        assert_eq!(packed.iter().filter(|chess_move| chess_move.is_capture()).count(), 8);
+        assert_eq!(packed.iter().filter(|chess_move| chess_move.castling_side().is_some()).count(), 2);
+        assert_eq!(packed.iter().filter(|chess_move| chess_move.is_double_pawn_push()).count(), 2);
+
+        let en_passant = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let capture = en_passant.parse_uci_packed("e5d6").unwrap();
+        assert_eq!(capture.kind(), MoveKind::EnPassant);
+        assert!(capture.is_capture());
+
+        let promotion = Position::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            promotion.parse_uci_packed("a7b8n").unwrap().kind(),
+            MoveKind::Promotion { piece: PieceKind::Knight, capture: true }
+        );
+        assert_eq!(promotion.parse_uci_packed("a7a8q").unwrap().to_uci(), "a7a8q");
+    }
+}
@@ -0,0 +1,593 @@
+//! Perft with root splitting across threads and an optional node-count hash,
+//! plus the EPD perft-suite reader behind `rchess perft-suite` and the
+//! external-engine cross-check behind `rchess perft-diff`.
+//!
+//! Root moves are split the same way as the deterministic search split: the
+//! legal root list is cut into `granularity`-sized chunks and the chunks are
+//! dealt round-robin to the threads. Node counts do not depend on scheduling,
+//! so the result is the same for any thread count.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::chess::{FenError, Position};
+use crate::packed_move::PackedMove;
+use crate::search::SearchSettings;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PerftSettings {
+    pub threads: usize,
+    pub granularity: usize,
+    /// Size of the node-count hash in megabytes; 0 turns it off.
+    pub hash_mb: usize,
+}
+
+impl Default for PerftSettings {
+    fn default() -> Self {
+        let threads = thread::available_parallelism()
+            .map(|threads| threads.get().clamp(1, 64))
+            .unwrap_or(1);
+        Self { threads, granularity: 1, hash_mb: 0 }
+    }
+}
+
+impl PerftSettings {
+    /// Threads and granularity of a search configuration, without a hash.
+    pub fn from_search(settings: SearchSettings) -> Self {
+        let threads = if settings.deterministic_multithread { settings.max_threads } else { 1 };
+        Self { threads, granularity: settings.granularity, hash_mb: 0 }.normalized()
+    }
+
+    pub fn normalized(mut self) -> Self {
+        self.threads = self.threads.clamp(1, 64);
+        self.granularity = self.granularity.clamp(1, 64);
+        self.hash_mb = self.hash_mb.min(4096);
+        self
+    }
+}
+
+pub fn perft(position: &Position, depth: u32, settings: PerftSettings) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    perft_divide(position, depth, settings).iter().map(|(_, nodes)| nodes).sum()
+}
+
+/// Node counts per root move, in `legal_packed_moves` order.
+pub fn perft_divide(position: &Position, depth: u32, settings: PerftSettings) -> Vec<(PackedMove, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let settings = settings.normalized();
+    let moves = position.legal_packed_moves();
+    let table = (settings.hash_mb > 0 && depth > 2).then(|| PerftTable::new(settings.hash_mb));
+    let tasks: Vec<&[PackedMove]> = moves.chunks(settings.granularity).collect();
+    let thread_count = settings.threads.min(tasks.len()).max(1);
+    let mut counts = vec![0; moves.len()];
+
+    thread::scope(|scope| {
+        let mut handles = Vec::with_capacity(thread_count);
+        for thread_id in 0..thread_count {
+            let assigned: Vec<(usize, &[PackedMove])> = tasks
+                .iter()
+                .enumerate()
+                .filter(|(task_index, _)| task_index % thread_count == thread_id)
+                .map(|(task_index, task)| (task_index * settings.granularity, *task))
+                .collect();
+            let table = table.as_ref();
+            let mut board = position.clone();
+            handles.push(scope.spawn(move || {
+                let mut results = Vec::new();
+                for (first_index, task) in assigned {
+                    for (offset, packed) in task.iter().enumerate() {
+                        let chess_move = packed.to_move();
+                        let undo = board.make_move(chess_move);
+                        results.push((first_index + offset, count_nodes(&mut board, depth - 1, table)));
+                        board.unmake_move(chess_move, undo);
+                    }
+                }
+                results
+            }));
+        }
+        for handle in handles {
+            for (index, nodes) in handle.join().expect("perft worker panicked") {
+                counts[index] = nodes;
+            }
+        }
+    });
+
+    moves.into_iter().zip(counts).collect()
+}
+
+fn count_nodes(position: &mut Position, depth: u32, table: Option<&PerftTable>) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = position.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let key = position.zobrist_key();
+    if let Some(nodes) = table.and_then(|table| table.probe(key, depth)) {
+        return nodes;
+    }
+    let mut nodes = 0;
+    for chess_move in moves {
+        let undo = position.make_move(chess_move);
+        nodes += count_nodes(position, depth - 1, table);
+        position.unmake_move(chess_move, undo);
+    }
+    if let Some(table) = table {
+        table.store(key, depth, nodes);
+    }
+    nodes
+}
+
+/// Shared node-count cache. The key slot holds `key ^ data`, so an entry
+/// torn by two threads writing at once fails the check on read.
+struct PerftTable {
+    entries: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl PerftTable {
+    fn new(hash_mb: usize) -> Self {
+        let count = (hash_mb * 1024 * 1024 / std::mem::size_of::<(AtomicU64, AtomicU64)>()).max(1024);
+        Self { entries: (0..count).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect() }
+    }
+
+    fn probe(&self, key: u64, depth: u32) -> Option<u64> {
+        let (stored_key, stored_data) = &self.entries[(key % self.entries.len() as u64) as usize];
+        let data = stored_data.load(Ordering::Relaxed);
+        let matches = stored_key.load(Ordering::Relaxed) ^ data == key && data & 0xff == depth as u64;
+        matches.then_some(data >> 8)
+    }
+
+    fn store(&self, key: u64, depth: u32, nodes: u64) {
+        let (stored_key, stored_data) = &self.entries[(key % self.entries.len() as u64) as usize];
+        let data = (nodes << 8) | (depth as u64 & 0xff);
+        stored_data.store(data, Ordering::Relaxed);
+        stored_key.store(key ^ data, Ordering::Relaxed);
+    }
+}
+
+/// One EPD line: a position and the expected node count for each depth.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftCase {
+    pub line: usize,
+    pub fen: String,
+    pub expected: Vec<(u32, u64)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EpdError {
+    Fen { line: usize, error: FenError },
+    BadField { line: usize, field: String },
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fen { line, error } => write!(formatter, "line {line}: {error}"),
+            Self::BadField { line, field } => write!(formatter, "line {line}: expected `D<depth> <nodes>`, found `{field}`"),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Fen { error, .. } => Some(error),
+            Self::BadField { .. } => None,
+        }
+    }
+}
+
+/// Reads `<fen> ;D1 20 ;D2 400 ...` lines. Blank lines and `#` comments are
+/// skipped; line numbers start at 1.
+pub fn parse_perft_epd(text: &str) -> Result<Vec<PerftCase>, EpdError> {
+    let mut cases = Vec::new();
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw_line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let mut parts = trimmed.split(';');
+        let fen = parts.next().unwrap_or_default().trim().to_string();
+        Position::from_fen(&fen).map_err(|error| EpdError::Fen { line, error })?;
+        let mut expected = Vec::new();
+        for field in parts.map(str::trim).filter(|field| !field.is_empty()) {
+            let bad_field = || EpdError::BadField { line, field: field.to_string() };
+            let (depth, nodes) = field.strip_prefix('D').and_then(|rest| rest.split_once(' ')).ok_or_else(bad_field)?;
+            let depth = depth.parse::<u32>().map_err(|_| bad_field())?;
+            let nodes = nodes.trim().parse::<u64>().map_err(|_| bad_field())?;
+            expected.push((depth, nodes));
+        }
+        cases.push(PerftCase { line, fen, expected });
+    }
+    Ok(cases)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftResult {
+    pub line: usize,
+    pub fen: String,
+    pub depth: u32,
+    pub expected: u64,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl PerftResult {
+    pub fn passed(&self) -> bool {
+        self.nodes == self.expected
+    }
+
+    pub fn nodes_per_second(&self) -> u64 {
+        nodes_per_second(self.nodes, self.elapsed)
+    }
+}
+
+pub fn nodes_per_second(nodes: u64, elapsed: Duration) -> u64 {
+    let micros = elapsed.as_micros().max(1);
+    (nodes as u128 * 1_000_000 / micros) as u64
+}
+
+/// Runs every expectation up to `max_depth` and hands each result to `report`
+/// as soon as it is known.
+pub fn run_perft_suite(
+    cases: &[PerftCase],
+    max_depth: Option<u32>,
+    settings: PerftSettings,
+    mut report: impl FnMut(&PerftResult),
+) -> Vec<PerftResult> {
+    let mut results = Vec::new();
+    for case in cases {
+        let Ok(position) = Position::from_fen(&case.fen) else {
+            continue;
+        };
+        for (depth, expected) in case.expected.iter().copied() {
+            if max_depth.is_some_and(|max_depth| depth > max_depth) {
+                continue;
+            }
+            let started = Instant::now();
+            let nodes = perft(&position, depth, settings);
+            let result = PerftResult {
+                line: case.line,
+                fen: case.fen.clone(),
+                depth,
+                expected,
+                nodes,
+                elapsed: started.elapsed(),
+            };
+            report(&result);
+            results.push(result);
+        }
+    }
+    results
+}
+
+/// One root move whose count differs between rchess and the reference.
+/// `None` means the move is missing from that side's list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoveCountDiff {
+    pub uci: String,
+    pub ours: Option<u64>,
+    pub theirs: Option<u64>,
+}
+
+/// Where the descent stopped: the moves played from the start position, the
+/// position reached, and the rows that still disagree there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftDiff {
+    pub path: Vec<String>,
+    pub fen: String,
+    pub depth: u32,
+    pub moves: Vec<MoveCountDiff>,
+}
+
+impl PerftDiff {
+    /// Moves one generator produces and the other does not. When this is
+    /// empty the reference agreed on every child of the first mismatch.
+    pub fn move_list_diffs(&self) -> impl Iterator<Item = &MoveCountDiff> {
+        self.moves.iter().filter(|row| row.ours.is_none() || row.theirs.is_none())
+    }
+}
+
+/// Compares `perft_divide` with `reference` and, while only the counts
+/// differ, plays the first mismatching move and repeats one ply shallower.
+/// Returns `None` when both sides agree at the root.
+pub fn perft_diff<E>(
+    position: &Position,
+    depth: u32,
+    settings: PerftSettings,
+    mut reference: impl FnMut(&Position, u32) -> Result<Vec<(String, u64)>, E>,
+) -> Result<Option<PerftDiff>, E> {
+    let mut position = position.clone();
+    let mut depth = depth;
+    let mut path = Vec::new();
+    let mut found = None;
+    while depth > 0 {
+        let ours: BTreeMap<String, (PackedMove, u64)> = perft_divide(&position, depth, settings)
+            .into_iter()
+            .map(|(chess_move, nodes)| (chess_move.to_uci(), (chess_move, nodes)))
+            .collect();
+        let theirs: BTreeMap<String, u64> = reference(&position, depth)?.into_iter().collect();
+        let mut moves: Vec<MoveCountDiff> = ours
+            .iter()
+            .map(|(uci, (_, nodes))| MoveCountDiff { uci: uci.clone(), ours: Some(*nodes), theirs: theirs.get(uci).copied() })
+            .chain(theirs.iter().filter(|(uci, _)| !ours.contains_key(*uci)).map(|(uci, nodes)| MoveCountDiff {
+                uci: uci.clone(),
+                ours: None,
+                theirs: Some(*nodes),
+            }))
+            .filter(|row| row.ours != row.theirs)
+            .collect();
+        if moves.is_empty() {
+            break;
+        }
+        moves.sort_by(|left, right| left.uci.cmp(&right.uci));
+        let descend = moves
+            .iter()
+            .all(|row| row.ours.is_some() && row.theirs.is_some())
+            .then(|| ours[&moves[0].uci].0);
+        found = Some(PerftDiff { path: path.clone(), fen: position.to_fen(), depth, moves });
+        let Some(chess_move) = descend.filter(|_| depth > 1) else {
+            break;
+        };
+        path.push(chess_move.to_uci());
+        position.make_move(chess_move.to_move());
+        depth -= 1;
+    }
+    Ok(found)
+}
+
+#[derive(Debug)]
+pub enum PerftEngineError {
+    Io(io::Error),
+    /// The engine closed its output before finishing the command.
+    Closed,
+    /// A divide row that is not `<move>: <nodes>`.
+    BadLine(String),
+    /// No `uciok` within the handshake timeout; the program is killed.
+    NotUci(Duration),
+}
+
+impl fmt::Display for PerftEngineError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(formatter, "engine I/O error: {error}"),
+            Self::Closed => formatter.write_str("engine closed its output"),
+            Self::BadLine(line) => write!(formatter, "unexpected engine output `{line}`"),
+            Self::NotUci(timeout) => write!(formatter, "no `uciok` within {:.1}s, not a UCI engine?", timeout.as_secs_f64()),
+        }
+    }
+}
+
+impl std::error::Error for PerftEngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PerftEngineError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// How long `UciPerftEngine::spawn` waits for `uciok`.
+pub const UCI_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A UCI engine that answers `go perft N` with Stockfish-style divide rows
+/// followed by `Nodes searched: N`.
+pub struct UciPerftEngine {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines from the engine's stdout, read on a separate thread so the
+    /// handshake can time out; the channel closes at EOF.
+    lines: Receiver<String>,
+    chess960: bool,
+}
+
+impl UciPerftEngine {
+    pub fn spawn(program: impl AsRef<Path>) -> Result<Self, PerftEngineError> {
+        Self::spawn_with_timeout(program, UCI_HANDSHAKE_TIMEOUT)
+    }
+
+    /// Like `spawn`, but gives up after `timeout` without `uciok`, so a
+    /// program that is not a UCI engine cannot hang the caller.
+    pub fn spawn_with_timeout(program: impl AsRef<Path>, timeout: Duration) -> Result<Self, PerftEngineError> {
+        let mut child = Command::new(program.as_ref())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or(PerftEngineError::Closed)?;
+        let stdout = BufReader::new(child.stdout.take().ok_or(PerftEngineError::Closed)?);
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line.trim().to_string()).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = Self { child, stdin, lines, chess960: false };
+        if let Err(error) = engine.handshake(timeout) {
+            let _ = engine.child.kill();
+            return Err(error);
+        }
+        Ok(engine)
+    }
+
+    fn handshake(&mut self, timeout: Duration) -> Result<(), PerftEngineError> {
+        self.send("uci")?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(left) {
+                Ok(line) if line == "uciok" => return Ok(()),
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => return Err(PerftEngineError::NotUci(timeout)),
+                Err(RecvTimeoutError::Disconnected) => return Err(PerftEngineError::Closed),
+            }
+        }
+    }
+
+    pub fn divide(&mut self, position: &Position, depth: u32) -> Result<Vec<(String, u64)>, PerftEngineError> {
+        if position.is_chess960() != self.chess960 {
+            self.chess960 = position.is_chess960();
+            self.send(&format!("setoption name UCI_Chess960 value {}", self.chess960))?;
+        }
+        self.send(&format!("position fen {}", position.to_fen()))?;
+        self.send(&format!("go perft {depth}"))?;
+        let mut rows = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            if line.starts_with("Nodes searched") {
+                return Ok(rows);
+            }
+            if line.is_empty() || line.starts_with("info") {
+                continue;
+            }
+            let row = line
+                .split_once(':')
+                .and_then(|(uci, nodes)| Some((uci.trim().to_string(), nodes.trim().parse::<u64>().ok()?)));
+            rows.push(row.ok_or(PerftEngineError::BadLine(line))?);
+        }
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{command}")?;
+        self.stdin.flush()
+    }
+
+    fn read_line(&mut self) -> Result<String, PerftEngineError> {
+        self.lines.recv().map_err(|_| PerftEngineError::Closed)
+    }
+}
+
+impl Drop for UciPerftEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::STARTPOS_FEN;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn threaded_and_hashed_perft_match_the_plain_count() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        let plain = position.perft(3);
+        for settings in [
+            PerftSettings { threads: 1, granularity: 1, hash_mb: 0 },
+            PerftSettings { threads: 4, granularity: 3, hash_mb: 0 },
+            PerftSettings { threads: 3, granularity: 2, hash_mb: 1 },
+        ] {
+            assert_eq!(perft(&position, 3, settings), plain, "{settings:?}");
+        }
+        let divide = perft_divide(&position, 3, PerftSettings { threads: 4, granularity: 5, hash_mb: 1 });
+        assert_eq!(divide, position.perft_divide(3));
+        assert_eq!(perft(&position, 0, PerftSettings::default()), 1);
+    }
+
+    #[test]
+    fn epd_suite_reports_mismatching_depths() {
+        let text = format!(
+            "# standard positions\n{STARTPOS_FEN} ;D1 20 ;D2 400 ;D3 8902\n\n8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - ;D1 14 ;D2 190\n"
+        );
+        let cases = parse_perft_epd(&text).unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[1].line, 4);
+        assert_eq!(cases[1].expected, vec![(1, 14), (2, 190)]);
+
+        let mut reported = 0;
+        let results = run_perft_suite(&cases, Some(2), PerftSettings::default(), |_| reported += 1);
+        assert_eq!(reported, 4);
+        let failures: Vec<_> = results.iter().filter(|result| !result.passed()).collect();
+        assert_eq!(failures.len(), 1);
+        assert_eq!((failures[0].line, failures[0].depth, failures[0].nodes), (4, 2, 191));
+
+        assert_eq!(
+            parse_perft_epd("8/8/8/8/8/8/8/8 w - - ;D1 0").unwrap_err(),
+            EpdError::Fen { line: 1, error: FenError::MissingKing { color: crate::chess::Color::White } }
+        );
+        assert!(matches!(
+            parse_perft_epd(&format!("{STARTPOS_FEN} ;D1 twenty")).unwrap_err(),
+            EpdError::BadField { line: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn perft_diff_descends_to_the_position_where_generators_disagree() {
+        let position = Position::from_fen(KIWIPETE).unwrap();
+        let settings = PerftSettings { threads: 2, granularity: 4, hash_mb: 0 };
+        let honest = |position: &Position, depth: u32| -> Result<_, ()> {
+            Ok(position.perft_divide(depth).into_iter().map(|(chess_move, nodes)| (chess_move.to_uci(), nodes)).collect())
+        };
+        assert_eq!(perft_diff(&position, 3, settings, honest), Ok(None));
+
+        // A reference generator that never produces White's queenside castling.
+        fn blind_count(position: &mut Position, depth: u32) -> u64 {
+            if depth == 0 {
+                return 1;
+            }
+            let mut nodes = 0;
+            for chess_move in position.legal_moves().into_iter().filter(|chess_move| chess_move.to_uci() != "e1c1") {
+                let undo = position.make_move(chess_move);
+                nodes += blind_count(position, depth - 1);
+                position.unmake_move(chess_move, undo);
+            }
+            nodes
+        }
+        let blind = |position: &Position, depth: u32| -> Result<Vec<(String, u64)>, ()> {
+            let mut board = position.clone();
+            Ok(position
+                .legal_moves()
+                .into_iter()
+                .filter(|chess_move| chess_move.to_uci() != "e1c1")
+                .map(|chess_move| {
+                    let undo = board.make_move(chess_move);
+                    let nodes = blind_count(&mut board, depth - 1);
+                    board.unmake_move(chess_move, undo);
+                    (chess_move.to_uci(), nodes)
+                })
+                .collect())
+        };
+        let black_to_move = Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1").unwrap();
+        let diff = perft_diff(&black_to_move, 2, settings, blind).unwrap().unwrap();
+        assert_eq!(diff.depth, 1);
+        assert_eq!(diff.path.len(), 1);
+        let missing: Vec<_> = diff.move_list_diffs().collect();
+        assert_eq!(missing, vec![&MoveCountDiff { uci: "e1c1".to_string(), ours: Some(1), theirs: None }]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn uci_handshake_gives_up_on_programs_that_are_not_engines() {
+        let started = Instant::now();
+        let silent = UciPerftEngine::spawn_with_timeout("cat", Duration::from_millis(200));
+        assert!(matches!(silent, Err(PerftEngineError::NotUci(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let exited = UciPerftEngine::spawn_with_timeout("true", Duration::from_secs(5));
+        assert!(matches!(exited, Err(PerftEngineError::Closed | PerftEngineError::Io(_))));
+    }
+}
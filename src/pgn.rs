@@ -1,22 +1,98 @@
 use std::collections::BTreeMap;
+use std::fmt;
 
-use crate::chess::{file_of, rank_of, square_name, ChessMove, Color, PieceKind, Position, STARTPOS_FEN};
+use crate::chess::{
+    file_of, parse_square, rank_of, square_name, CastlingSide, ChessError, ChessMove, Color, FenError, MoveError, PieceKind,
+    Position, STARTPOS_FEN,
+};
+use crate::packed_move::PackedMove;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PgnGame {
     pub tags: BTreeMap<String, String>,
     pub start_fen: String,
-    pub moves: Vec<ChessMove>,
+    pub moves: Vec<PackedMove>,
     pub result: String,
 }
 
-pub fn parse_pgn(text: &str) -> Result<PgnGame, String> {
+impl PgnGame {
+    pub fn chess_moves(&self) -> Vec<ChessMove> {
+        self.moves.iter().map(|packed| packed.to_move()).collect()
+    }
+}
+
+/// Where and why PGN import stopped. `game_index` counts games in the input
+/// from 0, `ply` is the number of moves of that game read before the failure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnError {
+    pub game_index: usize,
+    pub ply: usize,
+    pub token: String,
+    pub kind: PgnErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnErrorKind {
+    BadFen(FenError),
+    UnknownMove { fen: String },
+    AmbiguousMove { fen: String },
+    IllegalMove(MoveError),
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "game {}, ply {}: ", self.game_index + 1, self.ply + 1)?;
+        match &self.kind {
+            PgnErrorKind::BadFen(error) => write!(formatter, "bad FEN tag `{}`: {error}", self.token),
+            PgnErrorKind::UnknownMove { fen } => write!(formatter, "cannot parse PGN move `{}` in position {fen}", self.token),
+            PgnErrorKind::AmbiguousMove { fen } => write!(formatter, "ambiguous PGN move `{}` in position {fen}", self.token),
+            PgnErrorKind::IllegalMove(error) => write!(formatter, "PGN move `{}`: {error}", self.token),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            PgnErrorKind::BadFen(error) => Some(error),
+            PgnErrorKind::IllegalMove(error) => Some(error),
+            PgnErrorKind::UnknownMove { .. } | PgnErrorKind::AmbiguousMove { .. } => None,
+        }
+    }
+}
+
+/// Parses the first game of `text`.
+pub fn parse_pgn(text: &str) -> Result<PgnGame, PgnError> {
+    parse_pgn_game(text, 0)
+}
+
+/// Parses every game of a multi-game PGN file. A new game starts at the first
+/// tag line that follows move text.
+pub fn parse_pgn_games(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    split_games(text)
+        .iter()
+        .enumerate()
+        .map(|(game_index, game_text)| parse_pgn_game(game_text, game_index))
+        .collect()
+}
+
+fn parse_pgn_game(text: &str, game_index: usize) -> Result<PgnGame, PgnError> {
     let tags = parse_tags(text);
-    let start_fen = tags
-        .get("FEN")
-        .cloned()
-        .unwrap_or_else(|| STARTPOS_FEN.to_string());
-    let mut position = Position::from_fen(&start_fen)?;
+    let fen = tags.get("FEN").map(String::as_str).unwrap_or(STARTPOS_FEN);
+    let mut position = Position::from_fen(fen).map_err(|error| PgnError {
+        game_index,
+        ply: 0,
+        token: fen.to_string(),
+        kind: PgnErrorKind::BadFen(error),
+    })?;
+    if tags.get("Variant").is_some_and(|variant| is_chess960_variant(variant)) {
+        position.set_chess960(true);
+    }
+    let start_fen = if position.is_chess960() {
+        position.to_fen()
+    } else {
+        tags.get("FEN").cloned().unwrap_or_else(|| STARTPOS_FEN.to_string())
+    };
     let mut moves = Vec::new();
     let mut result = tags.get("Result").cloned().unwrap_or_else(|| "*".to_string());
 
@@ -34,9 +110,12 @@ pub fn parse_pgn(text: &str) -> Result<PgnGame, String> {
             continue;
         }
 
-        let chess_move = parse_move_token(&position, &token)?;
-        position.make_legal_move(chess_move)?;
-        moves.push(chess_move);
+        let error = |kind| PgnError { game_index, ply: moves.len(), token: token.clone(), kind };
+        let packed = parse_san(&position, &token).map_err(error)?;
+        position
+            .make_legal_move(packed.to_move())
+            .map_err(|move_error| error(PgnErrorKind::IllegalMove(move_error)))?;
+        moves.push(packed);
     }
 
     Ok(PgnGame {
@@ -47,7 +126,7 @@ pub fn parse_pgn(text: &str) -> Result<PgnGame, String> {
     })
 }
 
-pub fn export_pgn(start_fen: &str, moves: &[ChessMove], result: &str) -> Result<String, String> {
+pub fn export_pgn<M: Copy + Into<ChessMove>>(start_fen: &str, moves: &[M], result: &str) -> Result<String, ChessError> {
     let mut tags = BTreeMap::new();
     tags.insert("Event".to_string(), "rchess game".to_string());
     tags.insert("Site".to_string(), "?".to_string());
@@ -58,17 +137,35 @@ pub fn export_pgn(start_fen: &str, moves: &[ChessMove], result: &str) -> Result<
     tags.insert("Result".to_string(), result.to_string());
     if start_fen.trim() != STARTPOS_FEN {
         tags.insert("SetUp".to_string(), "1".to_string());
-        tags.insert("FEN".to_string(), start_fen.trim().to_string());
+        tags.insert("FEN".to_string(), fen_tag_value(start_fen));
     }
     export_pgn_with_tags(start_fen, moves, result, &tags)
 }
 
-pub fn export_pgn_with_tags(
+/// FEN tag value for a start position. Chess960 starts are written as X-FEN,
+/// which PGN readers that know the `Variant "Chess960"` tag expect.
+pub fn fen_tag_value(start_fen: &str) -> String {
+    match Position::from_fen(start_fen.trim()) {
+        Ok(position) if position.is_chess960() => position.to_xfen(),
+        _ => start_fen.trim().to_string(),
+    }
+}
+
+fn is_chess960_variant(variant: &str) -> bool {
+    let normalized: String = variant
+        .chars()
+        .filter(|ch| ch.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    matches!(normalized.as_str(), "chess960" | "fischerandom" | "fischerrandom" | "960")
+}
+
+pub fn export_pgn_with_tags<M: Copy + Into<ChessMove>>(
     start_fen: &str,
-    moves: &[ChessMove],
+    moves: &[M],
     result: &str,
     tags: &BTreeMap<String, String>,
-) -> Result<String, String> {
+) -> Result<String, ChessError> {
     let mut output = String::new();
     write_tag(&mut output, tags, "Event");
     write_tag(&mut output, tags, "Site");
@@ -77,6 +174,11 @@ pub fn export_pgn_with_tags(
     write_tag(&mut output, tags, "White");
     write_tag(&mut output, tags, "Black");
     write_tag(&mut output, tags, "Result");
+    let chess960 = Position::from_fen(start_fen.trim()).is_ok_and(|position| position.is_chess960());
+    if chess960 {
+        let variant = tags.get("Variant").map(String::as_str).unwrap_or("Chess960");
+        output.push_str(&format!("[Variant \"{}\"]\n", escape_tag_value(variant)));
+    }
     if start_fen.trim() != STARTPOS_FEN {
         write_tag(&mut output, tags, "SetUp");
         write_tag(&mut output, tags, "FEN");
@@ -85,7 +187,8 @@ pub fn export_pgn_with_tags(
         if matches!(
             name.as_str(),
             "Event" | "Site" | "Date" | "Round" | "White" | "Black" | "Result" | "SetUp" | "FEN"
-        ) {
+        ) || (chess960 && name == "Variant")
+        {
             continue;
         }
         output.push_str(&format!("[{name} \"{}\"]\n", escape_tag_value(value)));
@@ -98,12 +201,12 @@ pub fn export_pgn_with_tags(
     Ok(output)
 }
 
-pub fn export_move_text(start_fen: &str, moves: &[ChessMove], result: &str) -> Result<String, String> {
+pub fn export_move_text<M: Copy + Into<ChessMove>>(start_fen: &str, moves: &[M], result: &str) -> Result<String, ChessError> {
     let mut position = Position::from_fen(start_fen.trim())?;
     let mut move_number = start_fullmove_number(start_fen);
     let mut tokens = Vec::new();
 
-    for chess_move in moves {
+    for chess_move in moves.iter().map(|chess_move| (*chess_move).into()) {
         let side = position.side_to_move();
         if side == Color::White {
             tokens.push(format!("{move_number}."));
@@ -111,8 +214,8 @@ pub fn export_move_text(start_fen: &str, moves: &[ChessMove], result: &str) -> R
             tokens.push(format!("{move_number}..."));
         }
 
-        let san = move_to_san(&position, *chess_move)?;
-        position.make_legal_move(*chess_move)?;
+        let san = move_to_san(&position, chess_move)?;
+        position.make_legal_move(chess_move)?;
         tokens.push(san);
 
         if side == Color::Black {
@@ -124,36 +227,39 @@ pub fn export_move_text(start_fen: &str, moves: &[ChessMove], result: &str) -> R
     Ok(tokens.join(" "))
 }
 
-pub fn position_after_moves(start_fen: &str, moves: &[ChessMove]) -> Result<Position, String> {
+pub fn position_after_moves<M: Copy + Into<ChessMove>>(start_fen: &str, moves: &[M]) -> Result<Position, ChessError> {
     let mut position = Position::from_fen(start_fen.trim())?;
     for chess_move in moves {
-        position.make_legal_move(*chess_move)?;
+        position.make_legal_move((*chess_move).into())?;
     }
     Ok(position)
 }
 
-pub fn moves_to_san(start_fen: &str, moves: &[ChessMove]) -> Result<Vec<String>, String> {
+pub fn moves_to_san<M: Copy + Into<ChessMove>>(start_fen: &str, moves: &[M]) -> Result<Vec<String>, ChessError> {
     let mut position = Position::from_fen(start_fen.trim())?;
     let mut result = Vec::with_capacity(moves.len());
-    for chess_move in moves {
-        let san = move_to_san(&position, *chess_move)?;
-        position.make_legal_move(*chess_move)?;
+    for chess_move in moves.iter().map(|chess_move| (*chess_move).into()) {
+        let san = move_to_san(&position, chess_move)?;
+        position.make_legal_move(chess_move)?;
         result.push(san);
     }
     Ok(result)
 }
 
-pub fn move_to_san(position: &Position, chess_move: ChessMove) -> Result<String, String> {
-    let piece = position
-        .piece_at(chess_move.from)
-        .ok_or_else(|| format!("no piece on {}", square_name(chess_move.from)))?;
+/// Accepts a `ChessMove` or a `PackedMove`.
+pub fn move_to_san(position: &Position, chess_move: impl Into<ChessMove>) -> Result<String, MoveError> {
+    let chess_move = chess_move.into();
+    let piece = position.piece_at(chess_move.from).ok_or_else(|| MoveError::NoPiece {
+        square: square_name(chess_move.from),
+        fen: position.to_fen(),
+    })?;
+    let mut next = position.clone();
+    next.make_legal_move(chess_move)?;
 
-    let is_castling = piece.kind == PieceKind::King && (file_of(chess_move.from) - file_of(chess_move.to)).abs() == 2;
-    let mut san = if is_castling {
-        if file_of(chess_move.to) == 6 {
-            "O-O".to_string()
-        } else {
-            "O-O-O".to_string()
+    let mut san = if let Some(side) = position.castling_side(chess_move) {
+        match side {
+            CastlingSide::King => "O-O".to_string(),
+            CastlingSide::Queen => "O-O-O".to_string(),
         }
     } else {
         let mut value = String::new();
@@ -168,15 +274,13 @@ pub fn move_to_san(position: &Position, chess_move: ChessMove) -> Result<String,
             value.push('x');
         }
         value.push_str(&square_name(chess_move.to));
-        if let Some(promotion) = chess_move.promotion {
+        if let Some(letter) = chess_move.promotion.and_then(san_piece_letter) {
             value.push('=');
-            value.push(san_piece_letter(promotion).ok_or_else(|| "bad promotion piece".to_string())?);
+            value.push(letter);
         }
         value
     };
 
-    let mut next = position.clone();
-    next.make_legal_move(chess_move)?;
     if next.is_checkmate() {
         san.push('#');
     } else if next.is_in_check(next.side_to_move()) {
@@ -185,7 +289,12 @@ pub fn move_to_san(position: &Position, chess_move: ChessMove) -> Result<String,
     Ok(san)
 }
 
-fn parse_move_token(position: &Position, token: &str) -> Result<ChessMove, String> {
+/// Reads a SAN (or UCI) move of `position`, with its kind flags.
+pub fn parse_san(position: &Position, san: &str) -> Result<PackedMove, PgnErrorKind> {
+    parse_move_token(position, san).map(|chess_move| position.pack(chess_move))
+}
+
+fn parse_move_token(position: &Position, token: &str) -> Result<ChessMove, PgnErrorKind> {
     if let Some(chess_move) = position.parse_uci_move(token) {
         return Ok(chess_move);
     }
@@ -193,16 +302,89 @@ fn parse_move_token(position: &Position, token: &str) -> Result<ChessMove, Strin
     let normalized = normalize_san(token);
     let mut matches = Vec::new();
     for chess_move in position.legal_moves() {
-        let san = move_to_san(position, chess_move)?;
+        let san = move_to_san(position, chess_move).map_err(PgnErrorKind::IllegalMove)?;
         if normalize_san(&san) == normalized {
             matches.push(chess_move);
         }
     }
 
-    match matches.len() {
-        1 => Ok(matches[0]),
-        0 => Err(format!("cannot parse PGN move `{token}` in position {}", position.to_fen())),
-        _ => Err(format!("ambiguous PGN move `{token}` in position {}", position.to_fen())),
+    if let [chess_move] = matches[..] {
+        return Ok(chess_move);
+    }
+
+    // Not the exact SAN: match piece, target square and promotion, and use
+    // the from-square hint only to narrow the candidates down.
+    let Some(loose) = LooseSan::parse(&normalized) else {
+        return Err(PgnErrorKind::UnknownMove { fen: position.to_fen() });
+    };
+    let candidates: Vec<ChessMove> = position
+        .legal_moves()
+        .into_iter()
+        .filter(|chess_move| loose.matches(position, *chess_move))
+        .collect();
+    match candidates[..] {
+        [chess_move] => Ok(chess_move),
+        [] => Err(PgnErrorKind::UnknownMove { fen: position.to_fen() }),
+        _ => Err(PgnErrorKind::AmbiguousMove { fen: position.to_fen() }),
+    }
+}
+
+/// The parts of a SAN piece or pawn move that do not depend on how it was
+/// disambiguated: `Nbd2`, `N1d2`, `Nd2` and `Nxd2` all read as a knight to
+/// d2, with the file or rank hint kept apart.
+struct LooseSan {
+    kind: PieceKind,
+    from_file: Option<i32>,
+    from_rank: Option<i32>,
+    to: u8,
+    promotion: Option<PieceKind>,
+}
+
+impl LooseSan {
+    fn parse(san: &str) -> Option<Self> {
+        let (body, promotion) = match san.split_once('=') {
+            Some((body, piece)) => (body, Some(promotion_kind(piece)?)),
+            None => (san, None),
+        };
+        let (kind, rest) = match body.chars().next()? {
+            'N' => (PieceKind::Knight, &body[1..]),
+            'B' => (PieceKind::Bishop, &body[1..]),
+            'R' => (PieceKind::Rook, &body[1..]),
+            'Q' => (PieceKind::Queen, &body[1..]),
+            'K' => (PieceKind::King, &body[1..]),
+            _ => (PieceKind::Pawn, body),
+        };
+        let target_at = rest.len().checked_sub(2)?;
+        let to = parse_square(rest.get(target_at..)?)?;
+        let mut from_file = None;
+        let mut from_rank = None;
+        for ch in rest[..target_at].chars() {
+            match ch {
+                'a'..='h' if from_file.is_none() => from_file = Some(ch as i32 - 'a' as i32),
+                '1'..='8' if from_rank.is_none() => from_rank = Some(ch as i32 - '1' as i32),
+                'x' => {}
+                _ => return None,
+            }
+        }
+        Some(Self { kind, from_file, from_rank, to, promotion })
+    }
+
+    fn matches(&self, position: &Position, chess_move: ChessMove) -> bool {
+        chess_move.to == self.to
+            && chess_move.promotion == self.promotion
+            && position.piece_at(chess_move.from).is_some_and(|piece| piece.kind == self.kind)
+            && self.from_file.is_none_or(|file| file_of(chess_move.from) == file)
+            && self.from_rank.is_none_or(|rank| rank_of(chess_move.from) == rank)
+    }
+}
+
+fn promotion_kind(piece: &str) -> Option<PieceKind> {
+    match piece {
+        "N" => Some(PieceKind::Knight),
+        "B" => Some(PieceKind::Bishop),
+        "R" => Some(PieceKind::Rook),
+        "Q" => Some(PieceKind::Queen),
+        _ => None,
     }
 }
 
@@ -284,6 +466,29 @@ fn parse_tags(text: &str) -> BTreeMap<String, String> {
     tags
 }
 
+fn split_games(text: &str) -> Vec<String> {
+    let mut games = Vec::new();
+    let mut current = String::new();
+    let mut has_moves = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let is_tag = trimmed.starts_with('[') && trimmed.ends_with(']');
+        if is_tag && has_moves {
+            games.push(std::mem::take(&mut current));
+            has_moves = false;
+        }
+        if !is_tag && !trimmed.is_empty() {
+            has_moves = true;
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.trim().is_empty() {
+        games.push(current);
+    }
+    games
+}
+
 fn strip_tag_lines(text: &str) -> String {
     let mut body = String::new();
     for line in text.lines() {
@@ -488,7 +693,7 @@ mod tests {
 
         let pgn = format!("[SetUp \"1\"]\n[FEN \"{start_fen}\"]\n\n1. Nbd2 *");
         let game = parse_pgn(&pgn).unwrap();
-        assert_eq!(game.moves, vec![chess_move]);
+        assert_eq!(game.chess_moves(), vec![chess_move]);
     }
 
     #[test]
@@ -500,7 +705,7 @@ mod tests {
 
         let pgn = format!("[SetUp \"1\"]\n[FEN \"{start_fen}\"]\n\n1. R1a2 *");
         let game = parse_pgn(&pgn).unwrap();
-        assert_eq!(game.moves, vec![chess_move]);
+        assert_eq!(game.chess_moves(), vec![chess_move]);
     }
 
     #[test]
@@ -508,7 +713,33 @@ mod tests {
         let start_fen = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
         let pgn = format!("[SetUp \"1\"]\n[FEN \"{start_fen}\"]\n\n1. Nd2 *");
         let error = parse_pgn(&pgn).unwrap_err();
-        assert!(error.contains("cannot parse PGN move") || error.contains("ambiguous PGN move"));
+        assert_eq!(error.ply, 0);
+        assert_eq!(error.token, "Nd2");
+        assert_eq!(error.kind, PgnErrorKind::AmbiguousMove { fen: start_fen.to_string() });
+        assert!(error.to_string().starts_with("game 1, ply 1: ambiguous PGN move `Nd2`"));
+
+        let position = Position::from_fen(start_fen).unwrap();
+        let from_f1 = position.parse_uci_move("f1d2").unwrap();
+        assert_eq!(parse_san(&position, "Nf1d2").unwrap(), position.pack(from_f1));
+        assert_eq!(parse_san(&position, "Nf1xd2").unwrap(), position.pack(from_f1));
+        assert!(matches!(parse_san(&position, "Nc3d2"), Err(PgnErrorKind::UnknownMove { .. })));
+        assert!(matches!(parse_san(&position, "Nd3"), Err(PgnErrorKind::UnknownMove { .. })));
+    }
+
+    #[test]
+    fn multi_game_errors_report_game_index_ply_and_token() {
+        let text = "[Event \"a\"]\n\n1. e4 e5 1-0\n\n[Event \"b\"]\n\n1. d4 d5 2. Qxd5 *\n";
+        let error = parse_pgn_games(text).unwrap_err();
+        assert_eq!((error.game_index, error.ply, error.token.as_str()), (1, 2, "Qxd5"));
+        assert!(matches!(error.kind, PgnErrorKind::UnknownMove { .. }));
+
+        let games = parse_pgn_games("1. e4 e5 1-0\n[Event \"b\"]\n1. d4 *").unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].tags.get("Event").map(String::as_str), Some("b"));
+        assert_eq!(games[1].result, "*");
+
+        let error = parse_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n*").unwrap_err();
+        assert_eq!(error.kind, PgnErrorKind::BadFen(FenError::MissingKing { color: Color::White }));
     }
 
     #[test]
@@ -517,4 +748,28 @@ mod tests {
         let san = moves_to_san(&game.start_fen, &game.moves).unwrap();
         assert_eq!(san, vec!["e4", "e5", "Nf3", "Nc6"]);
     }
+
+    #[test]
+    fn chess960_games_round_trip_with_variant_tag() {
+        let mut position = Position::chess960(518).unwrap();
+        let start_fen = position.to_fen();
+        let mut moves = Vec::new();
+        for token in ["g1f3", "g8f6", "e2e3", "e7e6", "f1e2", "f8e7", "e1h1"] {
+            let chess_move = position.parse_uci_move(token).unwrap();
+            position.make_legal_move(chess_move).unwrap();
+            moves.push(chess_move);
+        }
+        let pgn = export_pgn(&start_fen, &moves, "*").unwrap();
+        assert!(pgn.contains("[Variant \"Chess960\"]"));
+        assert!(pgn.contains(&format!("[FEN \"{STARTPOS_FEN}\"]")));
+        assert!(pgn.contains("4. O-O *"));
+
+        let game = parse_pgn(&pgn).unwrap();
+        assert_eq!(game.start_fen, start_fen);
+        assert_eq!(game.chess_moves(), moves);
+        assert_eq!(game.moves.last().and_then(|packed| packed.castling_side()), Some(CastlingSide::King));
+        let before_castling = position_after_moves(&game.start_fen, &game.moves[..6]).unwrap();
+        assert_eq!(parse_san(&before_castling, "O-O").unwrap(), game.moves[6]);
+        assert_eq!(move_to_san(&before_castling, game.moves[6]).unwrap(), "O-O");
+    }
 }
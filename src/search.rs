@@ -1,22 +1,148 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::bitboard::{king_attacks, square_bit, squares};
 use crate::chess::{file_of, index, rank_of, ChessMove, Color, Piece, PieceKind, Position};
+use crate::packed_move::PackedMove;
+use crate::search_method::{
+    search_method, MethodOptions, SearchInput, SearchLimits, SearchMethod, SearchOutput, SearchStats,
+};
+use crate::time_manager::SearchDeadlines;
 
 const INFINITY: i32 = 1_000_000;
-const MATE_SCORE: i32 = 900_000;
+pub(crate) const MATE_SCORE: i32 = 900_000;
 const TT_SCORE_BIAS: i32 = 1_050_000;
 const TT_SCORE_BITS: u64 = (1 << 22) - 1;
 const TT_EXACT: u8 = 0;
 const TT_LOWER: u8 = 1;
 const TT_UPPER: u8 = 2;
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+const ASPIRATION_WINDOW: i32 = 30;
+/// History scores stay inside ±`HISTORY_MAX`.
+const HISTORY_MAX: i32 = 16_384;
+/// Ordering keys of quiet moves, just below a pawn taking a pawn (900).
+const FIRST_KILLER_ORDER: i32 = 850;
+const SECOND_KILLER_ORDER: i32 = 800;
+const COUNTER_MOVE_ORDER: i32 = 750;
+/// Selective search margins, in centipawns per ply of remaining depth.
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+const FUTILITY_MARGIN: i32 = 150;
+const RAZOR_MARGIN: i32 = 250;
+/// What a capture may gain beyond the captured piece, for delta pruning.
+const DELTA_MARGIN: i32 = 200;
+/// Deepest remaining depth at which each pruning applies.
+const REVERSE_FUTILITY_DEPTH: u8 = 3;
+const FUTILITY_DEPTH: u8 = 2;
+const RAZOR_DEPTH: u8 = 2;
+const NULL_MOVE_MIN_DEPTH: u8 = 3;
+const LMR_MIN_DEPTH: u8 = 3;
+/// Moves searched at full depth before late-move reductions start.
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+/// Extension plies one line may use unless `extension_budget` says otherwise.
+const DEFAULT_EXTENSION_BUDGET: u8 = 8;
+/// Nodes between two reads of the stop flag and the clock.
+const STOP_CHECK_NODES: u64 = 1024;
+
+/// Depth limit used when only the clock bounds the search.
+pub const MAX_SEARCH_DEPTH: u8 = 64;
+/// Upper limit of the `MultiPV` option; no position has more legal moves.
+pub const MAX_MULTI_PV: usize = 256;
+/// Upper limit of the `ExtensionBudget` option.
+pub const MAX_EXTENSION_BUDGET: u8 = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RootCandidate {
     pub root_index: usize,
     pub chess_move: ChessMove,
     pub score: i32,
+    /// Expected line, starting with `chess_move`.
+    pub pv: Vec<ChessMove>,
+    /// `false` when `score` is only an upper bound: the move was proven
+    /// worse than the `multi_pv` best moves and not searched further.
+    pub exact: bool,
+}
+
+/// Progress report after one completed iterative-deepening depth.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub seldepth: u8,
+    /// 1 for the best line, 2 for the second best and so on.
+    pub multipv: usize,
+    pub score: i32,
+    pub nodes: u64,
+    /// Permille of the transposition table filled by this search.
+    pub hashfull: usize,
+    pub elapsed: Duration,
+    pub pv: Vec<ChessMove>,
+}
+
+impl SearchInfo {
+    pub fn nodes_per_second(&self) -> u64 {
+        let micros = self.elapsed.as_micros().max(1);
+        (self.nodes as u128 * 1_000_000 / micros) as u64
+    }
+}
+
+/// Stop flag and deadlines shared by a running search and the thread that
+/// controls it. Deadlines count from `new` or from the last `start_clock`.
+#[derive(Debug)]
+pub struct SearchControl {
+    started: Instant,
+    stop: AtomicBool,
+    soft_ms: AtomicU64,
+    hard_ms: AtomicU64,
+}
+
+impl SearchControl {
+    pub fn new(deadlines: Option<SearchDeadlines>) -> Self {
+        let control = Self {
+            started: Instant::now(),
+            stop: AtomicBool::new(false),
+            soft_ms: AtomicU64::new(u64::MAX),
+            hard_ms: AtomicU64::new(u64::MAX),
+        };
+        control.start_clock(deadlines);
+        control
+    }
+
+    /// Makes the search return after its last completed depth.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Replaces the deadlines with ones counted from now, as on `ponderhit`.
+    pub fn start_clock(&self, deadlines: Option<SearchDeadlines>) {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        let (soft, hard) = deadlines.map_or((u64::MAX, u64::MAX), |deadlines| {
+            (elapsed + deadlines.soft.as_millis() as u64, elapsed + deadlines.hard.as_millis() as u64)
+        });
+        self.soft_ms.store(soft, Ordering::Relaxed);
+        self.hard_ms.store(hard, Ordering::Relaxed);
+    }
+
+    fn has_deadlines(&self) -> bool {
+        self.hard_ms.load(Ordering::Relaxed) != u64::MAX
+    }
+
+    fn past(&self, deadline: &AtomicU64) -> bool {
+        self.started.elapsed().as_millis() as u64 >= deadline.load(Ordering::Relaxed)
+    }
+
+    /// Whether the running depth has to be abandoned.
+    fn should_abort(&self) -> bool {
+        self.is_stopped() || self.past(&self.hard_ms)
+    }
+
+    /// Whether a new depth may still be started.
+    pub(crate) fn may_deepen(&self) -> bool {
+        !self.is_stopped() && !self.past(&self.soft_ms)
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -29,6 +155,55 @@ pub struct SearchSettings {
     pub draw_contempt_cp: i32,
     pub risk_level: i32,
     pub humanity_level: i32,
+    /// How many of the best root moves get an exact score.
+    pub multi_pv: usize,
+    /// Two quiet moves per ply that caused a beta cutoff.
+    pub killer_moves: bool,
+    /// Butterfly history of quiet moves, by side, from and to square.
+    pub history_heuristic: bool,
+    /// The quiet move that last refuted each previous move.
+    pub counter_moves: bool,
+    /// Search every move after the first with a null window first and
+    /// again with the full window only if it raises alpha. Most selective
+    /// search below only runs at such null-window nodes.
+    pub principal_variation_search: bool,
+    /// Pass the move at non-PV nodes and cut off if the reduced search
+    /// still fails high.
+    pub null_move_pruning: bool,
+    /// Search late quiet moves shallower, then again at full depth if they
+    /// raise alpha.
+    pub late_move_reductions: bool,
+    /// Cut off near the leaves when the static eval is far above beta.
+    pub reverse_futility_pruning: bool,
+    /// Skip quiet moves near the leaves when the static eval is far below
+    /// alpha.
+    pub futility_pruning: bool,
+    /// Drop into quiescence near the leaves when the static eval is far
+    /// below alpha.
+    pub razoring: bool,
+    /// Search a move that gives check one ply deeper.
+    pub check_extensions: bool,
+    /// Search the only legal move one ply deeper.
+    pub one_reply_extensions: bool,
+    /// Search a capture back on the square of the previous capture one ply
+    /// deeper.
+    pub recapture_extensions: bool,
+    /// Search a passed pawn push to the seventh rank one ply deeper.
+    pub passed_pawn_extensions: bool,
+    /// How many plies of extensions one line from the root may use.
+    pub extension_budget: u8,
+    /// Skip quiescence captures that lose material by static exchange.
+    pub quiescence_see_pruning: bool,
+    /// Skip quiescence captures that cannot bring the score back up to
+    /// alpha even if the captured piece comes for free.
+    pub delta_pruning: bool,
+    /// Search non-capturing queen promotions in quiescence.
+    pub quiescence_promotions: bool,
+    /// Search quiet checking moves at the first quiescence ply.
+    pub quiescence_checks: bool,
+    /// Search every evasion, without standing pat, when quiescence is in
+    /// check.
+    pub quiescence_evasions: bool,
 }
 
 impl Default for SearchSettings {
@@ -45,6 +220,26 @@ impl Default for SearchSettings {
             draw_contempt_cp: 35,
             risk_level: 0,
             humanity_level: 0,
+            multi_pv: 1,
+            killer_moves: true,
+            history_heuristic: true,
+            counter_moves: true,
+            principal_variation_search: true,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            reverse_futility_pruning: true,
+            futility_pruning: true,
+            razoring: true,
+            check_extensions: false,
+            one_reply_extensions: false,
+            recapture_extensions: false,
+            passed_pawn_extensions: false,
+            extension_budget: DEFAULT_EXTENSION_BUDGET,
+            quiescence_see_pruning: true,
+            delta_pruning: true,
+            quiescence_promotions: true,
+            quiescence_checks: false,
+            quiescence_evasions: true,
         }
     }
 }
@@ -57,6 +252,8 @@ impl SearchSettings {
         self.draw_contempt_cp = self.draw_contempt_cp.clamp(0, 400);
         self.risk_level = self.risk_level.clamp(-100, 100);
         self.humanity_level = self.humanity_level.clamp(-100, 100);
+        self.multi_pv = self.multi_pv.clamp(1, MAX_MULTI_PV);
+        self.extension_budget = self.extension_budget.min(MAX_EXTENSION_BUDGET);
         self
     }
 }
@@ -66,7 +263,17 @@ pub struct Engine {
     max_depth: u8,
     searched_nodes: u64,
     settings: SearchSettings,
+    control: Arc<SearchControl>,
+    /// Every root move gets an exact score, whatever `multi_pv` says.
+    exact_root_scores: bool,
     tt: Arc<TranspositionTable>,
+    /// Keys of the game positions since the last capture or pawn move,
+    /// ending with the position to search.
+    game_keys: Vec<u64>,
+    method: &'static dyn SearchMethod,
+    method_options: MethodOptions,
+    /// `info string` lines the method left about the latest search.
+    search_notes: Vec<String>,
 }
 
 impl Engine {
@@ -75,15 +282,63 @@ impl Engine {
         Self {
             max_depth: max_depth.max(1),
             searched_nodes: 0,
+            control: Arc::new(SearchControl::new(None)),
+            exact_root_scores: false,
             tt: Arc::new(TranspositionTable::new(settings.hash_mb)),
+            game_keys: Vec::new(),
+            method: &AlphaBeta,
+            method_options: MethodOptions::default(),
+            search_notes: Vec::new(),
             settings,
         }
     }
 
+    pub fn search_method(&self) -> &'static dyn SearchMethod {
+        self.method
+    }
+
+    /// Selects a registered search method by name, ignoring case. Returns
+    /// `false` and keeps the current one if there is none by that name.
+    pub fn set_search_method(&mut self, name: &str) -> bool {
+        let Some(method) = search_method(name) else {
+            return false;
+        };
+        self.method = method;
+        true
+    }
+
+    /// Positions the game went through before the one to search, as
+    /// returned by `Game::keys_since_irreversible`. A line that goes back to
+    /// one of them, or to an earlier position of the same line, is scored as
+    /// a draw. Ignored when the last key is not the searched position.
+    pub fn set_game_history(&mut self, keys: &[u64]) {
+        self.game_keys = keys.to_vec();
+    }
+
+    fn game_keys(&self, position: &Position) -> Vec<u64> {
+        let key = position.zobrist_key();
+        if self.game_keys.last() == Some(&key) {
+            self.game_keys.clone()
+        } else {
+            vec![key]
+        }
+    }
+
     pub fn set_depth(&mut self, max_depth: u8) {
         self.max_depth = max_depth.max(1);
     }
 
+    /// Time limits counted from this call; `None` searches to the depth
+    /// limit. Depth 1 always completes so that there is a move to return.
+    pub fn set_deadlines(&mut self, deadlines: Option<SearchDeadlines>) {
+        self.control = Arc::new(SearchControl::new(deadlines));
+    }
+
+    /// Hands the search a control that another thread can stop or re-time.
+    pub fn set_control(&mut self, control: Arc<SearchControl>) {
+        self.control = control;
+    }
+
     pub fn settings(&self) -> SearchSettings {
         self.settings
     }
@@ -147,6 +402,142 @@ impl Engine {
         self.set_settings(settings);
     }
 
+    pub fn set_multi_pv(&mut self, value: usize) {
+        let mut settings = self.settings;
+        settings.multi_pv = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_killer_moves(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.killer_moves = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_history_heuristic(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.history_heuristic = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_counter_moves(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.counter_moves = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_principal_variation_search(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.principal_variation_search = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_null_move_pruning(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.null_move_pruning = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_late_move_reductions(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.late_move_reductions = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_reverse_futility_pruning(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.reverse_futility_pruning = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_futility_pruning(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.futility_pruning = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_razoring(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.razoring = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_check_extensions(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.check_extensions = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_one_reply_extensions(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.one_reply_extensions = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_recapture_extensions(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.recapture_extensions = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_passed_pawn_extensions(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.passed_pawn_extensions = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_extension_budget(&mut self, plies: u8) {
+        let mut settings = self.settings;
+        settings.extension_budget = plies;
+        self.set_settings(settings);
+    }
+
+    pub fn set_quiescence_see_pruning(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.quiescence_see_pruning = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_delta_pruning(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.delta_pruning = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_quiescence_promotions(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.quiescence_promotions = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_quiescence_checks(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.quiescence_checks = value;
+        self.set_settings(settings);
+    }
+
+    pub fn set_quiescence_evasions(&mut self, value: bool) {
+        let mut settings = self.settings;
+        settings.quiescence_evasions = value;
+        self.set_settings(settings);
+    }
+
+    pub fn method_options(&self) -> &MethodOptions {
+        &self.method_options
+    }
+
+    /// Sets an option that one of the registered search methods declares.
+    /// Returns `false` if none does or the value is not allowed.
+    pub fn set_method_option(&mut self, name: &str, value: &str) -> bool {
+        self.method_options.set(name, value)
+    }
+
+    /// For callers that pick among all root moves by score, such as the
+    /// experience book.
+    pub fn set_exact_root_scores(&mut self, value: bool) {
+        self.exact_root_scores = value;
+    }
+
     pub fn searched_nodes(&self) -> u64 {
         self.searched_nodes
     }
@@ -155,6 +546,22 @@ impl Engine {
         self.tt.len()
     }
 
+    /// Permille of the transposition table written by the latest search, as
+    /// UCI `hashfull` expects.
+    pub fn hashfull(&self) -> usize {
+        self.tt.hashfull()
+    }
+
+    /// Empties the transposition table, which clones of this engine share.
+    pub fn clear_hash(&mut self) {
+        self.tt.clear();
+    }
+
+    /// Best move stored for `position` by earlier searches, if it is legal here.
+    pub fn hash_move(&self, position: &Position) -> Option<PackedMove> {
+        table_move(&self.tt, position)
+    }
+
     pub fn best_move(&mut self, position: &Position) -> Option<ChessMove> {
         self.best_move_with_score(position).map(|(chess_move, _score)| chess_move)
     }
@@ -167,103 +574,395 @@ impl Engine {
     }
 
     pub fn root_candidates(&mut self, position: &Position) -> Vec<RootCandidate> {
-        self.searched_nodes = 0;
+        self.root_candidates_with_info(position, |_| {})
+    }
+
+    /// Runs the selected search method on `position`, within the depth
+    /// limit and the control of this engine.
+    pub fn root_candidates_with_info(
+        &mut self,
+        position: &Position,
+        mut report: impl FnMut(&SearchInfo),
+    ) -> Vec<RootCandidate> {
+        let history = self.game_keys(position);
+        let input = SearchInput {
+            position,
+            history: &history,
+            limits: SearchLimits { max_depth: self.max_depth, control: self.control.clone() },
+            settings: self.settings,
+            exact_root_scores: self.exact_root_scores,
+            options: &self.method_options,
+            tt: self.tt.clone(),
+        };
+        let output = self.method.search(&input, &mut report);
+        self.searched_nodes = output.stats.nodes;
+        self.search_notes = output.notes;
+        output.candidates
+    }
+
+    pub fn search_notes(&self) -> &[String] {
+        &self.search_notes
+    }
+}
+
+/// The iterative-deepening alpha-beta search: negamax with a shared
+/// transposition table, selective search and quiescence, split over root
+/// moves when `deterministic_multithread` is on.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AlphaBeta;
+
+impl SearchMethod for AlphaBeta {
+    fn name(&self) -> &'static str {
+        "AlphaBeta"
+    }
+
+    fn description(&self) -> &'static str {
+        "Iterative-deepening negamax with a transposition table, pruning, extensions and quiescence"
+    }
+
+    fn search(&self, input: &SearchInput, report: &mut dyn FnMut(&SearchInfo)) -> SearchOutput {
+        AlphaBetaSearch { input, searched_nodes: 0 }.root_candidates(report)
+    }
+}
+
+/// One `AlphaBeta` search: its input and the nodes counted so far.
+struct AlphaBetaSearch<'a> {
+    input: &'a SearchInput<'a>,
+    searched_nodes: u64,
+}
+
+impl AlphaBetaSearch<'_> {
+    /// Iterative deepening from depth 1 to the configured depth. Each depth
+    /// searches the root moves in the order the previous depth ranked them,
+    /// and `report` is called once the depth is complete, for each of the
+    /// first `multi_pv` lines.
+    ///
+    /// Only the `multi_pv` best moves of the previous depth are searched with
+    /// a full window. The others are first tested with a null window at the
+    /// score of the last of them and keep that upper bound when they fail
+    /// low; a move that does not fail low, or whose bound still reaches the
+    /// top `multi_pv` once the depth is complete, is searched exactly.
+    fn root_candidates(&mut self, report: &mut dyn FnMut(&SearchInfo)) -> SearchOutput {
+        let position = self.input.position;
+        let settings = self.input.settings;
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
         let mut moves = position.legal_moves();
         if moves.is_empty() {
-            return Vec::new();
+            return SearchOutput { candidates: Vec::new(), stats, notes: Vec::new() };
+        }
+        order_moves_with_hash_move(position, &mut moves, table_move(&self.input.tt, position));
+        let age = self.input.tt.next_age();
+        let split = settings.deterministic_multithread
+            && settings.max_threads > 1
+            && moves.len() >= settings.granularity.max(1) * 2;
+
+        let exact_moves = self.exact_root_moves();
+        let mut jobs: Vec<RootJob> = moves
+            .into_iter()
+            .enumerate()
+            .map(|(root_index, chess_move)| RootJob {
+                root_index,
+                chess_move,
+                adjustment: root_score_adjustment(position, chess_move, settings),
+                previous_score: None,
+                floor: None,
+            })
+            .collect();
+        let mut candidates = Vec::new();
+        let control = &self.input.limits.control;
+        for depth in 1..=self.input.limits.max_depth {
+            let depth_control = (depth > 1).then(|| control.clone());
+            let mut iteration = self.search_root(&jobs, depth - 1, age, depth_control.clone(), split);
+            while !iteration.stopped {
+                let retry = bounds_reaching_top(&iteration.results, exact_moves);
+                if retry.is_empty() {
+                    break;
+                }
+                let exact = self.search_root(&retry, depth - 1, age, depth_control.clone(), split);
+                iteration.merge(exact);
+            }
+            self.searched_nodes += iteration.nodes;
+            stats.nodes = self.searched_nodes;
+            if iteration.stopped {
+                break;
+            }
+            stats.depth = depth;
+            stats.seldepth = stats.seldepth.max(iteration.seldepth.max(depth));
+            let results = iteration.results;
+
+            candidates = results
+                .iter()
+                .map(|result| RootCandidate {
+                    root_index: result.job.root_index,
+                    chess_move: result.job.chess_move,
+                    score: result.score(),
+                    pv: self.complete_pv(position, &result.pv, depth),
+                    exact: result.exact,
+                })
+                .collect();
+            sort_root_candidates(&mut candidates);
+            let floor = candidates
+                .get(exact_moves.saturating_sub(1))
+                .map(|candidate| candidate.score)
+                .filter(|score| !score_is_mate(*score));
+            jobs = candidates
+                .iter()
+                .enumerate()
+                .map(|(rank, candidate)| {
+                    let result = results
+                        .iter()
+                        .find(|result| result.job.root_index == candidate.root_index)
+                        .expect("every root move has a result");
+                    RootJob {
+                        previous_score: Some(result.raw_score),
+                        floor: floor.filter(|_| rank >= exact_moves).map(|score| score - result.job.adjustment),
+                        ..result.job
+                    }
+                })
+                .collect();
+
+            for (index, candidate) in candidates.iter().take(settings.multi_pv).enumerate() {
+                report(&SearchInfo {
+                    depth,
+                    seldepth: iteration.seldepth.max(depth),
+                    multipv: index + 1,
+                    score: candidate.score,
+                    nodes: self.searched_nodes,
+                    hashfull: self.input.tt.hashfull(),
+                    elapsed: started.elapsed(),
+                    pv: candidate.pv.clone(),
+                });
+            }
+            if !control.may_deepen() || (jobs.len() == 1 && control.has_deadlines()) {
+                break;
+            }
         }
-        order_moves(position, &mut moves);
-        let age = self.tt.next_age();
 
-        let mut candidates = if self.settings.deterministic_multithread
-            && self.settings.max_threads > 1
-            && moves.len() >= self.settings.granularity.max(1) * 2
-        {
-            self.root_candidates_split(position, moves, age)
-        } else {
-            self.root_candidates_single_thread(position, moves, age)
-        };
-        sort_root_candidates(&mut candidates);
-        apply_humanity_to_candidates(position, &mut candidates, self.settings);
+        apply_humanity_to_candidates(position, &mut candidates, settings);
         sort_root_candidates(&mut candidates);
-        candidates
+        stats.elapsed = started.elapsed();
+        SearchOutput { candidates, stats, notes: Vec::new() }
     }
 
-    fn root_candidates_single_thread(&mut self, position: &Position, moves: Vec<ChessMove>, age: u8) -> Vec<RootCandidate> {
-        let mut worker = SearchWorker::new(self.tt.clone(), age, self.settings);
-        let mut candidates = Vec::with_capacity(moves.len());
-        let depth = self.max_depth.saturating_sub(1);
-        for (root_index, chess_move) in moves.into_iter().enumerate() {
-            let mut next = position.clone();
-            if next.apply_unchecked(chess_move).is_err() {
-                continue;
-            }
-            let raw_score = -worker.negamax(&next, depth, -INFINITY, INFINITY, 1);
-            let score = adjusted_root_score(position, chess_move, raw_score, self.settings);
-            candidates.push(RootCandidate { root_index, chess_move, score });
+    /// Humanity and draw avoidance compare the scores of all root moves, so
+    /// they need every score exact.
+    fn exact_root_moves(&self) -> usize {
+        let settings = self.input.settings;
+        if self.input.exact_root_scores || settings.humanity_level > 0 || settings.avoid_draws {
+            usize::MAX
+        } else {
+            settings.multi_pv
         }
-        self.searched_nodes = worker.searched_nodes;
-        candidates
     }
 
-    fn root_candidates_split(&mut self, position: &Position, moves: Vec<ChessMove>, age: u8) -> Vec<RootCandidate> {
-        let indexed_moves: Vec<(usize, ChessMove)> = moves.iter().copied().enumerate().collect();
-        let granularity = self.settings.granularity.max(1);
-        let tasks: Vec<Vec<(usize, ChessMove)>> = indexed_moves
-            .chunks(granularity)
-            .map(|chunk| chunk.to_vec())
-            .collect();
-        let thread_count = self.settings.max_threads.min(tasks.len()).max(1);
-        let mut all_results: Vec<RootCandidate> = Vec::with_capacity(moves.len());
-        let mut total_nodes = 0_u64;
-        let tt = self.tt.clone();
-        let depth = self.max_depth.saturating_sub(1);
-        let settings = self.settings;
+    fn search_root(
+        &mut self,
+        jobs: &[RootJob],
+        depth: u8,
+        age: u8,
+        control: Option<Arc<SearchControl>>,
+        split: bool,
+    ) -> RootIteration {
+        if split {
+            self.search_root_split(jobs, depth, age, control)
+        } else {
+            self.search_root_single_thread(jobs, depth, age, control)
+        }
+    }
+
+    /// A line cut short by a transposition-table cutoff is continued with
+    /// the best moves stored in the table, up to `max_len` moves and never
+    /// through a repeated position.
+    fn complete_pv(&self, position: &Position, pv: &[ChessMove], max_len: u8) -> Vec<ChessMove> {
+        let mut board = position.clone();
+        let mut seen = vec![board.zobrist_key()];
+        for chess_move in pv {
+            board.make_move(*chess_move);
+            seen.push(board.zobrist_key());
+        }
+        let mut line = pv.to_vec();
+        while line.len() < max_len as usize {
+            let Some(next) = table_move(&self.input.tt, &board) else {
+                break;
+            };
+            board.make_move(next.to_move());
+            if seen.contains(&board.zobrist_key()) {
+                break;
+            }
+            seen.push(board.zobrist_key());
+            line.push(next.to_move());
+        }
+        line
+    }
+
+    fn search_root_single_thread(
+        &mut self,
+        jobs: &[RootJob],
+        depth: u8,
+        age: u8,
+        control: Option<Arc<SearchControl>>,
+    ) -> RootIteration {
+        let mut worker = SearchWorker::new(self.input.tt.clone(), age, self.input.settings);
+        worker.control = control;
+        worker.set_game_keys(self.input.history.to_vec());
+        let mut board = self.input.position.clone();
+        let mut results = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            let (raw_score, pv, exact) = worker.search_root_move(&mut board, job, depth);
+            if worker.stopped {
+                break;
+            }
+            results.push(RootResult { job: *job, raw_score, pv, exact });
+        }
+        RootIteration { results, nodes: worker.searched_nodes, seldepth: worker.seldepth, stopped: worker.stopped }
+    }
+
+    fn search_root_split(
+        &mut self,
+        jobs: &[RootJob],
+        depth: u8,
+        age: u8,
+        control: Option<Arc<SearchControl>>,
+    ) -> RootIteration {
+        let settings = self.input.settings;
+        let granularity = settings.granularity.max(1);
+        let tasks: Vec<&[RootJob]> = jobs.chunks(granularity).collect();
+        let thread_count = settings.max_threads.min(tasks.len()).max(1);
+        let mut iteration = RootIteration { results: Vec::with_capacity(jobs.len()), nodes: 0, seldepth: 0, stopped: false };
+        let game_keys = self.input.history.to_vec();
 
         thread::scope(|scope| {
             let mut handles = Vec::with_capacity(thread_count);
             for thread_id in 0..thread_count {
-                let assigned_tasks: Vec<Vec<(usize, ChessMove)>> = tasks
+                let assigned_tasks: Vec<&[RootJob]> = tasks
                     .iter()
                     .enumerate()
                     .filter(|(task_index, _)| task_index % thread_count == thread_id)
-                    .map(|(_, task)| task.clone())
+                    .map(|(_, task)| *task)
                     .collect();
-                let base_position = position.clone();
-                let tt = tt.clone();
+                let mut board = self.input.position.clone();
+                let tt = self.input.tt.clone();
+                let control = control.clone();
+                let game_keys = game_keys.clone();
                 handles.push(scope.spawn(move || {
                     let mut worker = SearchWorker::new(tt, age, settings);
+                    worker.control = control;
+                    worker.set_game_keys(game_keys);
                     let mut results = Vec::new();
-                    for task in assigned_tasks {
-                        for (root_index, chess_move) in task {
-                            let mut next = base_position.clone();
-                            if next.apply_unchecked(chess_move).is_err() {
-                                continue;
-                            }
-                            let raw_score = -worker.negamax(&next, depth, -INFINITY, INFINITY, 1);
-                            let score = adjusted_root_score(&base_position, chess_move, raw_score, settings);
-                            results.push(RootCandidate { root_index, chess_move, score });
+                    for job in assigned_tasks.into_iter().flatten() {
+                        let (raw_score, pv, exact) = worker.search_root_move(&mut board, job, depth);
+                        if worker.stopped {
+                            break;
                         }
+                        results.push(RootResult { job: *job, raw_score, pv, exact });
                     }
-                    (results, worker.searched_nodes)
+                    (results, worker.searched_nodes, worker.seldepth, worker.stopped)
                 }));
             }
 
             for handle in handles {
-                if let Ok((results, nodes)) = handle.join() {
-                    total_nodes += nodes;
-                    all_results.extend(results);
+                if let Ok((results, nodes, seldepth, stopped)) = handle.join() {
+                    iteration.nodes += nodes;
+                    iteration.seldepth = iteration.seldepth.max(seldepth);
+                    iteration.stopped |= stopped;
+                    iteration.results.extend(results);
                 }
             }
         });
 
-        self.searched_nodes = total_nodes;
-        all_results
+        iteration
+    }
+}
+
+/// A root move with its raw score from the previous depth, which centres the
+/// aspiration window.
+#[derive(Clone, Copy, Debug)]
+struct RootJob {
+    root_index: usize,
+    chess_move: ChessMove,
+    /// Tactical and personality bonus added to the raw score.
+    adjustment: i32,
+    previous_score: Option<i32>,
+    /// Raw score the move has to reach to be searched exactly; `None`
+    /// searches it exactly at once.
+    floor: Option<i32>,
+}
+
+struct RootResult {
+    job: RootJob,
+    raw_score: i32,
+    pv: Vec<ChessMove>,
+    exact: bool,
+}
+
+impl RootResult {
+    fn score(&self) -> i32 {
+        if score_is_mate(self.raw_score) {
+            self.raw_score
+        } else {
+            self.raw_score.saturating_add(self.job.adjustment)
+        }
     }
 }
 
+/// Raw root scores of one depth. A stopped iteration is incomplete and is
+/// thrown away.
+struct RootIteration {
+    results: Vec<RootResult>,
+    nodes: u64,
+    seldepth: u8,
+    stopped: bool,
+}
+
+impl RootIteration {
+    /// Replaces the results of the moves searched again in `other`.
+    fn merge(&mut self, other: RootIteration) {
+        for result in other.results {
+            if let Some(slot) = self.results.iter_mut().find(|slot| slot.job.root_index == result.job.root_index) {
+                *slot = result;
+            }
+        }
+        self.nodes += other.nodes;
+        self.seldepth = self.seldepth.max(other.seldepth);
+        self.stopped |= other.stopped;
+    }
+}
+
+/// Bounded moves that could still be among the `exact_moves` best, ready to
+/// be searched exactly.
+fn bounds_reaching_top(results: &[RootResult], exact_moves: usize) -> Vec<RootJob> {
+    let mut exact_scores: Vec<i32> = results.iter().filter(|result| result.exact).map(RootResult::score).collect();
+    exact_scores.sort_unstable_by(|left, right| right.cmp(left));
+    let floor = exact_scores.get(exact_moves.saturating_sub(1)).copied().unwrap_or(-INFINITY);
+    results
+        .iter()
+        .filter(|result| !result.exact && result.score() >= floor)
+        .map(|result| RootJob { floor: None, ..result.job })
+        .collect()
+}
+
 struct SearchWorker {
     searched_nodes: u64,
+    seldepth: u8,
+    /// `None` while searching depth 1, which is never interrupted.
+    control: Option<Arc<SearchControl>>,
+    stopped: bool,
+    /// Triangular PV table: `pv[ply]` is the best line found from `ply`.
+    pv: Vec<Vec<ChessMove>>,
+    /// `moves[ply]` is the move played at `ply`, the root move at 0, or
+    /// `None` for a null move.
+    moves: Vec<Option<ChessMove>>,
+    /// `capture_squares[ply]` is the target square of the move at `ply` if
+    /// it was a capture.
+    capture_squares: Vec<Option<u8>>,
+    /// Extension plies used on the line from the root to the current node.
+    extensions: u8,
+    /// Keys of the game positions before the root, then `keys[root + ply]`
+    /// for each node on the current line.
+    keys: Vec<u64>,
+    root: usize,
+    ordering: MoveOrdering,
     tt: Arc<TranspositionTable>,
     age: u8,
     settings: SearchSettings,
@@ -271,31 +970,259 @@ struct SearchWorker {
 
 impl SearchWorker {
     fn new(tt: Arc<TranspositionTable>, age: u8, settings: SearchSettings) -> Self {
-        Self { searched_nodes: 0, tt, age, settings: settings.normalized() }
+        Self {
+            searched_nodes: 0,
+            seldepth: 0,
+            control: None,
+            stopped: false,
+            pv: Vec::new(),
+            moves: Vec::new(),
+            capture_squares: Vec::new(),
+            extensions: 0,
+            keys: Vec::new(),
+            root: 0,
+            ordering: MoveOrdering::new(),
+            tt,
+            age,
+            settings: settings.normalized(),
+        }
+    }
+
+    /// Searches one root move inside an aspiration window around its score
+    /// from the previous depth, widening the failing side until the score
+    /// lands inside. Returns the score from the root side's point of view,
+    /// the line starting with the move and whether the score is exact: a
+    /// move with a `floor` that stays below it keeps an upper bound.
+    ///
+    /// Killers, history and counter-moves start empty for every root move,
+    /// so a score does not depend on which moves the worker searched before.
+    fn search_root_move(&mut self, position: &mut Position, job: &RootJob, depth: u8) -> (i32, Vec<ChessMove>, bool) {
+        let chess_move = job.chess_move;
+        self.ordering.clear();
+        self.extensions = 0;
+        self.set_move(0, chess_move);
+        if position.is_capture(chess_move) {
+            self.capture_squares[0] = Some(chess_move.to);
+        }
+        let undo = position.make_move(chess_move);
+        if let Some(floor) = job.floor {
+            let score = -self.negamax(position, depth, -floor, 1 - floor, 1);
+            if self.stopped || score < floor {
+                position.unmake_move(chess_move, undo);
+                let mut pv = vec![chess_move];
+                pv.extend_from_slice(&self.pv[1]);
+                return (score.min(floor - 1), pv, false);
+            }
+        }
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = match job.previous_score {
+            Some(score) if !score_is_mate(score) => (score - delta, score + delta),
+            _ => (-INFINITY, INFINITY),
+        };
+        let score = loop {
+            let score = -self.negamax(position, depth, -beta, -alpha, 1);
+            if self.stopped {
+                break score;
+            }
+            if score <= alpha && alpha > -INFINITY {
+                alpha = score.saturating_sub(delta).max(-INFINITY);
+            } else if score >= beta && beta < INFINITY {
+                beta = score.saturating_add(delta).min(INFINITY);
+            } else {
+                break score;
+            }
+            delta = delta.saturating_mul(2);
+        };
+        position.unmake_move(chess_move, undo);
+        let mut pv = vec![chess_move];
+        pv.extend_from_slice(&self.pv[1]);
+        (score, pv, true)
+    }
+
+    /// `keys` ends with the root position.
+    fn set_game_keys(&mut self, keys: Vec<u64>) {
+        self.root = keys.len().saturating_sub(1);
+        self.keys = keys;
+    }
+
+    /// Records the node at `ply` and tells whether it repeats an earlier
+    /// position of the game or of the line. Only positions since the last
+    /// capture or pawn move, with the same side to move and no null move in
+    /// between, can repeat.
+    fn is_repetition(&mut self, position: &Position, ply: i32) -> bool {
+        let key = position.zobrist_key();
+        let index = self.root + ply as usize;
+        if self.keys.len() <= index {
+            self.keys.resize(index + 1, 0);
+        }
+        self.keys[index] = key;
+        let window = (position.halfmove_clock() as usize).min(index).min(self.plies_since_null_move(ply));
+        (2..=window).step_by(2).any(|back| self.keys[index - back] == key)
+    }
+
+    /// How far back the line goes without crossing a null move.
+    fn plies_since_null_move(&self, ply: i32) -> usize {
+        let ply = ply as usize;
+        (0..ply)
+            .rev()
+            .find(|&earlier| self.moves.get(earlier).is_some_and(Option::is_none))
+            .map_or(usize::MAX, |null_ply| ply - null_ply - 1)
+    }
+
+    fn set_move(&mut self, ply: i32, chess_move: ChessMove) {
+        self.set_move_or_null(ply, Some(chess_move));
+    }
+
+    fn set_move_or_null(&mut self, ply: i32, chess_move: Option<ChessMove>) {
+        let ply = ply as usize;
+        if self.moves.len() <= ply {
+            self.moves.resize(ply + 1, None);
+            self.capture_squares.resize(ply + 1, None);
+        }
+        self.moves[ply] = chess_move;
+        self.capture_squares[ply] = None;
+    }
+
+    /// One ply for a forcing move while the line still has extension budget
+    /// left, otherwise zero. `position` is the one before the move.
+    fn extension(&self, position: &Position, chess_move: ChessMove, move_count: usize, ply: i32) -> u8 {
+        let settings = &self.settings;
+        if self.extensions >= settings.extension_budget {
+            return 0;
+        }
+        let forcing = (settings.one_reply_extensions && move_count == 1)
+            || (settings.check_extensions && position.gives_check(chess_move))
+            || (settings.recapture_extensions
+                && ply > 0
+                && position.is_capture(chess_move)
+                && self.capture_squares.get(ply as usize - 1) == Some(&Some(chess_move.to)))
+            || (settings.passed_pawn_extensions && is_passed_pawn_push_to_seventh(position, chess_move));
+        u8::from(forcing)
+    }
+
+    fn previous_move(&self, ply: i32) -> Option<ChessMove> {
+        let ply = usize::try_from(ply - 1).ok()?;
+        self.moves.get(ply).copied().flatten()
+    }
+
+    /// Static order with the hash move first, then killers and the
+    /// counter-move among the quiet moves. History breaks the ties between
+    /// the remaining quiet moves with the same static score.
+    fn order_moves(&self, position: &Position, moves: &mut [ChessMove], hash_move: Option<PackedMove>, ply: i32) {
+        let side = position.side_to_move();
+        let previous = self.previous_move(ply).filter(|_| self.settings.counter_moves);
+        let killers = if self.settings.killer_moves { self.ordering.killers(ply) } else { [None; 2] };
+        moves.sort_by_cached_key(|chess_move| {
+            let mut key = move_order_score(position, *chess_move);
+            let mut history = 0;
+            if chess_move.promotion.is_none() && !position.is_capture(*chess_move) {
+                if killers[0] == Some(*chess_move) {
+                    key += FIRST_KILLER_ORDER;
+                } else if killers[1] == Some(*chess_move) {
+                    key += SECOND_KILLER_ORDER;
+                } else if previous.is_some_and(|previous| self.ordering.counter_move(previous) == Some(*chess_move)) {
+                    key += COUNTER_MOVE_ORDER;
+                } else if self.settings.history_heuristic {
+                    history = self.ordering.history(side, *chess_move);
+                }
+            }
+            std::cmp::Reverse((key, history))
+        });
+        move_hash_move_first(moves, hash_move);
+    }
+
+    /// A quiet `chess_move` caused a beta cutoff after the quiet moves in
+    /// `tried` had failed to.
+    fn reward_quiet_cutoff(&mut self, position: &Position, chess_move: ChessMove, tried: &[ChessMove], depth: u8, ply: i32) {
+        if self.settings.killer_moves {
+            self.ordering.add_killer(ply, chess_move);
+        }
+        if self.settings.history_heuristic {
+            let bonus = i32::from(depth) * i32::from(depth);
+            let side = position.side_to_move();
+            self.ordering.update_history(side, chess_move, bonus);
+            for failed in tried {
+                self.ordering.update_history(side, *failed, -bonus);
+            }
+        }
+        if self.settings.counter_moves {
+            if let Some(previous) = self.previous_move(ply) {
+                self.ordering.set_counter_move(previous, chess_move);
+            }
+        }
+    }
+
+    /// Empties the line stored for `ply`; every node starts with this.
+    fn clear_pv(&mut self, ply: i32) {
+        let ply = ply as usize;
+        if self.pv.len() < ply + 2 {
+            self.pv.resize(ply + 2, Vec::new());
+        }
+        self.pv[ply].clear();
+    }
+
+    /// `chess_move` raised alpha at `ply`: its line is the move followed by
+    /// the child's line.
+    fn update_pv(&mut self, ply: i32, chess_move: ChessMove) {
+        let (head, tail) = self.pv.split_at_mut(ply as usize + 1);
+        let line = &mut head[ply as usize];
+        line.clear();
+        line.push(chess_move);
+        line.extend_from_slice(&tail[0]);
+    }
+
+    /// Reads the stop flag and the clock every `STOP_CHECK_NODES` nodes. Once
+    /// it returns true, every open node returns at once without touching the
+    /// table.
+    fn out_of_time(&mut self) -> bool {
+        if !self.stopped && self.searched_nodes.is_multiple_of(STOP_CHECK_NODES) {
+            self.stopped = self.control.as_ref().is_some_and(|control| control.should_abort());
+        }
+        self.stopped
     }
 
-    fn draw_score(&self, position: &Position) -> i32 {
-        draw_score_for_side_to_move(position, self.settings)
+    /// Contempt belongs to the side to move at the root, so at odd plies
+    /// the draw is worth the opposite to the side to move.
+    fn draw_score(&self, position: &Position, ply: i32) -> i32 {
+        if ply % 2 == 0 {
+            draw_score_for_side_to_move(position, self.settings)
+        } else if !self.settings.avoid_draws || self.settings.draw_contempt_cp == 0 {
+            0
+        } else {
+            -draw_score_for_root_side(-evaluate_for_side_to_move(position), self.settings)
+        }
     }
 
-    fn negamax(&mut self, position: &Position, depth: u8, mut alpha: i32, mut beta: i32, ply: i32) -> i32 {
+    fn negamax(&mut self, position: &mut Position, depth: u8, mut alpha: i32, mut beta: i32, ply: i32) -> i32 {
         self.searched_nodes += 1;
-        if position.is_fifty_move_rule_draw() {
-            return self.draw_score(position);
+        self.clear_pv(ply);
+        self.seldepth = self.seldepth.max(ply as u8);
+        if self.out_of_time() {
+            return 0;
         }
+        if position.is_draw_without_history() || self.is_repetition(position, ply) {
+            return self.draw_score(position, ply);
+        }
+        let pv_node = beta - alpha > 1;
         let alpha_start = alpha;
-        let key = hash_position(position);
+        let key = position.zobrist_key();
 
+        // A shallower entry cannot cut the node off, but its move is still
+        // the best ordering hint there is.
+        let mut hash_move = None;
         if depth > 0 {
-            if let Some(hit) = self.tt.probe(key, depth) {
-                match hit.bound {
-                    TT_EXACT => return hit.score,
-                    TT_LOWER => alpha = alpha.max(hit.score),
-                    TT_UPPER => beta = beta.min(hit.score),
-                    _ => {}
-                }
-                if alpha >= beta {
-                    return hit.score;
+            if let Some(hit) = self.tt.probe(key, 0) {
+                hash_move = hit.best_move;
+                if hit.depth >= depth {
+                    match hit.bound {
+                        TT_EXACT => return hit.score,
+                        TT_LOWER => alpha = alpha.max(hit.score),
+                        TT_UPPER => beta = beta.min(hit.score),
+                        _ => {}
+                    }
+                    if alpha >= beta {
+                        return hit.score;
+                    }
                 }
             }
         }
@@ -306,7 +1233,7 @@ impl SearchWorker {
             return if in_check {
                 -MATE_SCORE + ply
             } else {
-                self.draw_score(position)
+                self.draw_score(position, ply)
             };
         }
 
@@ -315,23 +1242,92 @@ impl SearchWorker {
         }
 
         if depth == 0 && !in_check {
-            return self.quiescence(position, alpha, beta, ply);
+            return self.quiescence(position, alpha, beta, ply, self.settings.quiescence_checks);
         }
 
-        order_moves(position, &mut moves);
+        // Selective search only runs where the window is null and no mate
+        // score is at stake.
+        let selective = !pv_node && !in_check && !score_is_mate(alpha) && !score_is_mate(beta);
+        let static_eval = if selective { evaluate_for_side_to_move(position) } else { 0 };
+        if selective {
+            if let Some(score) = self.prune_node(position, depth, alpha, beta, ply, static_eval) {
+                return score;
+            }
+            if self.stopped {
+                return 0;
+            }
+        }
+        let futility_value = (selective && self.settings.futility_pruning && depth <= FUTILITY_DEPTH)
+            .then(|| static_eval + FUTILITY_MARGIN * i32::from(depth))
+            .filter(|value| *value <= alpha);
+
+        self.order_moves(position, &mut moves, hash_move, ply);
         let mut best = -INFINITY;
-        for chess_move in moves {
-            let mut next = position.clone();
-            if next.apply_unchecked(chess_move).is_err() {
+        let mut best_move = None;
+        let mut quiets_tried = Vec::new();
+        let move_count = moves.len();
+        for (index, chess_move) in moves.into_iter().enumerate() {
+            let capture = position.is_capture(chess_move);
+            let quiet = chess_move.promotion.is_none() && !capture;
+            let extension = self.extension(position, chess_move, move_count, ply);
+            let may_reduce = self.settings.late_move_reductions && !in_check && depth >= LMR_MIN_DEPTH;
+            let late_quiet = quiet
+                && index > 0
+                && extension == 0
+                && (futility_value.is_some() || may_reduce)
+                && !position.gives_check(chess_move);
+            if let Some(value) = futility_value.filter(|_| late_quiet) {
+                best = best.max(value);
                 continue;
             }
-            let next_depth = depth.saturating_sub(1);
-            let score = -self.negamax(&next, next_depth, -beta, -alpha, ply + 1);
-            best = best.max(score);
+            self.set_move(ply, chess_move);
+            if capture {
+                self.capture_squares[ply as usize] = Some(chess_move.to);
+            }
+            let undo = position.make_move(chess_move);
+            self.extensions += extension;
+            let next_depth = depth.saturating_sub(1) + extension;
+            let reduction = if late_quiet && may_reduce {
+                late_move_reduction(depth, index, pv_node)
+            } else {
+                0
+            };
+            let scout = index > 0 && self.settings.principal_variation_search;
+            let mut score = -INFINITY;
+            let mut full_window = true;
+            if reduction > 0 {
+                score = -self.negamax(position, next_depth - reduction, -alpha - 1, -alpha, ply + 1);
+                full_window = score > alpha;
+            }
+            if scout && full_window && !self.stopped {
+                score = -self.negamax(position, next_depth, -alpha - 1, -alpha, ply + 1);
+                full_window = score > alpha && score < beta;
+            }
+            if full_window && !self.stopped {
+                score = -self.negamax(position, next_depth, -beta, -alpha, ply + 1);
+            }
+            self.extensions -= extension;
+            position.unmake_move(chess_move, undo);
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                best_move = Some(chess_move);
+            }
+            if score > alpha {
+                self.update_pv(ply, chess_move);
+            }
             alpha = alpha.max(score);
             if alpha >= beta {
+                if quiet {
+                    self.reward_quiet_cutoff(position, chess_move, &quiets_tried, depth, ply);
+                }
                 break;
             }
+            if quiet {
+                quiets_tried.push(chess_move);
+            }
         }
 
         let bound = if best <= alpha_start {
@@ -341,29 +1337,84 @@ impl SearchWorker {
         } else {
             TT_EXACT
         };
-        self.tt.store(key, depth, best, bound, self.age);
+        let best_move = best_move.map(|chess_move| position.pack(chess_move));
+        self.tt.store(key, depth, best, bound, self.age, best_move);
         best
     }
 
-    fn quiescence(&mut self, position: &Position, mut alpha: i32, beta: i32, ply: i32) -> i32 {
+    /// Reverse futility, razoring and null-move pruning at a non-PV node that
+    /// is not in check. Returns the score to cut the node off with.
+    fn prune_node(&mut self, position: &mut Position, depth: u8, alpha: i32, beta: i32, ply: i32, static_eval: i32) -> Option<i32> {
+        if self.settings.reverse_futility_pruning
+            && depth <= REVERSE_FUTILITY_DEPTH
+            && static_eval - REVERSE_FUTILITY_MARGIN * i32::from(depth) >= beta
+        {
+            return Some(static_eval - REVERSE_FUTILITY_MARGIN * i32::from(depth));
+        }
+
+        if self.settings.razoring && depth <= RAZOR_DEPTH && static_eval + RAZOR_MARGIN * i32::from(depth) <= alpha {
+            let score = self.quiescence(position, alpha, alpha + 1, ply, self.settings.quiescence_checks);
+            if score <= alpha {
+                return Some(score);
+            }
+        }
+
+        // Zugzwang guards: never two null moves in a row, and never with only
+        // king and pawns, where passing is often the best move.
+        if self.settings.null_move_pruning
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && static_eval >= beta
+            && self.previous_move(ply).is_some()
+            && has_non_pawn_material(position, position.side_to_move())
+        {
+            let reduction = 2 + depth / 4;
+            self.set_move_or_null(ply, None);
+            let undo = position.make_null_move();
+            let score = -self.negamax(position, depth.saturating_sub(1 + reduction), -beta, 1 - beta, ply + 1);
+            position.unmake_null_move(undo);
+            if !self.stopped && score >= beta {
+                return Some(beta);
+            }
+        }
+        None
+    }
+
+    /// Captures and promotions until the position is quiet. `quiet_checks`
+    /// adds quiet checking moves, which only the first quiescence ply does.
+    fn quiescence(&mut self, position: &mut Position, mut alpha: i32, beta: i32, ply: i32, quiet_checks: bool) -> i32 {
         self.searched_nodes += 1;
-        if position.is_fifty_move_rule_draw() {
-            return self.draw_score(position);
+        self.clear_pv(ply);
+        self.seldepth = self.seldepth.max(ply as u8);
+        if self.out_of_time() {
+            return 0;
         }
-        let stand_pat = evaluate_for_side_to_move(position);
-        if stand_pat >= beta {
-            return beta;
+        if position.is_draw_without_history() {
+            return self.draw_score(position, ply);
         }
-        alpha = alpha.max(stand_pat);
+        let in_check = self.settings.quiescence_evasions && position.is_in_check(position.side_to_move());
+        let mut moves = if in_check {
+            let evasions = position.legal_evasions();
+            if evasions.is_empty() {
+                return -MATE_SCORE + ply;
+            }
+            evasions
+        } else {
+            let stand_pat = evaluate_for_side_to_move(position);
+            if stand_pat >= beta {
+                return beta;
+            }
+            alpha = alpha.max(stand_pat);
+            self.quiescence_moves(position, stand_pat, alpha, quiet_checks)
+        };
 
-        let mut captures = position.legal_captures();
-        order_moves(position, &mut captures);
-        for chess_move in captures {
-            let mut next = position.clone();
-            if next.apply_unchecked(chess_move).is_err() {
-                continue;
+        order_moves(position, &mut moves);
+        for chess_move in moves {
+            let undo = position.make_move(chess_move);
+            let score = -self.quiescence(position, -beta, -alpha, ply + 1, false);
+            position.unmake_move(chess_move, undo);
+            if self.stopped {
+                return 0;
             }
-            let score = -self.quiescence(&next, -beta, -alpha, ply + 1);
             if score >= beta {
                 return beta;
             }
@@ -371,16 +1422,40 @@ impl SearchWorker {
         }
         alpha
     }
+
+    /// The moves quiescence searches when not in check.
+    fn quiescence_moves(&self, position: &Position, stand_pat: i32, alpha: i32, quiet_checks: bool) -> Vec<ChessMove> {
+        let settings = &self.settings;
+        let mut moves = position.legal_captures();
+        moves.retain(|chess_move| {
+            let Some(victim_value) = captured_piece_value(position, *chess_move) else {
+                return settings.quiescence_promotions && chess_move.promotion == Some(PieceKind::Queen);
+            };
+            if settings.delta_pruning
+                && chess_move.promotion.is_none()
+                && stand_pat + victim_value + DELTA_MARGIN < alpha
+            {
+                return false;
+            }
+            !settings.quiescence_see_pruning || static_exchange_eval(position, *chess_move) >= 0
+        });
+        if quiet_checks {
+            moves.extend(position.legal_quiet_checks());
+        }
+        moves
+    }
 }
 
+/// Lock-free table shared by every worker of a search and by clones of the
+/// engine.
 #[derive(Debug)]
-struct TranspositionTable {
+pub(crate) struct TranspositionTable {
     age: AtomicU64,
     entries: Vec<AtomicTtEntry>,
 }
 
 impl TranspositionTable {
-    fn new(hash_mb: usize) -> Self {
+    pub(crate) fn new(hash_mb: usize) -> Self {
         let bytes = hash_mb.clamp(1, 4096).saturating_mul(1024 * 1024);
         let entry_size = std::mem::size_of::<AtomicTtEntry>().max(1);
         let entry_count = (bytes / entry_size).max(1024);
@@ -399,6 +1474,24 @@ impl TranspositionTable {
         self.age.fetch_add(1, Ordering::Relaxed).wrapping_add(1) as u8
     }
 
+    /// Permille of the first 1000 slots holding an entry of the latest search.
+    pub(crate) fn hashfull(&self) -> usize {
+        let age = self.age.load(Ordering::Relaxed) as u8;
+        let sample = &self.entries[..self.entries.len().min(1000)];
+        let used = sample
+            .iter()
+            .filter(|slot| decode_tt_data(slot.data.load(Ordering::Relaxed)).is_some_and(|hit| hit.age == age))
+            .count();
+        used * 1000 / sample.len().max(1)
+    }
+
+    pub(crate) fn clear(&self) {
+        for slot in &self.entries {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
     fn probe(&self, key: u64, depth: u8) -> Option<TtHit> {
         let slot = self.slot(key)?;
         let key_a = slot.key.load(Ordering::Acquire);
@@ -417,7 +1510,7 @@ impl TranspositionTable {
         Some(hit)
     }
 
-    fn store(&self, key: u64, depth: u8, score: i32, bound: u8, age: u8) {
+    fn store(&self, key: u64, depth: u8, score: i32, bound: u8, age: u8, best_move: Option<PackedMove>) {
         if score.abs() >= MATE_SCORE - 1024 {
             return;
         }
@@ -433,7 +1526,7 @@ impl TranspositionTable {
         if !replace {
             return;
         }
-        if let Some(data) = encode_tt_data(depth, score, bound, age) {
+        if let Some(data) = encode_tt_data(depth, score, bound, age, best_move) {
             slot.data.store(data, Ordering::Release);
             slot.key.store(key, Ordering::Release);
         }
@@ -469,14 +1562,18 @@ struct TtHit {
     score: i32,
     bound: u8,
     age: u8,
+    best_move: Option<PackedMove>,
 }
 
-fn encode_tt_data(depth: u8, score: i32, bound: u8, age: u8) -> Option<u64> {
+/// Packs an entry as bound (2 bits), depth (8), age (8), biased score (22)
+/// and the raw best move (16), from the low bits up.
+fn encode_tt_data(depth: u8, score: i32, bound: u8, age: u8, best_move: Option<PackedMove>) -> Option<u64> {
     let encoded_score = score.checked_add(TT_SCORE_BIAS)? as u64;
     if encoded_score > TT_SCORE_BITS || bound > TT_UPPER {
         return None;
     }
-    Some((bound as u64) | ((depth as u64) << 2) | ((age as u64) << 10) | (encoded_score << 18))
+    let raw_move = best_move.map(PackedMove::raw).unwrap_or(0) as u64;
+    Some((bound as u64) | ((depth as u64) << 2) | ((age as u64) << 10) | (encoded_score << 18) | (raw_move << 40))
 }
 
 fn decode_tt_data(data: u64) -> Option<TtHit> {
@@ -488,16 +1585,8 @@ fn decode_tt_data(data: u64) -> Option<TtHit> {
     let age = ((data >> 10) & 0xff) as u8;
     let encoded_score = ((data >> 18) & TT_SCORE_BITS) as i32;
     let score = encoded_score - TT_SCORE_BIAS;
-    Some(TtHit { depth, score, bound, age })
-}
-
-fn hash_position(position: &Position) -> u64 {
-    let mut hash = 0xcbf29ce484222325_u64;
-    for byte in position.to_fen().bytes() {
-        hash ^= byte as u64;
-        hash = hash.wrapping_mul(0x100000001b3);
-    }
-    if hash == 0 { 1 } else { hash }
+    let best_move = PackedMove::from_raw(((data >> 40) & 0xffff) as u16);
+    Some(TtHit { depth, score, bound, age, best_move })
 }
 
 pub fn evaluate_for_side_to_move(position: &Position) -> i32 {
@@ -733,7 +1822,11 @@ fn draw_score_for_side_to_move(position: &Position, settings: SearchSettings) ->
     if !settings.avoid_draws || settings.draw_contempt_cp == 0 {
         return 0;
     }
-    let static_score = evaluate_for_side_to_move(position);
+    draw_score_for_root_side(evaluate_for_side_to_move(position), settings)
+}
+
+/// A draw is welcome when the side with the contempt stands clearly worse.
+fn draw_score_for_root_side(static_score: i32, settings: SearchSettings) -> i32 {
     if static_score <= -150 {
         (settings.draw_contempt_cp / 2).max(1)
     } else {
@@ -741,18 +1834,9 @@ fn draw_score_for_side_to_move(position: &Position, settings: SearchSettings) ->
     }
 }
 
-fn adjusted_root_score(
-    position: &Position,
-    chess_move: ChessMove,
-    raw_score: i32,
-    settings: SearchSettings,
-) -> i32 {
-    if score_is_mate(raw_score) {
-        return raw_score;
-    }
-    raw_score
-        .saturating_add(root_tactical_adjustment(position, chess_move))
-        .saturating_add(root_personality_adjustment(position, chess_move, settings))
+/// Added to the raw search score of a root move that is not a mate.
+fn root_score_adjustment(position: &Position, chess_move: ChessMove, settings: SearchSettings) -> i32 {
+    root_tactical_adjustment(position, chess_move).saturating_add(root_personality_adjustment(position, chess_move, settings))
 }
 
 fn root_personality_adjustment(position: &Position, chess_move: ChessMove, settings: SearchSettings) -> i32 {
@@ -855,7 +1939,7 @@ fn apply_humanity_to_candidates(
 }
 
 fn personality_seed(position: &Position) -> u64 {
-    let mut seed = hash_position(position) ^ 0x9e37_79b9_7f4a_7c15;
+    let mut seed = position.zobrist_key() ^ 0x9e37_79b9_7f4a_7c15;
     seed ^= (position.fullmove_number() as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
     seed.rotate_left(23).wrapping_mul(0x94d0_49bb_1331_11eb)
 }
@@ -876,7 +1960,7 @@ fn root_tactical_adjustment(position: &Position, chess_move: ChessMove) -> i32 {
 
     let mut penalty = 0;
     let opponent_moves = next.legal_moves();
-    if mate_in_one_score(&next, &opponent_moves, 1).is_some() {
+    if mate_in_one_score(&mut next, &opponent_moves, 1).is_some() {
         penalty += 220_000;
     } else {
         let own_king_danger = side_king_danger(&next, mover.color);
@@ -937,17 +2021,14 @@ pub fn static_exchange_eval(position: &Position, chess_move: ChessMove) -> i32 {
 }
 
 fn captured_piece_value(position: &Position, chess_move: ChessMove) -> Option<i32> {
-    if let Some(piece) = position.piece_at(chess_move.to) {
-        return Some(piece.kind.material_value());
-    }
-    let mover = position.piece_at(chess_move.from)?;
-    if mover.kind == PieceKind::Pawn
-        && position.is_capture(chess_move)
-        && file_of(chess_move.from) != file_of(chess_move.to)
-    {
+    let packed = position.pack(chess_move);
+    if packed.is_en_passant() {
         return Some(PieceKind::Pawn.material_value());
     }
-    None
+    if !packed.is_capture() {
+        return None;
+    }
+    position.piece_at(chess_move.to).map(|piece| piece.kind.material_value())
 }
 
 fn checking_reply_count(position: &Position, moves: &[ChessMove]) -> usize {
@@ -988,7 +2069,7 @@ fn side_has_forced_mate_in_two(position: &Position, first_moves: &[ChessMove]) -
                 break;
             }
             let mating_moves = after_reply.legal_moves();
-            if mate_in_one_score(&after_reply, &mating_moves, 0).is_none() {
+            if mate_in_one_score(&mut after_reply, &mating_moves, 0).is_none() {
                 all_replies_allow_mate = false;
                 break;
             }
@@ -1012,27 +2093,20 @@ fn side_king_danger(position: &Position, color: Color) -> i32 {
         return 0;
     }
     let opponent = color.opposite();
-    let ring = king_ring_squares(king);
+    let ring = king_attacks(king) | square_bit(king);
     let mut danger = 0;
-    for square in 0_u8..64 {
+    for square in squares(position.occupancy(opponent)) {
         let Some(piece) = position.piece_at(square) else {
             continue;
         };
-        if piece.color != opponent {
-            continue;
-        }
         let unit = attack_unit(piece.kind);
-        let mut touches_ring = false;
-        for target in &ring {
-            if attacks_square(position, square, piece, *target) {
-                touches_ring = true;
-                danger += unit;
-            }
-        }
-        if touches_ring && piece.kind == PieceKind::Queen {
+        let attacks = position.attacks_from(square);
+        let ring_hits = (attacks & ring).count_ones() as i32;
+        danger += unit * ring_hits;
+        if ring_hits > 0 && piece.kind == PieceKind::Queen {
             danger += 4;
         }
-        if attacks_square(position, square, piece, king) {
+        if attacks & square_bit(king) != 0 {
             danger += unit * 2;
         }
     }
@@ -1057,18 +2131,6 @@ fn non_pawn_material_total(position: &Position) -> i32 {
     total
 }
 
-fn king_ring_squares(king: u8) -> Vec<u8> {
-    let mut squares = Vec::with_capacity(9);
-    for df in -1..=1 {
-        for dr in -1..=1 {
-            if let Some(square) = index(file_of(king) + df, rank_of(king) + dr) {
-                squares.push(square);
-            }
-        }
-    }
-    squares
-}
-
 fn missing_pawn_shield_penalty(position: &Position, color: Color, king: u8) -> i32 {
     let direction = match color {
         Color::White => 1,
@@ -1098,82 +2160,32 @@ fn attack_unit(kind: PieceKind) -> i32 {
 }
 
 fn least_attacker_value(position: &Position, target: u8, color: Color) -> Option<i32> {
-    let mut best: Option<i32> = None;
-    for square in 0_u8..64 {
-        let Some(piece) = position.piece_at(square) else {
-            continue;
-        };
-        if piece.color == color && attacks_square(position, square, piece, target) {
-            let value = piece.kind.material_value();
-            best = Some(best.map(|current| current.min(value)).unwrap_or(value));
-        }
-    }
-    best
-}
-
-fn attacks_square(position: &Position, from: u8, piece: Piece, target: u8) -> bool {
-    if from == target {
-        return false;
-    }
-    let df = file_of(target) - file_of(from);
-    let dr = rank_of(target) - rank_of(from);
-    match piece.kind {
-        PieceKind::Pawn => match piece.color {
-            Color::White => dr == 1 && df.abs() == 1,
-            Color::Black => dr == -1 && df.abs() == 1,
-        },
-        PieceKind::Knight => (df.abs() == 1 && dr.abs() == 2) || (df.abs() == 2 && dr.abs() == 1),
-        PieceKind::Bishop => df.abs() == dr.abs() && line_clear(position, from, target, df.signum(), dr.signum()),
-        PieceKind::Rook => (df == 0 || dr == 0) && line_clear(position, from, target, df.signum(), dr.signum()),
-        PieceKind::Queen => {
-            (df.abs() == dr.abs() || df == 0 || dr == 0)
-                && line_clear(position, from, target, df.signum(), dr.signum())
-        }
-        PieceKind::King => df.abs() <= 1 && dr.abs() <= 1,
-    }
-}
-
-fn line_clear(position: &Position, from: u8, target: u8, step_file: i32, step_rank: i32) -> bool {
-    if step_file == 0 && step_rank == 0 {
-        return false;
-    }
-    let mut file = file_of(from) + step_file;
-    let mut rank = rank_of(from) + step_rank;
-    while let Some(square) = index(file, rank) {
-        if square == target {
-            return true;
-        }
-        if position.piece_at(square).is_some() {
-            return false;
-        }
-        file += step_file;
-        rank += step_rank;
-    }
-    false
+    squares(position.attackers_to(target, color))
+        .filter_map(|square| position.piece_at(square))
+        .map(|piece| piece.kind.material_value())
+        .min()
 }
 
-
 pub fn evaluate_tactical_for_side_to_move(position: &Position) -> i32 {
     if position.is_checkmate() {
         return -MATE_SCORE;
     }
-    if position.is_stalemate() || position.is_fifty_move_rule_draw() {
+    if position.is_stalemate() || position.is_draw_without_history() {
         return 0;
     }
     let moves = position.legal_moves();
-    if let Some(score) = mate_in_one_score(position, &moves, 0) {
+    if let Some(score) = mate_in_one_score(&mut position.clone(), &moves, 0) {
         return score;
     }
     evaluate_for_side_to_move(position)
 }
 
-fn mate_in_one_score(position: &Position, moves: &[ChessMove], ply: i32) -> Option<i32> {
+fn mate_in_one_score(position: &mut Position, moves: &[ChessMove], ply: i32) -> Option<i32> {
     for chess_move in moves.iter().copied() {
-        let mut next = position.clone();
-        if next.apply_unchecked(chess_move).is_err() {
-            continue;
-        }
-        if next.is_checkmate() {
+        let undo = position.make_move(chess_move);
+        let mated = position.is_in_check(position.side_to_move()) && position.legal_moves().is_empty();
+        position.unmake_move(chess_move, undo);
+        if mated {
             return Some(MATE_SCORE - ply - 1);
         }
     }
@@ -1194,7 +2206,110 @@ pub fn mate_score_to_uci_moves(score: i32) -> Option<i32> {
 }
 
 fn order_moves(position: &Position, moves: &mut [ChessMove]) {
-    moves.sort_by(|left, right| move_order_score(position, *right).cmp(&move_order_score(position, *left)));
+    moves.sort_by_key(|chess_move| std::cmp::Reverse(move_order_score(position, *chess_move)));
+}
+
+/// `order_moves`, then the transposition-table move in front. The others
+/// keep their order.
+fn order_moves_with_hash_move(position: &Position, moves: &mut [ChessMove], hash_move: Option<PackedMove>) {
+    order_moves(position, moves);
+    move_hash_move_first(moves, hash_move);
+}
+
+/// Plies taken off a late quiet move: one, two for very late moves in deep
+/// searches, and one less at PV nodes.
+fn late_move_reduction(depth: u8, index: usize, pv_node: bool) -> u8 {
+    if depth < LMR_MIN_DEPTH || index < LMR_FULL_DEPTH_MOVES {
+        return 0;
+    }
+    let reduction: u8 = if depth >= 6 && index >= 8 { 2 } else { 1 };
+    let reduction = reduction - u8::from(pv_node);
+    reduction.min(depth - 2)
+}
+
+/// No enemy pawn can stand in front of a pawn on its seventh rank, so every
+/// such pawn is passed.
+fn is_passed_pawn_push_to_seventh(position: &Position, chess_move: ChessMove) -> bool {
+    position.piece_at(chess_move.from).is_some_and(|piece| {
+        let seventh = if piece.color == Color::White { 6 } else { 1 };
+        piece.kind == PieceKind::Pawn && rank_of(chess_move.to) == seventh
+    })
+}
+
+fn has_non_pawn_material(position: &Position, color: Color) -> bool {
+    [PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen]
+        .into_iter()
+        .any(|kind| position.pieces(color, kind) != 0)
+}
+
+fn table_move(tt: &TranspositionTable, position: &Position) -> Option<PackedMove> {
+    let packed = tt.probe(position.zobrist_key(), 0)?.best_move?;
+    position.legal_packed_moves().contains(&packed).then_some(packed)
+}
+
+fn move_hash_move_first(moves: &mut [ChessMove], hash_move: Option<PackedMove>) {
+    let Some(hash_move) = hash_move.map(PackedMove::to_move) else {
+        return;
+    };
+    if let Some(index) = moves.iter().position(|chess_move| *chess_move == hash_move) {
+        moves[..=index].rotate_right(1);
+    }
+}
+
+/// Quiet-move ordering state of one worker: killer slots per ply, butterfly
+/// history and counter-moves indexed by the squares of the previous move.
+struct MoveOrdering {
+    killers: Vec<[Option<ChessMove>; 2]>,
+    history: Box<[[[i32; 64]; 64]; 2]>,
+    counter_moves: Box<[[Option<ChessMove>; 64]; 64]>,
+}
+
+impl MoveOrdering {
+    fn new() -> Self {
+        Self { killers: Vec::new(), history: Box::new([[[0; 64]; 64]; 2]), counter_moves: Box::new([[None; 64]; 64]) }
+    }
+
+    fn clear(&mut self) {
+        self.killers.clear();
+        *self.history = [[[0; 64]; 64]; 2];
+        *self.counter_moves = [[None; 64]; 64];
+    }
+
+    fn killers(&self, ply: i32) -> [Option<ChessMove>; 2] {
+        self.killers.get(ply as usize).copied().unwrap_or([None; 2])
+    }
+
+    fn add_killer(&mut self, ply: i32, chess_move: ChessMove) {
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let slots = &mut self.killers[ply];
+        if slots[0] != Some(chess_move) {
+            slots[1] = slots[0];
+            slots[0] = Some(chess_move);
+        }
+    }
+
+    fn history(&self, side: Color, chess_move: ChessMove) -> i32 {
+        self.history[side.index()][chess_move.from as usize][chess_move.to as usize]
+    }
+
+    /// Moves the entry towards ±`HISTORY_MAX` by `bonus`, less so the
+    /// closer it already is.
+    fn update_history(&mut self, side: Color, chess_move: ChessMove, bonus: i32) {
+        let bonus = bonus.clamp(-HISTORY_MAX, HISTORY_MAX);
+        let entry = &mut self.history[side.index()][chess_move.from as usize][chess_move.to as usize];
+        *entry += bonus - *entry * bonus.abs() / HISTORY_MAX;
+    }
+
+    fn counter_move(&self, previous: ChessMove) -> Option<ChessMove> {
+        self.counter_moves[previous.from as usize][previous.to as usize]
+    }
+
+    fn set_counter_move(&mut self, previous: ChessMove, chess_move: ChessMove) {
+        self.counter_moves[previous.from as usize][previous.to as usize] = Some(chess_move);
+    }
 }
 
 fn move_order_score(position: &Position, chess_move: ChessMove) -> i32 {
@@ -1213,7 +2328,7 @@ fn move_order_score(position: &Position, chess_move: ChessMove) -> i32 {
         score += 10 * victim_value - attacker.kind.material_value();
         score += (static_exchange_eval(position, chess_move) * 2).clamp(-2_000, 2_000);
     }
-    if attacker.kind == PieceKind::King && (file_of(chess_move.from) - file_of(chess_move.to)).abs() == 2 {
+    if position.is_castling(chess_move) {
         score += 50;
     }
     score
@@ -1222,7 +2337,7 @@ fn move_order_score(position: &Position, chess_move: ChessMove) -> i32 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chess::Position;
+    use crate::chess::{Game, Position};
 
     #[test]
     fn engine_finds_a_legal_move_from_startpos() {
@@ -1232,6 +2347,259 @@ mod tests {
         assert!(position.legal_moves().contains(&best_move));
     }
 
+    #[test]
+    fn transposition_entries_keep_the_packed_best_move() {
+        let position = Position::startpos();
+        let e4 = position.parse_uci_packed("e2e4").unwrap();
+        let data = encode_tt_data(7, -1234, TT_LOWER, 3, Some(e4)).unwrap();
+        let hit = decode_tt_data(data).unwrap();
+        assert_eq!((hit.depth, hit.score, hit.bound, hit.age, hit.best_move), (7, -1234, TT_LOWER, 3, Some(e4)));
+        assert_eq!(decode_tt_data(encode_tt_data(1, 0, TT_EXACT, 0, None).unwrap()).unwrap().best_move, None);
+
+        let mut engine = Engine::new(3);
+        let best = engine.best_move(&position).unwrap();
+        let mut after = position.clone();
+        after.make_legal_move(best).unwrap();
+        let reply = engine.hash_move(&after).unwrap();
+        assert!(after.legal_packed_moves().contains(&reply));
+    }
+
+    #[test]
+    fn hash_move_goes_first_and_clear_hash_empties_the_table() {
+        let position = Position::startpos();
+        let mut moves = position.legal_moves();
+        let quiet = position.parse_uci_packed("g2g3").unwrap();
+        order_moves_with_hash_move(&position, &mut moves, Some(quiet));
+        assert_eq!(moves[0], quiet.to_move());
+        let mut unordered = position.legal_moves();
+        order_moves(&position, &mut unordered);
+        unordered.retain(|chess_move| *chess_move != quiet.to_move());
+        assert_eq!(moves[1..], unordered[..]);
+
+        let mut engine = Engine::new(3);
+        engine.set_hash_mb(1);
+        let mut reports = Vec::new();
+        engine.root_candidates_with_info(&position, |info| reports.push(info.hashfull));
+        assert!(reports.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(engine.hashfull() > 0 && engine.hashfull() <= 1000);
+        assert_eq!(engine.hashfull(), *reports.last().unwrap());
+        engine.clear_hash();
+        assert_eq!(engine.hashfull(), 0);
+        assert_eq!(engine.hash_move(&position), None);
+    }
+
+    #[test]
+    fn history_killers_and_counter_moves_order_quiet_moves() {
+        let position = Position::startpos();
+        let g3 = position.parse_uci_move("g2g3").unwrap();
+        let h3 = position.parse_uci_move("h2h3").unwrap();
+        let e4 = position.parse_uci_move("e2e4").unwrap();
+        let mut ordering = MoveOrdering::new();
+        for _ in 0..200 {
+            ordering.update_history(Color::White, g3, 64);
+            ordering.update_history(Color::White, h3, -64);
+        }
+        assert!(ordering.history(Color::White, g3) > 0 && ordering.history(Color::White, g3) <= HISTORY_MAX);
+        assert!(ordering.history(Color::White, h3) < 0 && ordering.history(Color::White, h3) >= -HISTORY_MAX);
+        assert_eq!(ordering.history(Color::Black, g3), 0);
+        ordering.add_killer(2, e4);
+        ordering.add_killer(2, g3);
+        ordering.add_killer(2, g3);
+        assert_eq!(ordering.killers(2), [Some(g3), Some(e4)]);
+        assert_eq!(ordering.killers(3), [None, None]);
+
+        let mut worker = SearchWorker::new(Arc::new(TranspositionTable::new(1)), 1, SearchSettings::default());
+        worker.ordering.update_history(Color::White, h3, 4_000);
+        let mut moves = position.legal_moves();
+        worker.order_moves(&position, &mut moves, None, 0);
+        assert_eq!(moves[0], h3);
+        worker.settings.history_heuristic = false;
+        let mut moves = position.legal_moves();
+        worker.order_moves(&position, &mut moves, None, 0);
+        let mut static_order = position.legal_moves();
+        order_moves(&position, &mut static_order);
+        assert_eq!(moves, static_order);
+
+        let mut after_e4 = position.clone();
+        after_e4.make_legal_move(e4).unwrap();
+        let h6 = after_e4.parse_uci_move("h7h6").unwrap();
+        worker.set_move(0, e4);
+        worker.ordering.set_counter_move(e4, h6);
+        let mut replies = after_e4.legal_moves();
+        worker.order_moves(&after_e4, &mut replies, None, 1);
+        assert_eq!(replies[0], h6);
+        worker.settings.counter_moves = false;
+        let mut replies = after_e4.legal_moves();
+        worker.order_moves(&after_e4, &mut replies, None, 1);
+        assert_ne!(replies[0], h6);
+
+        worker.ordering.clear();
+        assert_eq!(worker.ordering.history(Color::White, h3), 0);
+        assert_eq!(worker.ordering.counter_move(e4), None);
+    }
+
+    #[test]
+    fn quiet_move_heuristics_shrink_the_tree_and_can_be_switched_off() {
+        let position = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let nodes = |killer_moves: bool, history_heuristic: bool, counter_moves: bool| {
+            let mut engine = Engine::new(3);
+            engine.set_settings(SearchSettings {
+                deterministic_multithread: false,
+                killer_moves,
+                history_heuristic,
+                counter_moves,
+                ..SearchSettings::default()
+            });
+            let best = engine.best_move(&position).unwrap();
+            assert!(position.legal_moves().contains(&best));
+            engine.searched_nodes()
+        };
+        let all = nodes(true, true, true);
+        assert_eq!(all, nodes(true, true, true));
+        let none = nodes(false, false, false);
+        assert!(all < none, "{all} >= {none}");
+        assert_ne!(nodes(true, false, false), none);
+        assert_ne!(nodes(false, true, false), none);
+    }
+
+    #[test]
+    fn selective_search_shrinks_the_tree_and_keeps_the_move() {
+        let position = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let search = |selective: bool| {
+            let mut engine = Engine::new(4);
+            engine.set_settings(SearchSettings {
+                deterministic_multithread: false,
+                principal_variation_search: selective,
+                null_move_pruning: selective,
+                late_move_reductions: selective,
+                reverse_futility_pruning: selective,
+                futility_pruning: selective,
+                razoring: selective,
+                ..SearchSettings::default()
+            });
+            let best = engine.best_move_with_score(&position).unwrap();
+            (best, engine.searched_nodes())
+        };
+        let (selective_best, selective_nodes) = search(true);
+        let (full_best, full_nodes) = search(false);
+        assert_eq!(selective_best, full_best);
+        assert!(selective_nodes < full_nodes, "{selective_nodes} vs {full_nodes}");
+    }
+
+    #[test]
+    fn repeating_a_game_position_is_scored_as_a_draw() {
+        let mut game = Game::new(Position::startpos());
+        for text in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6"] {
+            game.push_uci(text).unwrap();
+        }
+        let position = game.position().clone();
+        let back = position.parse_uci_move("f3g1").unwrap();
+        let score_of_back = |engine: &mut Engine| {
+            engine.set_exact_root_scores(true);
+            let candidates = engine.root_candidates_with_info(&position, |_| {});
+            candidates.into_iter().find(|candidate| candidate.chess_move == back).unwrap().score
+        };
+
+        let mut engine = Engine::new(2);
+        engine.set_deterministic_multithread(false);
+        let without_history = score_of_back(&mut engine);
+        engine.set_game_history(game.keys_since_irreversible());
+        let with_history = score_of_back(&mut engine);
+        let adjustment = root_score_adjustment(&position, back, engine.settings());
+        assert_eq!(with_history, adjustment);
+        assert_ne!(without_history, with_history);
+
+        engine.set_avoid_draws(true);
+        engine.set_draw_contempt_cp(60);
+        assert_eq!(score_of_back(&mut engine), adjustment - 60);
+
+        // History that does not end with the searched position is ignored.
+        engine.set_avoid_draws(false);
+        engine.set_game_history(&game.keys_since_irreversible()[..3]);
+        assert_eq!(score_of_back(&mut engine), without_history);
+    }
+
+    #[test]
+    fn repetitions_inside_the_line_do_not_cross_null_moves() {
+        let mut worker = SearchWorker::new(Arc::new(TranspositionTable::new(1)), 1, SearchSettings::default());
+        let mut position = Position::startpos();
+        worker.set_game_keys(vec![position.zobrist_key()]);
+        for (ply, text) in ["g1f3", "g8f6", "f3g1", "f6g8"].into_iter().enumerate() {
+            let chess_move = position.parse_uci_move(text).unwrap();
+            worker.set_move(ply as i32, chess_move);
+            position.make_move(chess_move);
+            assert_eq!(worker.is_repetition(&position, ply as i32 + 1), ply == 3);
+        }
+
+        worker.set_move_or_null(1, None);
+        assert!(!worker.is_repetition(&position, 4));
+    }
+
+    #[test]
+    fn forcing_moves_are_extended_within_the_budget() {
+        let position = Position::from_fen("4k3/8/1P6/8/3p4/4P3/8/R3K3 w - - 0 1").unwrap();
+        let check = position.parse_uci_move("a1a8").unwrap();
+        let push = position.parse_uci_move("b6b7").unwrap();
+        let recapture = position.parse_uci_move("e3d4").unwrap();
+        let quiet = position.parse_uci_move("e1e2").unwrap();
+        let settings = SearchSettings {
+            check_extensions: true,
+            one_reply_extensions: true,
+            recapture_extensions: true,
+            passed_pawn_extensions: true,
+            ..SearchSettings::default()
+        };
+        let mut worker = SearchWorker::new(Arc::new(TranspositionTable::new(1)), 1, settings);
+        assert_eq!(worker.extension(&position, check, 20, 0), 1);
+        assert_eq!(worker.extension(&position, push, 20, 0), 1);
+        assert_eq!(worker.extension(&position, quiet, 20, 0), 0);
+        assert_eq!(worker.extension(&position, quiet, 1, 0), 1);
+        assert_eq!(worker.extension(&position, recapture, 20, 1), 0);
+        worker.set_move(0, quiet);
+        worker.capture_squares[0] = Some(recapture.to);
+        assert_eq!(worker.extension(&position, recapture, 20, 1), 1);
+
+        worker.extensions = settings.extension_budget;
+        assert_eq!(worker.extension(&position, check, 20, 0), 0);
+        worker.extensions = 0;
+        worker.settings = SearchSettings::default();
+        assert_eq!(worker.extension(&position, check, 1, 1), 0);
+        assert!(!is_passed_pawn_push_to_seventh(&position, position.parse_uci_move("e3e4").unwrap()));
+    }
+
+    #[test]
+    fn check_extensions_grow_the_tree_only_within_the_budget() {
+        let position = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+        let search = |check_extensions: bool, extension_budget: u8| {
+            let mut engine = Engine::new(3);
+            engine.set_settings(SearchSettings {
+                deterministic_multithread: false,
+                check_extensions,
+                extension_budget,
+                ..SearchSettings::default()
+            });
+            engine.best_move_with_score(&position).unwrap();
+            engine.searched_nodes()
+        };
+        let plain = search(false, 8);
+        assert_eq!(search(true, 0), plain);
+        assert!(search(true, 8) > plain);
+    }
+
+    #[test]
+    fn null_move_is_not_tried_with_only_king_and_pawns() {
+        let pawns = Position::from_fen("8/8/4k3/4p3/4P3/4K3/8/8 w - - 0 1").unwrap();
+        assert!(!has_non_pawn_material(&pawns, Color::White));
+        let knight = Position::from_fen("8/8/4k3/4p3/4P3/4K3/8/6N1 w - - 0 1").unwrap();
+        assert!(has_non_pawn_material(&knight, Color::White));
+        assert!(!has_non_pawn_material(&knight, Color::Black));
+
+        assert_eq!(late_move_reduction(2, 10, false), 0);
+        assert_eq!(late_move_reduction(4, 2, false), 0);
+        assert_eq!(late_move_reduction(4, 3, true), 0);
+        assert_eq!(late_move_reduction(8, 9, false), 2);
+    }
+
     #[test]
     fn deterministic_root_split_matches_single_thread_best_move() {
         let position = Position::startpos();
@@ -1246,10 +2614,7 @@ mod tests {
             max_threads: 4,
             granularity: 2,
             hash_mb: 4,
-            avoid_draws: false,
-            draw_contempt_cp: 35,
-            risk_level: 0,
-            humanity_level: 0,
+            ..SearchSettings::default()
         });
         let parallel_best = parallel.best_move_with_score(&position).unwrap();
 
@@ -1257,6 +2622,113 @@ mod tests {
         assert_eq!(single_best.1, parallel_best.1);
     }
 
+    #[test]
+    fn iterative_deepening_reports_every_depth_and_split_keeps_candidates() {
+        let position = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut single = Engine::new(3);
+        single.set_deterministic_multithread(false);
+        let mut reports = Vec::new();
+        let single_candidates = single.root_candidates_with_info(&position, |info| reports.push(info.clone()));
+
+        assert_eq!(reports.iter().map(|info| info.depth).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(reports.windows(2).all(|pair| pair[0].nodes < pair[1].nodes));
+        let last = reports.last().unwrap();
+        assert_eq!((last.score, last.pv[0]), (single_candidates[0].score, single_candidates[0].chess_move));
+        assert!(last.seldepth >= 3);
+        assert_eq!(last.pv, single_candidates[0].pv);
+        assert_eq!(last.nodes, single.searched_nodes());
+
+        let mut parallel = Engine::new(3);
+        parallel.set_settings(SearchSettings {
+            deterministic_multithread: true,
+            max_threads: 3,
+            granularity: 2,
+            ..SearchSettings::default()
+        });
+        assert_eq!(parallel.root_candidates(&position), single_candidates);
+    }
+
+    #[test]
+    fn every_root_candidate_carries_a_legal_principal_variation() {
+        let position = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut engine = Engine::new(3);
+        let candidates = engine.root_candidates(&position);
+        for candidate in &candidates {
+            assert_eq!(candidate.pv[0], candidate.chess_move);
+            assert!(candidate.pv.len() <= 3);
+            let mut board = position.clone();
+            for chess_move in &candidate.pv {
+                board.make_legal_move(*chess_move).unwrap();
+            }
+        }
+        assert_eq!(candidates[0].pv.len(), 3);
+
+        let mate = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let best = Engine::new(3).root_candidates(&mate).remove(0);
+        assert_eq!(best.pv, vec![mate.parse_uci_move("a1a8").unwrap()]);
+    }
+
+    #[test]
+    fn multi_pv_scores_the_best_moves_exactly() {
+        let position = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut full = Engine::new(3);
+        full.set_deterministic_multithread(false);
+        full.set_exact_root_scores(true);
+        let exact = full.root_candidates(&position);
+        assert!(exact.iter().all(|candidate| candidate.exact));
+
+        let mut engine = Engine::new(3);
+        engine.set_deterministic_multithread(false);
+        engine.set_multi_pv(3);
+        let mut reports = Vec::new();
+        let candidates = engine.root_candidates_with_info(&position, |info| reports.push(info.clone()));
+        assert_eq!(candidates.len(), exact.len());
+        assert_eq!(candidates[..3], exact[..3]);
+        assert!(candidates[3..].iter().all(|candidate| candidate.score < candidates[2].score));
+        assert!(candidates.iter().any(|candidate| !candidate.exact));
+        assert!(engine.searched_nodes() < full.searched_nodes());
+
+        let last: Vec<_> = reports.iter().filter(|info| info.depth == 3).collect();
+        assert_eq!(last.iter().map(|info| info.multipv).collect::<Vec<_>>(), vec![1, 2, 3]);
+        for (info, candidate) in last.iter().zip(&candidates) {
+            assert_eq!((info.score, &info.pv), (candidate.score, &candidate.pv));
+        }
+    }
+
+    #[test]
+    fn hard_deadline_returns_the_last_completed_depth() {
+        let position = Position::startpos();
+        let mut engine = Engine::new(MAX_SEARCH_DEPTH);
+        engine.set_deterministic_multithread(false);
+        engine.set_deadlines(Some(SearchDeadlines { soft: Duration::from_secs(30), hard: Duration::from_millis(150) }));
+        let started = Instant::now();
+        let mut depths = Vec::new();
+        let candidates = engine.root_candidates_with_info(&position, |info| depths.push(info.depth));
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!depths.is_empty() && *depths.last().unwrap() < MAX_SEARCH_DEPTH);
+        assert_eq!(candidates.len(), 20);
+        assert!(engine.searched_nodes() > 0);
+    }
+
+    #[test]
+    fn stop_flag_keeps_only_the_completed_depth() {
+        let position = Position::startpos();
+        let mut engine = Engine::new(MAX_SEARCH_DEPTH);
+        let control = Arc::new(SearchControl::new(None));
+        engine.set_control(control.clone());
+        control.stop();
+        let mut depths = Vec::new();
+        let candidates = engine.root_candidates_with_info(&position, |info| depths.push(info.depth));
+        assert_eq!(depths, vec![1]);
+        assert_eq!(candidates.len(), 20);
+
+        let ponder = SearchControl::new(None);
+        assert!(ponder.may_deepen() && !ponder.should_abort());
+        ponder.start_clock(Some(SearchDeadlines { soft: Duration::ZERO, hard: Duration::ZERO }));
+        assert!(!ponder.may_deepen() && ponder.should_abort());
+    }
+
     #[test]
     fn hash_size_setting_resizes_transposition_table() {
         let mut engine = Engine::new(1);
@@ -1291,6 +2763,50 @@ mod tests {
         assert!(draw_score_for_side_to_move(&position, engine.settings()) < 0);
     }
 
+    #[test]
+    fn quiescence_move_selection_follows_the_settings() {
+        // Qxa7 loses the queen to the rook, bxc6 wins a knight, g7g8 is a quiet
+        // promotion and Qe5+ a quiet check.
+        let position = Position::from_fen("r3k3/p5P1/2n5/1P6/8/8/8/Q3K3 w - - 0 1").unwrap();
+        let uci = |moves: Vec<ChessMove>| {
+            let mut moves: Vec<String> = moves.into_iter().map(ChessMove::to_uci).collect();
+            moves.sort();
+            moves
+        };
+        let mut worker = SearchWorker::new(Arc::new(TranspositionTable::new(1)), 1, SearchSettings::default());
+        let stand_pat = evaluate_for_side_to_move(&position);
+        assert_eq!(uci(worker.quiescence_moves(&position, stand_pat, stand_pat, false)), ["b5c6", "g7g8q"]);
+
+        worker.settings.quiescence_see_pruning = false;
+        worker.settings.quiescence_promotions = false;
+        assert_eq!(uci(worker.quiescence_moves(&position, stand_pat, stand_pat, false)), ["a1a7", "b5c6"]);
+
+        // Far below alpha, no capture can catch up.
+        assert!(worker.quiescence_moves(&position, stand_pat, stand_pat + 2_000, false).is_empty());
+        worker.settings.delta_pruning = false;
+        assert_eq!(worker.quiescence_moves(&position, stand_pat, stand_pat + 2_000, false).len(), 2);
+
+        let checks = worker.quiescence_moves(&position, stand_pat, stand_pat, true);
+        assert!(checks.contains(&position.parse_uci_move("a1e5").unwrap()));
+        assert!(checks.iter().all(|chess_move| position.is_capture(*chess_move) || position.gives_check(*chess_move)));
+    }
+
+    #[test]
+    fn quiescence_searches_evasions_when_in_check() {
+        let mut mated = Position::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        let mut worker = SearchWorker::new(Arc::new(TranspositionTable::new(1)), 1, SearchSettings::default());
+        assert_eq!(worker.quiescence(&mut mated, -INFINITY, INFINITY, 3, false), -MATE_SCORE + 3);
+        worker.settings.quiescence_evasions = false;
+        assert_eq!(worker.quiescence(&mut mated, -INFINITY, INFINITY, 3, false), evaluate_for_side_to_move(&mated));
+
+        // Quiet checks at the first quiescence ply find a mate that captures
+        // alone miss.
+        let mut mate_in_one = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        worker.settings.quiescence_evasions = true;
+        assert!(worker.quiescence(&mut mate_in_one, -INFINITY, INFINITY, 1, false) < MATE_SCORE - 1024);
+        assert_eq!(worker.quiescence(&mut mate_in_one, -INFINITY, INFINITY, 1, true), MATE_SCORE - 2);
+    }
+
     #[test]
     fn see_marks_hanging_queen_capture_as_bad() {
         let position = Position::from_fen("4k3/8/8/8/8/1b6/r7/Q3K3 w - - 0 1").unwrap();
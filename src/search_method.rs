@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::chess::Position;
+use crate::hybrid::Hybrid;
+use crate::search::{AlphaBeta, RootCandidate, SearchControl, SearchInfo, SearchSettings, TranspositionTable};
+
+/// Name of the method a new `Engine` uses.
+pub const DEFAULT_SEARCH_METHOD: &str = "AlphaBeta";
+
+static SEARCH_METHODS: &[&dyn SearchMethod] = &[&AlphaBeta, &Hybrid];
+
+/// A way of scoring the root moves of a position. Methods are registered in
+/// `search_methods` and picked by name through `Engine::set_search_method`
+/// or the `SearchMethod` UCI option.
+pub trait SearchMethod: Debug + Sync {
+    /// Name shown in the UCI combo and the GUI.
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Options of this method. UCI and the GUI list them next to the
+    /// engine's own, and their values reach the method as
+    /// `SearchInput::options`.
+    fn options(&self) -> &'static [MethodOption] {
+        &[]
+    }
+
+    /// Scores the root moves of `input.position`, best first. `report` is
+    /// called with progress lines the way UCI `info` prints them.
+    fn search(&self, input: &SearchInput, report: &mut dyn FnMut(&SearchInfo)) -> SearchOutput;
+}
+
+/// Everything a method gets from the engine for one search.
+pub struct SearchInput<'a> {
+    pub position: &'a Position,
+    /// Keys of the game positions since the last capture or pawn move,
+    /// ending with `position`.
+    pub history: &'a [u64],
+    pub limits: SearchLimits,
+    pub settings: SearchSettings,
+    /// Every root move needs an exact score, whatever `multi_pv` says.
+    pub exact_root_scores: bool,
+    pub options: &'a MethodOptions,
+    pub(crate) tt: Arc<TranspositionTable>,
+}
+
+/// An option a search method declares, with its UCI type and default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MethodOption {
+    pub name: &'static str,
+    pub kind: MethodOptionKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MethodOptionKind {
+    Spin { default: i32, min: i32, max: i32 },
+    Combo { default: &'static str, values: &'static [&'static str] },
+}
+
+impl MethodOption {
+    pub fn default_value(&self) -> String {
+        match self.kind {
+            MethodOptionKind::Spin { default, .. } => default.to_string(),
+            MethodOptionKind::Combo { default, .. } => default.to_string(),
+        }
+    }
+
+    /// `value` in the form the option stores it: a spin value clamped to its
+    /// range, a combo value spelled as declared. `None` if it does not parse
+    /// or is not one of the combo values.
+    pub fn normalize(&self, value: &str) -> Option<String> {
+        let value = value.trim();
+        match self.kind {
+            MethodOptionKind::Spin { min, max, .. } => value.parse::<i32>().ok().map(|value| value.clamp(min, max).to_string()),
+            MethodOptionKind::Combo { values, .. } => {
+                values.iter().find(|known| known.eq_ignore_ascii_case(value)).map(|known| known.to_string())
+            }
+        }
+    }
+
+    /// The `option name ...` line UCI prints for it, with `current` as the
+    /// default.
+    pub fn uci_declaration(&self, current: &str) -> String {
+        match self.kind {
+            MethodOptionKind::Spin { min, max, .. } => {
+                format!("option name {} type spin default {current} min {min} max {max}", self.name)
+            }
+            MethodOptionKind::Combo { values, .. } => {
+                format!("option name {} type combo default {current} var {}", self.name, values.join(" var "))
+            }
+        }
+    }
+
+    /// Whether `name` refers to this option, ignoring case, spaces,
+    /// underscores and dashes.
+    fn is_named(&self, name: &str) -> bool {
+        let squeeze = |text: &str| -> String {
+            text.chars().filter(|ch| !matches!(ch, ' ' | '_' | '-')).map(|ch| ch.to_ascii_lowercase()).collect()
+        };
+        squeeze(self.name) == squeeze(name)
+    }
+}
+
+/// Values of the options the registered methods declare. An option that was
+/// never set reads as its default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MethodOptions {
+    values: BTreeMap<&'static str, String>,
+}
+
+impl MethodOptions {
+    pub fn get(&self, option: &MethodOption) -> String {
+        self.values.get(option.name).cloned().unwrap_or_else(|| option.default_value())
+    }
+
+    /// A spin option's value; the default for anything else.
+    pub fn spin(&self, option: &MethodOption) -> i32 {
+        match option.kind {
+            MethodOptionKind::Spin { default, .. } => self.get(option).parse().unwrap_or(default),
+            MethodOptionKind::Combo { .. } => 0,
+        }
+    }
+
+    /// Sets the option called `name` of any registered method. Returns
+    /// `false` when no method declares it or the value is not allowed.
+    pub fn set(&mut self, name: &str, value: &str) -> bool {
+        let Some(option) = method_option(name) else {
+            return false;
+        };
+        let Some(value) = option.normalize(value) else {
+            return false;
+        };
+        self.values.insert(option.name, value);
+        true
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchLimits {
+    pub max_depth: u8,
+    /// Stop flag and deadlines; depth 1 should complete regardless.
+    pub control: Arc<SearchControl>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SearchOutput {
+    /// Root moves with scores from the side to move's point of view, best
+    /// first.
+    pub candidates: Vec<RootCandidate>,
+    pub stats: SearchStats,
+    /// Explanations for the `info string` lines.
+    pub notes: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Deepest completed depth.
+    pub depth: u8,
+    pub seldepth: u8,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+pub fn search_methods() -> &'static [&'static dyn SearchMethod] {
+    SEARCH_METHODS
+}
+
+/// The registered method called `name`, ignoring case.
+pub fn search_method(name: &str) -> Option<&'static dyn SearchMethod> {
+    let name = name.trim();
+    SEARCH_METHODS.iter().copied().find(|method| method.name().eq_ignore_ascii_case(name))
+}
+
+/// The option called `name` of any registered method.
+pub fn method_option(name: &str) -> Option<&'static MethodOption> {
+    SEARCH_METHODS.iter().flat_map(|method| method.options()).find(|option| option.is_named(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::Engine;
+
+    #[test]
+    fn registry_finds_methods_by_name_ignoring_case() {
+        assert!(search_methods().iter().any(|method| method.name() == DEFAULT_SEARCH_METHOD));
+        assert_eq!(search_method("alphabeta").map(|method| method.name()), Some("AlphaBeta"));
+        assert!(search_method("minimax").is_none());
+
+        let mut engine = Engine::new(2);
+        assert_eq!(engine.search_method().name(), DEFAULT_SEARCH_METHOD);
+        assert!(!engine.set_search_method("minimax"));
+        assert!(engine.set_search_method(" ALPHABETA "));
+        assert_eq!(engine.search_method().name(), "AlphaBeta");
+    }
+
+    #[test]
+    fn alpha_beta_method_matches_the_engine_search() {
+        let position = Position::startpos();
+        let mut engine = Engine::new(3);
+        engine.set_deterministic_multithread(false);
+        let candidates = engine.root_candidates(&position);
+        let nodes = engine.searched_nodes();
+
+        let mut fresh = Engine::new(3);
+        fresh.set_deterministic_multithread(false);
+        let history = [position.zobrist_key()];
+        let input = SearchInput {
+            position: &position,
+            history: &history,
+            limits: SearchLimits { max_depth: 3, control: Arc::new(SearchControl::new(None)) },
+            settings: fresh.settings(),
+            exact_root_scores: false,
+            options: &MethodOptions::default(),
+            tt: Arc::new(TranspositionTable::new(fresh.settings().hash_mb)),
+        };
+        let mut depths = Vec::new();
+        let output = AlphaBeta.search(&input, &mut |info| depths.push(info.depth));
+        assert_eq!(output.candidates, candidates);
+        assert_eq!(output.stats.nodes, nodes);
+        assert_eq!(output.stats.depth, 3);
+        assert!(output.stats.seldepth >= 3);
+        assert_eq!(depths, [1, 2, 3]);
+    }
+
+    #[test]
+    fn method_options_are_found_by_name_and_kept_in_range() {
+        let option = method_option("hybrid_tactical_weight").unwrap();
+        assert_eq!(option.name, "HybridTacticalWeight");
+        assert!(method_option("NoSuchOption").is_none());
+
+        let mut options = MethodOptions::default();
+        assert_eq!(options.spin(option), 1);
+        assert!(options.set("HybridTacticalWeight", "50"));
+        assert_eq!(options.spin(option), 10);
+        assert!(!options.set("HybridTacticalWeight", "many"));
+        assert!(options.set("hybridrule", "VOTE"));
+        assert_eq!(options.get(method_option("HybridRule").unwrap()), "Vote");
+        assert!(!options.set("HybridRule", "Median"));
+        assert_eq!(
+            method_option("HybridRule").unwrap().uci_declaration("Vote"),
+            "option name HybridRule type combo default Vote var Weighted var Vote"
+        );
+    }
+}
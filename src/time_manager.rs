@@ -0,0 +1,137 @@
+//! Turns the limits of a UCI `go` command into search deadlines.
+//!
+//! The soft deadline is checked between iterative-deepening depths: once it
+//! has passed, no new depth is started. The hard deadline is checked inside
+//! the search, and the running depth is abandoned when it passes, so the move
+//! comes from the last completed depth. Both are measured from the start of
+//! the search and already have the move overhead taken off.
+
+use std::time::Duration;
+
+use crate::chess::Color;
+
+pub const DEFAULT_MOVE_OVERHEAD_MS: u64 = 30;
+
+/// Moves left in the time control when the GUI does not send `movestogo`.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GoLimits {
+    pub depth: Option<u8>,
+    pub movetime_ms: Option<u64>,
+    pub wtime_ms: Option<u64>,
+    pub btime_ms: Option<u64>,
+    pub winc_ms: u64,
+    pub binc_ms: u64,
+    pub movestogo: Option<u64>,
+    /// Search until `stop`.
+    pub infinite: bool,
+    /// Search the expected reply until `ponderhit` or `stop`.
+    pub ponder: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchDeadlines {
+    pub soft: Duration,
+    pub hard: Duration,
+}
+
+impl GoLimits {
+    /// Reads the arguments after `go`. Unknown tokens are skipped, and
+    /// negative clock values, which some GUIs send after a flag, count as 0.
+    pub fn parse(args: &str) -> Self {
+        let tokens: Vec<&str> = args.split_whitespace().collect();
+        let mut limits = Self {
+            infinite: tokens.contains(&"infinite"),
+            ponder: tokens.contains(&"ponder"),
+            ..Self::default()
+        };
+        for window in tokens.windows(2) {
+            let millis = || window[1].parse::<i64>().ok().map(|value| value.max(0) as u64);
+            match window[0] {
+                "depth" => limits.depth = window[1].parse::<u8>().ok(),
+                "movetime" => limits.movetime_ms = millis(),
+                "wtime" => limits.wtime_ms = millis(),
+                "btime" => limits.btime_ms = millis(),
+                "winc" => limits.winc_ms = millis().unwrap_or(0),
+                "binc" => limits.binc_ms = millis().unwrap_or(0),
+                "movestogo" => limits.movestogo = millis(),
+                _ => {}
+            }
+        }
+        limits
+    }
+
+    /// Deadlines for `side`, or `None` when the command has no time limit.
+    /// `movetime` wins over the clock when both are present.
+    pub fn deadlines(&self, side: Color, move_overhead_ms: u64) -> Option<SearchDeadlines> {
+        if let Some(movetime_ms) = self.movetime_ms {
+            let budget = Duration::from_millis(movetime_ms.saturating_sub(move_overhead_ms).max(1));
+            return Some(SearchDeadlines { soft: budget, hard: budget });
+        }
+        let (time_ms, increment_ms) = match side {
+            Color::White => (self.wtime_ms?, self.winc_ms),
+            Color::Black => (self.btime_ms?, self.binc_ms),
+        };
+        let available = time_ms.saturating_sub(move_overhead_ms);
+        let moves_to_go = self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, 50);
+        let base = available / moves_to_go + increment_ms * 3 / 4;
+        let hard = (base * 3).min(available * 3 / 4).max(1);
+        let soft = (base / 2).clamp(1, hard);
+        Some(SearchDeadlines { soft: Duration::from_millis(soft), hard: Duration::from_millis(hard) })
+    }
+
+    pub fn has_clock(&self) -> bool {
+        self.movetime_ms.is_some() || self.wtime_ms.is_some() || self.btime_ms.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_clock_and_depth_tokens() {
+        let limits = GoLimits::parse(" wtime 60000 btime -20 winc 1000 binc 500 movestogo 12 depth 5");
+        assert_eq!(
+            limits,
+            GoLimits {
+                depth: Some(5),
+                movetime_ms: None,
+                wtime_ms: Some(60_000),
+                btime_ms: Some(0),
+                winc_ms: 1000,
+                binc_ms: 500,
+                movestogo: Some(12),
+                infinite: false,
+                ponder: false,
+            }
+        );
+        let ponder = GoLimits::parse(" ponder wtime 1000 btime 1000");
+        assert!(ponder.ponder && !ponder.infinite && ponder.has_clock());
+        assert!(GoLimits::parse(" infinite").infinite);
+        assert!(limits.has_clock());
+        assert!(!GoLimits::parse(" depth 3").has_clock());
+    }
+
+    #[test]
+    fn deadlines_stay_inside_the_clock() {
+        let movetime = GoLimits::parse("movetime 1000 wtime 5000");
+        let deadlines = movetime.deadlines(Color::White, 30).unwrap();
+        assert_eq!((deadlines.soft, deadlines.hard), (Duration::from_millis(970), Duration::from_millis(970)));
+
+        let sudden_death = GoLimits::parse("wtime 60000 btime 1000 winc 1000 binc 0");
+        let white = sudden_death.deadlines(Color::White, 0).unwrap();
+        assert_eq!(white.soft, Duration::from_millis((2000 + 750) / 2));
+        assert_eq!(white.hard, Duration::from_millis((2000 + 750) * 3));
+        let black = sudden_death.deadlines(Color::Black, 50).unwrap();
+        assert!(black.hard <= Duration::from_millis(950 * 3 / 4));
+        assert!(black.soft <= black.hard);
+
+        let last_move = GoLimits::parse("wtime 10000 movestogo 1").deadlines(Color::White, 100).unwrap();
+        assert_eq!(last_move.hard, Duration::from_millis(9900 * 3 / 4));
+
+        assert_eq!(GoLimits::parse("depth 4").deadlines(Color::White, 30), None);
+        assert_eq!(GoLimits::parse("wtime 1000").deadlines(Color::Black, 30), None);
+    }
+}
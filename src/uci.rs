@@ -1,8 +1,18 @@
 use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
-use crate::chess::{ChessMove, Position, STARTPOS_FEN};
+use crate::chess::{ChessMove, Game, Position};
 use crate::experience::{ExperienceBook, ExperienceConfig};
-use crate::search::{evaluate_for_side_to_move, mate_score_to_uci_moves, Engine, RootCandidate, SearchSettings};
+use crate::perft::{perft, PerftSettings};
+use crate::search::{
+    evaluate_for_side_to_move, mate_score_to_uci_moves, Engine, RootCandidate, SearchControl, SearchInfo,
+    SearchSettings, MAX_EXTENSION_BUDGET, MAX_MULTI_PV, MAX_SEARCH_DEPTH,
+};
+use crate::search_method::search_methods;
+use crate::time_manager::{GoLimits, SearchDeadlines, DEFAULT_MOVE_OVERHEAD_MS};
 
 pub fn run() {
     let stdin = io::stdin();
@@ -10,6 +20,9 @@ pub fn run() {
     let mut state = UciPositionState::startpos();
     let mut engine = Engine::new(4);
     let mut experience = ExperienceConfig::default();
+    let mut chess960 = false;
+    let mut move_overhead_ms = DEFAULT_MOVE_OVERHEAD_MS;
+    let mut search: Option<RunningSearch> = None;
 
     for line in stdin.lock().lines() {
         let Ok(line) = line else {
@@ -19,12 +32,23 @@ pub fn run() {
         if line.is_empty() {
             continue;
         }
+        // `stop` and `quit` end the running search. Other commands wait for
+        // its `bestmove`, so they never see a half-finished search.
+        if !matches!(line, "isready" | "ponderhit" | "uci") {
+            if let Some(running) = search.take() {
+                if matches!(line, "stop" | "quit") {
+                    running.finish();
+                } else {
+                    running.wait();
+                }
+            }
+        }
 
         if line == "uci" {
             println!("id name rchess-reborn 0.4.0");
             println!("id author ReD_Chajek project");
             let settings = engine.settings();
-            println!("option name Depth type spin default 4 min 1 max 8");
+            println!("option name Depth type spin default 4 min 1 max {MAX_SEARCH_DEPTH}");
             println!(
                 "option name deterministic_multithread type check default {}",
                 settings.deterministic_multithread
@@ -32,6 +56,39 @@ pub fn run() {
             println!("option name max_threads type spin default {} min 1 max 64", settings.max_threads);
             println!("option name granularity type spin default 1 min 1 max 64");
             println!("option name Hash type spin default 64 min 1 max 4096");
+            println!("option name Clear Hash type button");
+            println!("option name MultiPV type spin default 1 min 1 max {MAX_MULTI_PV}");
+            println!("option name KillerMoves type check default {}", settings.killer_moves);
+            println!("option name HistoryHeuristic type check default {}", settings.history_heuristic);
+            println!("option name CounterMoves type check default {}", settings.counter_moves);
+            println!("option name PrincipalVariationSearch type check default {}", settings.principal_variation_search);
+            println!("option name NullMovePruning type check default {}", settings.null_move_pruning);
+            println!("option name LateMoveReductions type check default {}", settings.late_move_reductions);
+            println!("option name ReverseFutilityPruning type check default {}", settings.reverse_futility_pruning);
+            println!("option name FutilityPruning type check default {}", settings.futility_pruning);
+            println!("option name Razoring type check default {}", settings.razoring);
+            println!("option name CheckExtensions type check default {}", settings.check_extensions);
+            println!("option name OneReplyExtensions type check default {}", settings.one_reply_extensions);
+            println!("option name RecaptureExtensions type check default {}", settings.recapture_extensions);
+            println!("option name PassedPawnExtensions type check default {}", settings.passed_pawn_extensions);
+            println!(
+                "option name ExtensionBudget type spin default {} min 0 max {MAX_EXTENSION_BUDGET}",
+                settings.extension_budget
+            );
+            println!("option name QuiescenceSeePruning type check default {}", settings.quiescence_see_pruning);
+            println!("option name DeltaPruning type check default {}", settings.delta_pruning);
+            println!("option name QuiescencePromotions type check default {}", settings.quiescence_promotions);
+            println!("option name QuiescenceChecks type check default {}", settings.quiescence_checks);
+            println!("option name QuiescenceEvasions type check default {}", settings.quiescence_evasions);
+            let methods: Vec<&str> = search_methods().iter().map(|method| method.name()).collect();
+            println!(
+                "option name SearchMethod type combo default {} var {}",
+                engine.search_method().name(),
+                methods.join(" var ")
+            );
+            for option in search_methods().iter().flat_map(|method| method.options()) {
+                println!("{}", option.uci_declaration(&engine.method_options().get(option)));
+            }
             println!("option name UseExperienceBook type check default false");
             println!("option name ExperienceBookPath type string default rchess_experience.rxp");
             println!("option name ExperienceMinGames type spin default 1 min 1 max 10000");
@@ -40,80 +97,180 @@ pub fn run() {
             println!("option name DrawContemptCp type spin default 35 min 0 max 400");
             println!("option name RiskLevel type spin default 0 min -100 max 100");
             println!("option name HumanityLevel type spin default 0 min -100 max 100");
+            println!("option name UCI_Chess960 type check default false");
+            println!("option name MoveOverhead type spin default {DEFAULT_MOVE_OVERHEAD_MS} min 0 max 5000");
+            println!("option name Ponder type check default false");
             println!("uciok");
         } else if line == "isready" {
             println!("readyok");
         } else if line == "ucinewgame" {
             state = UciPositionState::startpos();
         } else if let Some(rest) = line.strip_prefix("setoption ") {
-            handle_setoption(rest, &mut engine, &mut experience);
+            if let Some(enabled) = parse_chess960_setoption(rest) {
+                chess960 = enabled;
+            } else if let Some(overhead_ms) = parse_move_overhead_setoption(rest) {
+                move_overhead_ms = overhead_ms;
+            } else {
+                handle_setoption(rest, &mut engine, &mut experience);
+            }
         } else if let Some(rest) = line.strip_prefix("position ") {
-            match parse_position_command(rest) {
+            match parse_position_command(rest, chess960) {
                 Ok(next_state) => state = next_state,
                 Err(error) => eprintln!("info string position error: {error}"),
             }
         } else if let Some(rest) = line.strip_prefix("go") {
-            let depth = parse_go_depth(rest).unwrap_or_else(|| parse_go_movetime_depth(rest).unwrap_or(4));
-            engine.set_depth(depth);
-            let settings = engine.settings();
-            let best = search_best_move(&mut engine, &state, &experience);
-            match best {
-                Some((chess_move, score, experience_note)) => {
-                    println!(
-                        "info depth {depth} {} nodes {} hashfull 0 string deterministic_multithread={} max_threads={} granularity={} hash_mb={} risk_level={} humanity_level={}",
-                        format_uci_score(score),
-                        engine.searched_nodes(),
-                        settings.deterministic_multithread,
-                        settings.max_threads,
-                        settings.granularity,
-                        settings.hash_mb,
-                        settings.risk_level,
-                        settings.humanity_level
-                    );
-                    if let Some(note) = experience_note {
-                        println!("info string {note}");
-                    }
-                    println!("bestmove {}", chess_move.to_uci());
-                }
-                None => {
-                    if state.position.is_checkmate() {
-                        println!("info depth {depth} score mate -1 nodes {} string terminal checkmate", engine.searched_nodes());
-                    } else {
-                        println!("info depth {depth} score cp 0 nodes {} string terminal stalemate-or-no-move", engine.searched_nodes());
-                    }
-                    println!("bestmove 0000");
-                }
+            let limits = GoLimits::parse(rest);
+            let deadlines = limits.deadlines(state.position().side_to_move(), move_overhead_ms);
+            engine.set_depth(go_max_depth(&limits, deadlines));
+            search = Some(RunningSearch::start(&engine, &state, &experience, limits, deadlines));
+        } else if line == "ponderhit" {
+            if let Some(running) = &search {
+                running.ponderhit();
             }
         } else if let Some(rest) = line.strip_prefix("perft ") {
             let depth = rest.trim().parse::<u32>().unwrap_or(1);
-            println!("nodes {}", state.position.perft(depth));
+            println!("nodes {}", perft(state.position(), depth, PerftSettings::from_search(engine.settings())));
         } else if line == "d" {
-            println!("{}", state.position.ascii_board());
-            println!("Fen: {}", state.position.to_fen());
-        } else if line == "stop" {
-            continue;
+            println!("{}", state.position().ascii_board());
+            println!("Fen: {}", state.position().to_fen());
         } else if line == "quit" {
             break;
         }
 
         let _ = stdout.flush();
     }
+    if let Some(running) = search.take() {
+        running.finish();
+    }
+}
+
+/// A `go` command running on its own thread. The engine handed to the
+/// thread is a clone that shares the transposition table.
+struct RunningSearch {
+    handle: thread::JoinHandle<()>,
+    control: Arc<SearchControl>,
+    /// Set for `go infinite` and `go ponder`: `bestmove` waits for `stop`, or
+    /// for `ponderhit` when pondering.
+    hold: Arc<AtomicBool>,
+    /// Clock deadlines that start counting on `ponderhit`.
+    ponder_deadlines: Option<SearchDeadlines>,
+}
+
+impl RunningSearch {
+    fn start(
+        engine: &Engine,
+        state: &UciPositionState,
+        experience: &ExperienceConfig,
+        limits: GoLimits,
+        deadlines: Option<SearchDeadlines>,
+    ) -> Self {
+        let control = Arc::new(SearchControl::new(if limits.ponder { None } else { deadlines }));
+        let hold = Arc::new(AtomicBool::new(limits.infinite || limits.ponder));
+        let mut engine = engine.clone();
+        engine.set_control(control.clone());
+        let state = state.clone();
+        let experience = experience.clone();
+        let thread_control = control.clone();
+        let thread_hold = hold.clone();
+        let handle = thread::spawn(move || {
+            search_and_report(&mut engine, &state, &experience, &thread_control, &thread_hold);
+        });
+        Self { handle, control, hold, ponder_deadlines: limits.ponder.then_some(deadlines).flatten() }
+    }
+
+    /// The guessed move was played: keep searching on the real clock.
+    fn ponderhit(&self) {
+        self.control.start_clock(self.ponder_deadlines);
+        self.hold.store(false, Ordering::Relaxed);
+    }
+
+    /// Stops the search and waits for its `bestmove`.
+    fn finish(self) {
+        self.control.stop();
+        let _ = self.handle.join();
+    }
+
+    /// Lets a timed or depth-limited search run to its end. Infinite and
+    /// pondering searches have no end of their own and are stopped.
+    fn wait(self) {
+        if self.hold.load(Ordering::Relaxed) {
+            self.control.stop();
+        }
+        let _ = self.handle.join();
+    }
+}
+
+fn search_and_report(
+    engine: &mut Engine,
+    state: &UciPositionState,
+    experience: &ExperienceConfig,
+    control: &SearchControl,
+    hold: &AtomicBool,
+) {
+    let settings = engine.settings();
+    let mut depth = 0;
+    let mut pv = Vec::new();
+    let best = search_best_move(engine, state, experience, |info| {
+        if info.multipv == 1 {
+            depth = info.depth;
+            pv = info.pv.clone();
+        }
+        println!("{}", format_search_info(info));
+        let _ = io::stdout().flush();
+    });
+    while hold.load(Ordering::Relaxed) && !control.is_stopped() {
+        thread::sleep(Duration::from_millis(1));
+    }
+    match best {
+        Some((chess_move, score, experience_note)) => {
+            println!(
+                "info depth {depth} {} nodes {} hashfull {} string deterministic_multithread={} max_threads={} granularity={} hash_mb={} risk_level={} humanity_level={} method={}",
+                format_uci_score(score),
+                engine.searched_nodes(),
+                engine.hashfull(),
+                settings.deterministic_multithread,
+                settings.max_threads,
+                settings.granularity,
+                settings.hash_mb,
+                settings.risk_level,
+                settings.humanity_level,
+                engine.search_method().name()
+            );
+            for note in engine.search_notes() {
+                println!("info string {note}");
+            }
+            if let Some(note) = experience_note {
+                println!("info string {note}");
+            }
+            match pv.get(1).filter(|_| pv.first() == Some(&chess_move)) {
+                Some(reply) => println!("bestmove {} ponder {}", chess_move.to_uci(), reply.to_uci()),
+                None => println!("bestmove {}", chess_move.to_uci()),
+            }
+        }
+        None => {
+            if state.position().is_checkmate() {
+                println!("info depth {depth} score mate -1 nodes {} string terminal checkmate", engine.searched_nodes());
+            } else {
+                println!("info depth {depth} score cp 0 nodes {} string terminal stalemate-or-no-move", engine.searched_nodes());
+            }
+            println!("bestmove 0000");
+        }
+    }
+    let _ = io::stdout().flush();
 }
 
 #[derive(Clone, Debug)]
 struct UciPositionState {
-    start_fen: String,
-    position: Position,
-    moves: Vec<ChessMove>,
+    game: Game,
 }
 
 impl UciPositionState {
     fn startpos() -> Self {
-        Self {
-            start_fen: STARTPOS_FEN.to_string(),
-            position: Position::startpos(),
-            moves: Vec::new(),
-        }
+        Self { game: Game::new(Position::startpos()) }
+    }
+
+    fn position(&self) -> &Position {
+        self.game.position()
     }
 }
 
@@ -121,10 +278,13 @@ fn search_best_move(
     engine: &mut Engine,
     state: &UciPositionState,
     experience: &ExperienceConfig,
+    report: impl FnMut(&SearchInfo),
 ) -> Option<(crate::chess::ChessMove, i32, Option<String>)> {
-    let candidates = engine.root_candidates(&state.position);
-    let best = candidates.first().copied()?;
     let config = experience.clone().normalized();
+    engine.set_exact_root_scores(config.enabled);
+    engine.set_game_history(state.game.keys_since_irreversible());
+    let candidates = engine.root_candidates_with_info(state.position(), report);
+    let best = candidates.first().cloned()?;
     let mut selected_move = best.chess_move;
     let mut selected_score = best.score;
     let mut note = None;
@@ -132,7 +292,7 @@ fn search_best_move(
     if config.enabled {
         match ExperienceBook::load_from_path(&config.path) {
             Ok(book) => {
-                if let Some(decision) = book.choose_move(&state.position, &candidates, config.min_games, config.score_tolerance_cp) {
+                if let Some(decision) = book.choose_move(state.position(), &candidates, config.min_games, config.score_tolerance_cp) {
                     selected_move = decision.chosen_move;
                     selected_score = decision.chosen_score;
                     note = Some(decision.uci_info());
@@ -169,6 +329,8 @@ fn append_info_note(note: &mut Option<String>, extra: String) {
     }
 }
 
+/// The search already scores repetitions with the draw contempt. This still
+/// swaps a selected move that draws for a close non-drawing candidate.
 fn choose_avoid_draw_move(
     settings: SearchSettings,
     state: &UciPositionState,
@@ -176,10 +338,11 @@ fn choose_avoid_draw_move(
     selected_move: ChessMove,
     selected_score: i32,
 ) -> Option<(ChessMove, i32, String)> {
-    if !settings.avoid_draws || evaluate_for_side_to_move(&state.position) <= -150 {
+    if !settings.avoid_draws || evaluate_for_side_to_move(state.position()) <= -150 {
         return None;
     }
-    if !candidate_has_draw_risk(state, selected_move) {
+    let mut game = state.game.clone();
+    if !candidate_has_draw_risk(&mut game, selected_move) {
         return None;
     }
     let tolerance = (settings.draw_contempt_cp.max(35) * 4).clamp(80, 220);
@@ -188,7 +351,7 @@ fn choose_avoid_draw_move(
         if candidate.score < score_floor {
             continue;
         }
-        if !candidate_has_draw_risk(state, candidate.chess_move) {
+        if !candidate_has_draw_risk(&mut game, candidate.chess_move) {
             return Some((
                 candidate.chess_move,
                 candidate.score,
@@ -204,29 +367,29 @@ fn choose_avoid_draw_move(
     None
 }
 
-fn candidate_has_draw_risk(state: &UciPositionState, chess_move: ChessMove) -> bool {
-    let mut moves = state.moves.clone();
-    moves.push(chess_move);
-    if Position::draw_reason_from_history(&state.start_fen, &moves)
-        .ok()
-        .flatten()
-        .is_some()
-    {
-        return true;
-    }
-    Position::repetition_count_from_history(&state.start_fen, &moves)
-        .map(|count| count >= 2)
-        .unwrap_or(false)
+/// Whether `chess_move` draws at once or repeats a position. `game` is left as
+/// it was.
+fn candidate_has_draw_risk(game: &mut Game, chess_move: ChessMove) -> bool {
+    if game.push(chess_move).is_err() {
+        return false;
+    }
+    let risk = game.draw_reason().is_some() || game.repetition_count() >= 2;
+    game.pop();
+    risk
 }
 
 fn handle_setoption(rest: &str, engine: &mut Engine, experience: &mut ExperienceConfig) {
+    if parse_button_setoption(rest).as_deref() == Some("clear_hash") {
+        engine.clear_hash();
+        return;
+    }
     let Some((name, value)) = parse_setoption_name_value(rest) else {
         return;
     };
     match normalize_option_name(&name).as_str() {
         "depth" => {
             if let Ok(depth) = value.parse::<u8>() {
-                engine.set_depth(depth.clamp(1, 8));
+                engine.set_depth(depth.clamp(1, MAX_SEARCH_DEPTH));
             }
         }
         "deterministic_multithread" => {
@@ -248,13 +411,46 @@ fn handle_setoption(rest: &str, engine: &mut Engine, experience: &mut Experience
                 engine.set_hash_mb(hash_mb);
             }
         }
+        "multipv" | "multi_pv" => {
+            if let Ok(lines) = value.parse::<usize>() {
+                engine.set_multi_pv(lines);
+            }
+        }
+        "killermoves" | "killer_moves" => engine.set_killer_moves(parse_uci_bool(&value)),
+        "historyheuristic" | "history_heuristic" => engine.set_history_heuristic(parse_uci_bool(&value)),
+        "countermoves" | "counter_moves" => engine.set_counter_moves(parse_uci_bool(&value)),
+        "principalvariationsearch" | "principal_variation_search" | "pvs" => {
+            engine.set_principal_variation_search(parse_uci_bool(&value))
+        }
+        "nullmovepruning" | "null_move_pruning" => engine.set_null_move_pruning(parse_uci_bool(&value)),
+        "latemovereductions" | "late_move_reductions" => engine.set_late_move_reductions(parse_uci_bool(&value)),
+        "reversefutilitypruning" | "reverse_futility_pruning" => {
+            engine.set_reverse_futility_pruning(parse_uci_bool(&value))
+        }
+        "futilitypruning" | "futility_pruning" => engine.set_futility_pruning(parse_uci_bool(&value)),
+        "razoring" => engine.set_razoring(parse_uci_bool(&value)),
+        "searchmethod" | "search_method" => {
+            engine.set_search_method(&value);
+        }
+        "checkextensions" | "check_extensions" => engine.set_check_extensions(parse_uci_bool(&value)),
+        "onereplyextensions" | "one_reply_extensions" => engine.set_one_reply_extensions(parse_uci_bool(&value)),
+        "recaptureextensions" | "recapture_extensions" => engine.set_recapture_extensions(parse_uci_bool(&value)),
+        "passedpawnextensions" | "passed_pawn_extensions" => engine.set_passed_pawn_extensions(parse_uci_bool(&value)),
+        "extensionbudget" | "extension_budget" => {
+            if let Ok(plies) = value.parse::<u8>() {
+                engine.set_extension_budget(plies);
+            }
+        }
+        "quiescenceseepruning" | "quiescence_see_pruning" => engine.set_quiescence_see_pruning(parse_uci_bool(&value)),
+        "deltapruning" | "delta_pruning" => engine.set_delta_pruning(parse_uci_bool(&value)),
+        "quiescencepromotions" | "quiescence_promotions" => engine.set_quiescence_promotions(parse_uci_bool(&value)),
+        "quiescencechecks" | "quiescence_checks" => engine.set_quiescence_checks(parse_uci_bool(&value)),
+        "quiescenceevasions" | "quiescence_evasions" => engine.set_quiescence_evasions(parse_uci_bool(&value)),
         "useexperiencebook" | "use_experience_book" | "experience_book" => {
             experience.enabled = matches!(value.to_ascii_lowercase().as_str(), "true" | "1" | "yes" | "on");
         }
-        "experiencebookpath" | "experience_book_path" => {
-            if !value.trim().is_empty() {
-                experience.path = value.trim().to_string();
-            }
+        "experiencebookpath" | "experience_book_path" if !value.trim().is_empty() => {
+            experience.path = value.trim().to_string();
         }
         "experiencemingames" | "experience_min_games" => {
             if let Ok(min_games) = value.parse::<u32>() {
@@ -285,10 +481,42 @@ fn handle_setoption(rest: &str, engine: &mut Engine, experience: &mut Experience
                 engine.set_humanity_level(value);
             }
         }
-        _ => {}
+        _ => {
+            engine.set_method_option(&name, &value);
+        }
     }
 }
 
+/// `UCI_Chess960` changes how positions and castling moves are read, so it is
+/// kept next to the position state instead of inside `Engine`.
+fn parse_chess960_setoption(rest: &str) -> Option<bool> {
+    let (name, value) = parse_setoption_name_value(rest)?;
+    (normalize_option_name(&name) == "uci_chess960")
+        .then(|| matches!(value.to_ascii_lowercase().as_str(), "true" | "1" | "yes" | "on"))
+}
+
+/// Depth limit of a `go` command. Without `depth`, only a search that ends
+/// on its own clock or waits for `stop` may go as deep as it can; a clock
+/// that belongs to the other side alone is no limit, so the default depth
+/// applies then.
+fn go_max_depth(limits: &GoLimits, deadlines: Option<SearchDeadlines>) -> u8 {
+    match limits.depth {
+        Some(depth) => depth.clamp(1, MAX_SEARCH_DEPTH),
+        None if deadlines.is_some() || limits.infinite => MAX_SEARCH_DEPTH,
+        None => 4,
+    }
+}
+
+/// `MoveOverhead` only changes how `go` clocks become deadlines, so it is
+/// kept in the UCI loop as well.
+fn parse_move_overhead_setoption(rest: &str) -> Option<u64> {
+    let (name, value) = parse_setoption_name_value(rest)?;
+    if !matches!(normalize_option_name(&name).as_str(), "moveoverhead" | "move_overhead") {
+        return None;
+    }
+    Some(value.parse::<u64>().unwrap_or(DEFAULT_MOVE_OVERHEAD_MS).min(5000))
+}
+
 fn parse_setoption_name_value(rest: &str) -> Option<(String, String)> {
     let tokens: Vec<&str> = rest.split_whitespace().collect();
     if tokens.len() < 4 || !tokens[0].eq_ignore_ascii_case("name") {
@@ -303,40 +531,26 @@ fn parse_setoption_name_value(rest: &str) -> Option<(String, String)> {
     Some((name, value))
 }
 
-fn normalize_option_name(name: &str) -> String {
-    name.trim()
-        .to_ascii_lowercase()
-        .replace([' ', '-'], "_")
+fn parse_uci_bool(value: &str) -> bool {
+    matches!(value.to_ascii_lowercase().as_str(), "true" | "1" | "yes" | "on")
 }
 
-fn parse_go_depth(rest: &str) -> Option<u8> {
+/// Name of a `button` option, which is sent without a `value`.
+fn parse_button_setoption(rest: &str) -> Option<String> {
     let tokens: Vec<&str> = rest.split_whitespace().collect();
-    for window in tokens.windows(2) {
-        if window[0] == "depth" {
-            return window[1].parse::<u8>().ok().map(|depth| depth.clamp(1, 8));
-        }
+    if tokens.len() < 2 || !tokens[0].eq_ignore_ascii_case("name") {
+        return None;
     }
-    None
+    if tokens.iter().any(|token| token.eq_ignore_ascii_case("value")) {
+        return None;
+    }
+    Some(normalize_option_name(&tokens[1..].join(" ")))
 }
 
-fn parse_go_movetime_depth(rest: &str) -> Option<u8> {
-    let tokens: Vec<&str> = rest.split_whitespace().collect();
-    for window in tokens.windows(2) {
-        if window[0] == "movetime" {
-            let movetime_ms = window[1].parse::<u64>().ok()?;
-            return Some(match movetime_ms {
-                0..=25 => 1,
-                26..=100 => 2,
-                101..=350 => 3,
-                351..=1200 => 4,
-                1201..=3500 => 5,
-                3501..=9000 => 6,
-                9001..=20000 => 7,
-                _ => 8,
-            });
-        }
-    }
-    None
+fn normalize_option_name(name: &str) -> String {
+    name.trim()
+        .to_ascii_lowercase()
+        .replace([' ', '-'], "_")
 }
 
 fn format_uci_score(score: i32) -> String {
@@ -347,7 +561,25 @@ fn format_uci_score(score: i32) -> String {
     }
 }
 
-fn parse_position_command(rest: &str) -> Result<UciPositionState, String> {
+fn format_search_info(info: &SearchInfo) -> String {
+    let pv = info.pv.iter().map(|chess_move| chess_move.to_uci()).collect::<Vec<_>>().join(" ");
+    format!(
+        "info depth {} seldepth {} multipv {} {} nodes {} nps {} hashfull {} time {} pv {pv}",
+        info.depth,
+        info.seldepth,
+        info.multipv,
+        format_uci_score(info.score),
+        info.nodes,
+        info.nodes_per_second(),
+        info.hashfull,
+        info.elapsed.as_millis()
+    )
+}
+
+/// With `chess960` set, castling is read and written as king-takes-rook
+/// (`e1h1`). FENs whose castling field or piece placement is only valid in
+/// Chess960 switch it on by themselves.
+fn parse_position_command(rest: &str, chess960: bool) -> Result<UciPositionState, String> {
     let tokens: Vec<&str> = rest.split_whitespace().collect();
     if tokens.is_empty() {
         return Err("empty position command".to_string());
@@ -369,40 +601,56 @@ fn parse_position_command(rest: &str) -> Result<UciPositionState, String> {
         if fen.trim().is_empty() {
             return Err("missing FEN after `position fen`".to_string());
         }
-        Position::from_fen(&fen)?
+        Position::from_fen(&fen).map_err(|error| error.to_string())?
     } else {
         return Err("expected `startpos` or `fen`".to_string());
     };
-    let start_fen = position.to_fen();
-    let mut moves = Vec::new();
+    if chess960 {
+        position.set_chess960(true);
+    }
+    let mut game = Game::new(position);
 
     if let Some(index) = move_index {
         for token in &tokens[index + 1..] {
-            let chess_move = position
-                .parse_uci_move(token)
-                .ok_or_else(|| format!("bad or illegal move: {token}"))?;
-            position.make_legal_move(chess_move)?;
-            moves.push(chess_move);
+            game.push_uci(token).map_err(|error| format!("bad or illegal move {token}: {error}"))?;
         }
     }
-    Ok(UciPositionState { start_fen, position, moves })
+    Ok(UciPositionState { game })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chess::{Color, STARTPOS_FEN};
 
     #[test]
     fn parses_startpos_with_moves() {
-        let state = parse_position_command("startpos moves e2e4 e7e5").unwrap();
-        assert_eq!(state.position.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        let state = parse_position_command("startpos moves e2e4 e7e5", false).unwrap();
+        assert_eq!(state.position().to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
     }
 
     #[test]
     fn parses_fen() {
-        let state = parse_position_command(&format!("fen {STARTPOS_FEN}")).unwrap();
-        assert_eq!(state.position.to_fen(), STARTPOS_FEN);
-        assert_eq!(state.start_fen, STARTPOS_FEN);
+        let state = parse_position_command(&format!("fen {STARTPOS_FEN}"), false).unwrap();
+        assert_eq!(state.position().to_fen(), STARTPOS_FEN);
+        assert_eq!(state.game.start_fen(), STARTPOS_FEN);
+    }
+
+    #[test]
+    fn parses_chess960_castling_as_king_takes_rook() {
+        assert_eq!(parse_chess960_setoption("name UCI_Chess960 value true"), Some(true));
+        assert_eq!(parse_chess960_setoption("name Hash value 8"), None);
+
+        let state = parse_position_command("startpos moves g1f3 g8f6 e2e3 e7e6 f1e2 f8e7 e1h1", true).unwrap();
+        assert!(state.position().is_chess960());
+        assert_eq!(state.game.start_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+        assert_eq!(state.game.last_move().unwrap().to_uci(), "e1h1");
+        assert_eq!(state.position().to_fen(), "rnbqk2r/ppppbppp/4pn2/8/8/4PN2/PPPPBPPP/RNBQ1RK1 b ha - 3 4");
+
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        let state = parse_position_command(&format!("fen {fen} moves f4f5"), false).unwrap();
+        assert!(state.position().is_chess960());
+        assert_eq!(state.position().to_fen(), "bqnb1rkr/pp3ppp/3ppn2/2p2P2/8/P2P4/NPP1P1PP/BQ1BNRKR b HFhf - 0 9");
     }
 
     #[test]
@@ -420,6 +668,24 @@ mod tests {
         assert_eq!(settings.hash_mb, 8);
     }
 
+    #[test]
+    fn clear_hash_button_empties_the_table() {
+        let mut engine = Engine::new(3);
+        let mut experience = ExperienceConfig::default();
+        handle_setoption("name Hash value 1", &mut engine, &mut experience);
+        let mut lines = Vec::new();
+        search_best_move(&mut engine, &UciPositionState::startpos(), &experience, |info| {
+            lines.push(format_search_info(info));
+        });
+        assert!(!lines.last().unwrap().contains(" hashfull 0 "));
+        assert!(engine.hashfull() > 0);
+
+        handle_setoption("name Clear Hash value now", &mut engine, &mut experience);
+        assert!(engine.hashfull() > 0);
+        handle_setoption("name Clear Hash", &mut engine, &mut experience);
+        assert_eq!(engine.hashfull(), 0);
+    }
+
     #[test]
     fn parses_experience_setoptions() {
         let mut engine = Engine::new(4);
@@ -445,6 +711,86 @@ mod tests {
         assert_eq!(settings.draw_contempt_cp, 90);
     }
 
+    #[test]
+    fn parses_selective_search_setoptions() {
+        let mut engine = Engine::new(4);
+        let mut experience = ExperienceConfig::default();
+        for name in ["KillerMoves", "HistoryHeuristic", "CounterMoves", "PrincipalVariationSearch", "NullMovePruning"] {
+            handle_setoption(&format!("name {name} value false"), &mut engine, &mut experience);
+        }
+        handle_setoption("name LateMoveReductions value false", &mut engine, &mut experience);
+        handle_setoption("name ReverseFutilityPruning value false", &mut engine, &mut experience);
+        handle_setoption("name FutilityPruning value false", &mut engine, &mut experience);
+        handle_setoption("name Razoring value false", &mut engine, &mut experience);
+        let settings = engine.settings();
+        assert!(!settings.killer_moves && !settings.history_heuristic && !settings.counter_moves);
+        assert!(!settings.principal_variation_search && !settings.null_move_pruning && !settings.late_move_reductions);
+        assert!(!settings.reverse_futility_pruning && !settings.futility_pruning && !settings.razoring);
+        handle_setoption("name Razoring value true", &mut engine, &mut experience);
+        assert!(engine.settings().razoring);
+    }
+
+    #[test]
+    fn parses_search_method_setoption() {
+        let mut engine = Engine::new(4);
+        let mut experience = ExperienceConfig::default();
+        handle_setoption("name SearchMethod value alphabeta", &mut engine, &mut experience);
+        assert_eq!(engine.search_method().name(), "AlphaBeta");
+        handle_setoption("name SearchMethod value NoSuchMethod", &mut engine, &mut experience);
+        assert_eq!(engine.search_method().name(), "AlphaBeta");
+    }
+
+    #[test]
+    fn routes_setoptions_declared_by_search_methods() {
+        let mut engine = Engine::new(4);
+        let mut experience = ExperienceConfig::default();
+        handle_setoption("name SearchMethod value Hybrid", &mut engine, &mut experience);
+        handle_setoption("name HybridRule value vote", &mut engine, &mut experience);
+        handle_setoption("name HybridTacticalWeight value 3", &mut engine, &mut experience);
+        handle_setoption("name hybrid_solid_weight value 0", &mut engine, &mut experience);
+        handle_setoption("name HybridEndgameWeight value 50", &mut engine, &mut experience);
+        assert_eq!(engine.search_method().name(), "Hybrid");
+        let values: Vec<String> = search_methods()
+            .iter()
+            .flat_map(|method| method.options())
+            .map(|option| format!("{}={}", option.name, engine.method_options().get(option)))
+            .collect();
+        assert_eq!(
+            values,
+            ["HybridRule=Vote", "HybridTacticalWeight=3", "HybridSolidWeight=0", "HybridEndgameWeight=10"]
+        );
+    }
+
+    #[test]
+    fn parses_extension_setoptions() {
+        let mut engine = Engine::new(4);
+        let mut experience = ExperienceConfig::default();
+        for name in ["CheckExtensions", "OneReplyExtensions", "RecaptureExtensions", "PassedPawnExtensions"] {
+            handle_setoption(&format!("name {name} value true"), &mut engine, &mut experience);
+        }
+        handle_setoption("name ExtensionBudget value 3", &mut engine, &mut experience);
+        let settings = engine.settings();
+        assert!(settings.check_extensions && settings.one_reply_extensions);
+        assert!(settings.recapture_extensions && settings.passed_pawn_extensions);
+        assert_eq!(settings.extension_budget, 3);
+        handle_setoption("name ExtensionBudget value 200", &mut engine, &mut experience);
+        assert_eq!(engine.settings().extension_budget, MAX_EXTENSION_BUDGET);
+    }
+
+    #[test]
+    fn parses_quiescence_setoptions() {
+        let mut engine = Engine::new(4);
+        let mut experience = ExperienceConfig::default();
+        for name in ["QuiescenceSeePruning", "DeltaPruning", "QuiescencePromotions", "QuiescenceEvasions"] {
+            handle_setoption(&format!("name {name} value false"), &mut engine, &mut experience);
+        }
+        handle_setoption("name QuiescenceChecks value true", &mut engine, &mut experience);
+        let settings = engine.settings();
+        assert!(!settings.quiescence_see_pruning && !settings.delta_pruning);
+        assert!(!settings.quiescence_promotions && !settings.quiescence_evasions);
+        assert!(settings.quiescence_checks);
+    }
+
     #[test]
     fn parses_personality_setoptions() {
         let mut engine = Engine::new(4);
@@ -457,9 +803,65 @@ mod tests {
     }
 
     #[test]
-    fn maps_movetime_to_internal_depth() {
-        assert_eq!(parse_go_movetime_depth("movetime 10"), Some(1));
-        assert_eq!(parse_go_movetime_depth("movetime 1000"), Some(4));
-        assert_eq!(parse_go_movetime_depth("movetime 30000"), Some(8));
+    fn multipv_option_reports_one_info_line_per_rank() {
+        let mut engine = Engine::new(2);
+        let mut experience = ExperienceConfig::default();
+        handle_setoption("name MultiPV value 3", &mut engine, &mut experience);
+        assert_eq!(engine.settings().multi_pv, 3);
+
+        let mut lines = Vec::new();
+        search_best_move(&mut engine, &UciPositionState::startpos(), &experience, |info| {
+            lines.push(format_search_info(info));
+        });
+        assert_eq!(lines.len(), 6);
+        assert!(lines[3].starts_with("info depth 2 seldepth"));
+        for (line, rank) in lines.iter().zip([1, 2, 3, 1, 2, 3]) {
+            assert!(line.contains(&format!(" multipv {rank} score cp ")), "{line}");
+        }
+    }
+
+    #[test]
+    fn stop_ends_infinite_and_ponder_searches() {
+        let engine = Engine::new(MAX_SEARCH_DEPTH);
+        let state = UciPositionState::startpos();
+        let experience = ExperienceConfig::default();
+        for command in ["infinite", "ponder wtime 100000 btime 100000"] {
+            let running = RunningSearch::start(&engine, &state, &experience, GoLimits::parse(command), None);
+            thread::sleep(Duration::from_millis(20));
+            assert!(!running.handle.is_finished(), "{command}");
+            running.finish();
+        }
+
+        let limits = GoLimits::parse("ponder movetime 10");
+        let deadlines = limits.deadlines(state.position().side_to_move(), 0);
+        let running = RunningSearch::start(&engine, &state, &experience, limits, deadlines);
+        running.ponderhit();
+        while !running.handle.is_finished() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        running.finish();
+    }
+
+    #[test]
+    fn go_depth_is_unbounded_only_with_an_own_clock_or_stop() {
+        let depth = |command: &str, side| {
+            let limits = GoLimits::parse(command);
+            go_max_depth(&limits, limits.deadlines(side, 0))
+        };
+        assert_eq!(depth("wtime 1000", Color::White), MAX_SEARCH_DEPTH);
+        assert_eq!(depth("wtime 1000", Color::Black), 4);
+        assert_eq!(depth("wtime 1000 depth 7", Color::Black), 7);
+        assert_eq!(depth("movetime 50", Color::Black), MAX_SEARCH_DEPTH);
+        assert_eq!(depth("infinite", Color::White), MAX_SEARCH_DEPTH);
+        assert_eq!(depth("ponder wtime 1000", Color::White), MAX_SEARCH_DEPTH);
+        assert_eq!(depth("ponder btime 1000", Color::White), 4);
+        assert_eq!(depth("", Color::White), 4);
+    }
+
+    #[test]
+    fn parses_move_overhead_setoption() {
+        assert_eq!(parse_move_overhead_setoption("name MoveOverhead value 120"), Some(120));
+        assert_eq!(parse_move_overhead_setoption("name Move Overhead value 90000"), Some(5000));
+        assert_eq!(parse_move_overhead_setoption("name Hash value 8"), None);
     }
 }
@@ -0,0 +1,108 @@
+//! Zobrist key tables.
+//!
+//! The numbers come from a fixed splitmix64 sequence, so keys are identical
+//! across runs, threads and platforms. That keeps TT behaviour and anything
+//! seeded from a position key reproducible.
+
+struct ZobristKeys {
+    pieces: [[[u64; 64]; 6]; 2],
+    side_to_move: u64,
+    castling: [u64; 4],
+    en_passant_file: [u64; 8],
+    castling_rook_file: [[u64; 8]; 4],
+}
+
+static KEYS: ZobristKeys = build_keys();
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut value = state;
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, value ^ (value >> 31))
+}
+
+const fn build_keys() -> ZobristKeys {
+    let mut keys = ZobristKeys {
+        pieces: [[[0; 64]; 6]; 2],
+        side_to_move: 0,
+        castling: [0; 4],
+        en_passant_file: [0; 8],
+        castling_rook_file: [[0; 8]; 4],
+    };
+    let mut state = 0x7263_6865_7373_u64;
+    let mut color = 0;
+    while color < 2 {
+        let mut kind = 0;
+        while kind < 6 {
+            let mut square = 0;
+            while square < 64 {
+                let (next, value) = splitmix64(state);
+                state = next;
+                keys.pieces[color][kind][square] = value;
+                square += 1;
+            }
+            kind += 1;
+        }
+        color += 1;
+    }
+    let (next, value) = splitmix64(state);
+    state = next;
+    keys.side_to_move = value;
+    let mut right = 0;
+    while right < 4 {
+        let (next, value) = splitmix64(state);
+        state = next;
+        keys.castling[right] = value;
+        right += 1;
+    }
+    let mut file = 0;
+    while file < 8 {
+        let (next, value) = splitmix64(state);
+        state = next;
+        keys.en_passant_file[file] = value;
+        file += 1;
+    }
+    // Drawn after the older keys so those keep their values. The classical
+    // rook file of each right stays zero, leaving classical keys unchanged.
+    let mut right = 0;
+    while right < 4 {
+        let classical_file = if right % 2 == 0 { 7 } else { 0 };
+        let mut file = 0;
+        while file < 8 {
+            let (next, value) = splitmix64(state);
+            state = next;
+            if file != classical_file {
+                keys.castling_rook_file[right][file] = value;
+            }
+            file += 1;
+        }
+        right += 1;
+    }
+    keys
+}
+
+pub fn piece(color_index: usize, kind_index: usize, square: u8) -> u64 {
+    KEYS.pieces[color_index][kind_index][square as usize]
+}
+
+pub fn side_to_move() -> u64 {
+    KEYS.side_to_move
+}
+
+/// Key for one castling right: 0 = white king side, 1 = white queen side,
+/// 2 = black king side, 3 = black queen side.
+pub fn castling(right: usize) -> u64 {
+    KEYS.castling[right]
+}
+
+/// Extra key for the file of the rook a castling right belongs to, so
+/// Chess960 positions that differ only in which rook may castle get
+/// different keys. Zero for the classical a/h file of the right.
+pub fn castling_rook_file(right: usize, file: u8) -> u64 {
+    KEYS.castling_rook_file[right][file as usize]
+}
+
+pub fn en_passant_file(file: i32) -> u64 {
+    KEYS.en_passant_file[file as usize]
+}